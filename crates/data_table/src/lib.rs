@@ -24,12 +24,23 @@ pub struct DataColumn {
     pub tags: Vec<String>,
     pub data_type: Type,
     pub is_primary: bool,
+    /// the columns of the rows holding the values of this column,
+    /// a composite foreign key is edited as one column
+    pub column_names: Vec<String>,
 }
 
 impl DataColumn {
     fn convert(&self, value: &Value) -> Value {
         common::cast_type(value, &self.data_type)
     }
+
+    /// the column which the display, the sort and the search are placed on
+    pub fn first_column_name(&self) -> &str {
+        self.column_names
+            .first()
+            .map(String::as_str)
+            .unwrap_or(&self.name)
+    }
 }
 
 impl DataTable {
@@ -120,6 +131,7 @@ java,8,medium,true,large,jdk
                 description: None,
                 tags: vec![],
                 is_primary: false,
+                column_names: vec!["pl".into()],
             },
             DataColumn {
                 name: "compiler".into(),
@@ -127,6 +139,7 @@ java,8,medium,true,large,jdk
                 description: None,
                 tags: vec![],
                 is_primary: false,
+                column_names: vec!["compiler".into()],
             },
             DataColumn {
                 name: "speed".into(),
@@ -134,6 +147,7 @@ java,8,medium,true,large,jdk
                 description: None,
                 tags: vec![],
                 is_primary: false,
+                column_names: vec!["speed".into()],
             },
             DataColumn {
                 name: "vm".into(),
@@ -141,6 +155,7 @@ java,8,medium,true,large,jdk
                 description: None,
                 tags: vec![],
                 is_primary: false,
+                column_names: vec!["vm".into()],
             },
             DataColumn {
                 name: "size".into(),
@@ -148,6 +163,7 @@ java,8,medium,true,large,jdk
                 description: None,
                 tags: vec![],
                 is_primary: false,
+                column_names: vec!["size".into()],
             },
             DataColumn {
                 name: "version".into(),
@@ -155,6 +171,7 @@ java,8,medium,true,large,jdk
                 description: None,
                 tags: vec![],
                 is_primary: false,
+                column_names: vec!["version".into()],
             },
         ];
         let dataview = DataTable::from_csv(columns, csv);
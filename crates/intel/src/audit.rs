@@ -0,0 +1,163 @@
+//! the audit log of the modifications done through diwata,
+//! stored in a separate database from the one being edited
+//! so it is kept even when the rows are gone
+use crate::{
+    common,
+    data_container::QueryResult,
+    error::IntelError,
+    journal::{
+        Journal,
+        JournalEntry,
+    },
+    window,
+    Context,
+};
+use chrono::Utc;
+use rustorm::{
+    Dao,
+    DaoManager,
+    EntityManager,
+    TableName,
+    Value,
+};
+
+pub const AUDIT_TABLE: &str = "diwata_audit_log";
+
+/// create the audit log table if it does not exist yet
+pub fn ensure_audit_table(dm: &mut DaoManager) -> Result<(), IntelError> {
+    let sql = "CREATE TABLE IF NOT EXISTS diwata_audit_log ( \
+               audit_id INTEGER PRIMARY KEY AUTOINCREMENT, \
+               username TEXT, \
+               created TEXT NOT NULL, \
+               table_name TEXT NOT NULL, \
+               record_id TEXT, \
+               action TEXT NOT NULL, \
+               before TEXT, \
+               after TEXT)";
+    dm.execute_sql_with_return(sql, &[])?;
+    Ok(())
+}
+
+/// write an audit log entry for each of the changes in the journal,
+/// the before and after values are stored as json
+pub fn write_journal(
+    audit_dm: &mut DaoManager,
+    context: &Context,
+    username: Option<&str>,
+    journal: &Journal,
+) -> Result<(), IntelError> {
+    let created = Utc::now().to_rfc3339();
+    let sql = format!(
+        "INSERT INTO {} \
+         (username, created, table_name, record_id, action, before, after) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        AUDIT_TABLE
+    );
+    for entry in journal.entries.iter() {
+        let params = [
+            username
+                .map(|username| Value::Text(username.to_string()))
+                .unwrap_or(Value::Nil),
+            Value::Text(created.clone()),
+            Value::Text(entry.table_name.complete_name()),
+            get_record_id(context, entry)
+                .map(Value::Text)
+                .unwrap_or(Value::Nil),
+            Value::Text(format!("{:?}", entry.action)),
+            to_json(&entry.before),
+            to_json(&entry.after),
+        ];
+        let bparams: Vec<&Value> = params.iter().collect();
+        audit_dm.execute_sql_with_return(&sql, &bparams)?;
+    }
+    Ok(())
+}
+
+/// the record id of the changed row, taken from the row after the change
+/// or before the change if it is deleted
+fn get_record_id(context: &Context, entry: &JournalEntry) -> Option<String> {
+    let table = context.get_table(&entry.table_name)?;
+    let dao = entry.after.as_ref().or_else(|| entry.before.as_ref())?;
+    let mut pk_values = vec![];
+    for pk in table.get_primary_column_names() {
+        pk_values.push(dao.get_value(&pk.name)?);
+    }
+    if pk_values.is_empty() {
+        None
+    } else {
+        Some(common::encode_record_id(&pk_values))
+    }
+}
+
+fn to_json(dao: &Option<Dao>) -> Value {
+    match dao {
+        Some(dao) => {
+            Value::Text(
+                serde_json::to_string(dao).expect("dao must serialize to json"),
+            )
+        }
+        None => Value::Nil,
+    }
+}
+
+/// the audit log of a table is only shown to the users who can read the table,
+/// the privilege is checked on the session user of the dm
+pub fn can_read_table(
+    dm: &mut DaoManager,
+    db_url: &str,
+    table_name: &TableName,
+) -> Result<bool, IntelError> {
+    if !db_url.starts_with("postgres") {
+        return Ok(true);
+    }
+    let sql = "SELECT has_table_privilege($1, 'SELECT') AS can_read";
+    let table_name = Value::Text(table_name.complete_name());
+    let dao = dm.execute_sql_with_one_return(sql, &[&table_name])?;
+    match dao.get_value("can_read") {
+        Some(Value::Bool(can_read)) => Ok(*can_read),
+        _ => Ok(false),
+    }
+}
+
+/// the audit log of the table, or of one record of the table
+/// when the record id is specified, the latest changes first.
+/// The window of the audit log table is included so it can be
+/// displayed like any other window
+pub fn get_audit_log(
+    audit_em: &mut EntityManager,
+    audit_dm: &mut DaoManager,
+    table_name: &TableName,
+    record_id: Option<&str>,
+    page: usize,
+    page_size: usize,
+) -> Result<QueryResult, IntelError> {
+    let mut condition = String::from("WHERE table_name = $1 ");
+    let mut params = vec![Value::Text(table_name.complete_name())];
+    if let Some(record_id) = record_id {
+        condition += "AND record_id = $2 ";
+        params.push(Value::Text(record_id.to_string()));
+    }
+    let bparams: Vec<&Value> = params.iter().collect();
+    let sql = format!(
+        "SELECT * FROM {} {}ORDER BY audit_id DESC LIMIT {} OFFSET {}",
+        AUDIT_TABLE,
+        condition,
+        page_size,
+        common::calc_offset(page, page_size)
+    );
+    let mut rows = audit_dm.execute_sql_with_return(&sql, &bparams)?;
+    let count_sql = format!(
+        "SELECT COUNT(*) AS count FROM {} {}",
+        AUDIT_TABLE, condition
+    );
+    let count = audit_dm.execute_sql_with_one_return(&count_sql, &bparams)?;
+    rows.count = Some(common::get_count(&count, "count"));
+
+    let audit_table_name = TableName::from(AUDIT_TABLE);
+    let tables = audit_em.get_all_tables()?;
+    let windows = window::derive_all_windows(&tables);
+    let audit_window = windows
+        .iter()
+        .find(|window| window.main_tab.table_name == audit_table_name);
+    Ok(QueryResult::with_rows(audit_window, rows))
+}
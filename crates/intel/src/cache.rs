@@ -1,17 +1,31 @@
 use crate::{
     error::IntelError,
+    identifier::IdentifierRegistry,
+    lookup::LookupKey,
+    media::MediaRegistry,
     window::{
         self,
         Window,
     },
+    window_override::{
+        self,
+        WindowOverrides,
+    },
 };
 use lazy_static::lazy_static;
 use rustorm::{
+    ColumnName,
+    DaoManager,
     EntityManager,
+    Rows,
     Table,
+    TableName,
 };
 use std::{
-    collections::BTreeMap,
+    collections::{
+        BTreeMap,
+        HashMap,
+    },
     sync::{
         Arc,
         Mutex,
@@ -64,19 +78,25 @@ impl CachePool {
         }
     }
 
+    /// cache the tables, infer the identifier and the media columns
+    /// of the tables from their records,
+    /// then cache the windows derived from them
     pub fn precache(
         &mut self,
         em: &mut EntityManager,
+        dm: &mut DaoManager,
         db_url: &str,
     ) -> Result<(), IntelError> {
         self.ensure_cache(db_url);
-        self.perform_window_caching(em, db_url)?;
+        self.get_cached_tables(em, dm, db_url)?;
+        self.perform_window_caching(em, dm, db_url)?;
         Ok(())
     }
 
     pub fn get_cached_tables(
         &mut self,
         em: &mut EntityManager,
+        dm: &mut DaoManager,
         db_url: &str,
     ) -> Result<Vec<Table>, IntelError> {
         self.ensure_cache(db_url);
@@ -97,14 +117,15 @@ impl CachePool {
         } else {
             // do a caching and try again
             println!("Performing a TABLE caching and trying again");
-            self.perform_table_caching(em, db_url)?;
-            self.get_cached_tables(em, db_url)
+            self.perform_table_caching(em, dm, db_url)?;
+            self.get_cached_tables(em, dm, db_url)
         }
     }
 
     pub fn get_cached_windows(
         &mut self,
         em: &mut EntityManager,
+        dm: &mut DaoManager,
         db_url: &str,
     ) -> Result<Vec<Window>, IntelError> {
         self.ensure_cache(db_url);
@@ -125,19 +146,129 @@ impl CachePool {
         } else {
             // do a caching and try again
             println!("Performing a WINDOW caching and trying again");
-            self.perform_window_caching(em, db_url)?;
-            self.get_cached_windows(em, db_url)
+            self.perform_window_caching(em, dm, db_url)?;
+            self.get_cached_windows(em, dm, db_url)
+        }
+    }
+
+    /// the identifier displays of the tables on this DB_URL
+    pub fn get_cached_identifiers(&self, db_url: &str) -> IdentifierRegistry {
+        self.0
+            .get(db_url)
+            .map(|cache| cache.identifiers.clone())
+            .unwrap_or_default()
+    }
+
+    /// register the display of the table on this DB_URL,
+    /// the windows that are already cached are derived again
+    pub fn register_display(
+        &mut self,
+        db_url: &str,
+        table_name: &TableName,
+        columns: Vec<ColumnName>,
+        separator: Option<String>,
+    ) {
+        self.ensure_cache(db_url);
+        if let Some(cache) = self.0.get_mut(db_url) {
+            cache.identifiers.register(table_name, columns, separator);
+            cache.windows = None;
+        }
+    }
+
+    /// the media kind of the columns on this DB_URL
+    pub fn get_cached_media(&self, db_url: &str) -> MediaRegistry {
+        self.0
+            .get(db_url)
+            .map(|cache| cache.media.clone())
+            .unwrap_or_default()
+    }
+
+    /// the definitions of the CHECK constraints of the table on this DB_URL
+    pub fn get_cached_check_constraints(
+        &self,
+        db_url: &str,
+        table_name: &TableName,
+    ) -> Option<Vec<String>> {
+        self.0
+            .get(db_url)
+            .and_then(|cache| cache.check_constraints.get(table_name))
+            .cloned()
+    }
+
+    pub fn cache_check_constraints(
+        &mut self,
+        db_url: &str,
+        table_name: &TableName,
+        definitions: Vec<String>,
+    ) {
+        self.ensure_cache(db_url);
+        if let Some(cache) = self.0.get_mut(db_url) {
+            cache
+                .check_constraints
+                .insert(table_name.clone(), definitions);
+        }
+    }
+
+    /// set the overrides applied to the windows derived on this DB_URL,
+    /// the windows that are already cached are derived again
+    pub fn set_window_overrides(
+        &mut self,
+        db_url: &str,
+        overrides: WindowOverrides,
+    ) {
+        self.ensure_cache(db_url);
+        if let Some(cache) = self.0.get_mut(db_url) {
+            cache.window_overrides = Some(overrides);
+            cache.windows = None;
+        }
+    }
+
+    /// the overrides of the windows set on this DB_URL
+    pub fn get_window_overrides(
+        &self,
+        db_url: &str,
+    ) -> Option<WindowOverrides> {
+        self.0
+            .get(db_url)
+            .and_then(|cache| cache.window_overrides.clone())
+    }
+
+    /// the cached page of lookup options on this DB_URL
+    pub fn get_cached_lookup(
+        &self,
+        db_url: &str,
+        key: &LookupKey,
+    ) -> Option<Rows> {
+        self.0
+            .get(db_url)
+            .and_then(|cache| cache.lookups.get(key))
+            .cloned()
+    }
+
+    pub fn cache_lookup(&mut self, db_url: &str, key: LookupKey, rows: Rows) {
+        self.ensure_cache(db_url);
+        if let Some(cache) = self.0.get_mut(db_url) {
+            cache.lookups.insert(key, rows);
+        }
+    }
+
+    /// clear the lookup options cached on all the DB_URL,
+    /// called when records are modified since the users see the same records
+    pub fn clear_lookups(&mut self) {
+        for cache in self.0.values_mut() {
+            cache.lookups.clear();
         }
     }
 
     fn perform_table_caching(
         &mut self,
         em: &mut EntityManager,
+        dm: &mut DaoManager,
         db_url: &str,
     ) -> Result<(), IntelError> {
         let cache = self.0.get_mut(db_url);
         match cache {
-            Some(cache) => cache.perform_table_caching(em),
+            Some(cache) => cache.perform_table_caching(em, dm),
             None => Err(IntelError::CacheServiceError),
         }
     }
@@ -145,11 +276,12 @@ impl CachePool {
     fn perform_window_caching(
         &mut self,
         em: &mut EntityManager,
+        dm: &mut DaoManager,
         db_url: &str,
     ) -> Result<(), IntelError> {
         let cache = self.0.get_mut(db_url);
         match cache {
-            Some(cache) => cache.perform_window_caching(em),
+            Some(cache) => cache.perform_window_caching(em, dm),
             None => Err(IntelError::CacheServiceError),
         }
     }
@@ -162,6 +294,19 @@ pub struct Cache {
     pub windows: Option<Vec<Window>>,
     /// tables extraction is an expensive operation and doesn't change very often
     pub tables: Option<Vec<Table>>,
+    /// the unsearched pages of the lookup options that are already fetched
+    pub lookups: HashMap<LookupKey, Rows>,
+    /// the definitions of the CHECK constraints of each table,
+    /// these are checked for every record that is saved
+    pub check_constraints: HashMap<TableName, Vec<String>>,
+    /// applied over the derived windows before they are cached
+    pub window_overrides: Option<WindowOverrides>,
+    /// the identifier displays registered by the application
+    /// and inferred from the records when the tables are cached
+    pub identifiers: IdentifierRegistry,
+    /// the media kind of the columns inferred from the records
+    /// when the tables are cached
+    pub media: MediaRegistry,
 }
 
 impl Cache {
@@ -169,6 +314,11 @@ impl Cache {
         Cache {
             windows: None,
             tables: None,
+            lookups: HashMap::new(),
+            check_constraints: HashMap::new(),
+            window_overrides: None,
+            identifiers: IdentifierRegistry::default(),
+            media: MediaRegistry::default(),
         }
     }
 
@@ -180,12 +330,17 @@ impl Cache {
         self.windows.is_some()
     }
 
+    /// the identifier and the media columns of the tables
+    /// are inferred from their records at the same time
     fn perform_table_caching(
         &mut self,
         em: &mut EntityManager,
+        dm: &mut DaoManager,
     ) -> Result<(), IntelError> {
         println!("----> ACTUAL TABLE CACHING");
         let tables = em.get_all_tables()?;
+        self.identifiers.infer(dm, &tables);
+        self.media.infer(dm, &tables);
         self.tables = Some(tables);
         Ok(())
     }
@@ -193,16 +348,28 @@ impl Cache {
     fn perform_window_caching(
         &mut self,
         em: &mut EntityManager,
+        dm: &mut DaoManager,
     ) -> Result<(), IntelError> {
         println!("----> ACTUAL WINDOW CACHING");
         match self.tables {
             Some(ref tables) => {
-                self.windows = Some(window::derive_all_windows(&tables));
+                let windows = window::derive_all_windows(&tables);
+                let mut windows = match self.window_overrides {
+                    Some(ref overrides) => {
+                        window_override::apply_overrides(
+                            windows, overrides, tables,
+                        )
+                    }
+                    None => windows,
+                };
+                self.identifiers.apply_to_windows(&mut windows, tables);
+                self.media.apply_to_windows(&mut windows, tables);
+                self.windows = Some(windows);
                 Ok(())
             }
             None => {
-                self.perform_table_caching(em)?;
-                self.perform_window_caching(em)?;
+                self.perform_table_caching(em, dm)?;
+                self.perform_window_caching(em, dm)?;
                 Ok(())
             }
         }
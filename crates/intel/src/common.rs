@@ -1,6 +1,13 @@
 //! provides data service for window
 use crate::error::IntelError;
 use bigdecimal::BigDecimal;
+use chrono::{
+    DateTime,
+    NaiveDate,
+    NaiveDateTime,
+    NaiveTime,
+    Utc,
+};
 use rustorm::{
     common,
     types::SqlType,
@@ -11,6 +18,7 @@ use rustorm::{
 };
 use std::{
     collections::BTreeMap,
+    fmt,
     str::FromStr,
 };
 use uuid::Uuid;
@@ -20,6 +28,51 @@ pub fn calc_offset(page: usize, page_size: usize) -> usize {
     (page - 1) * page_size
 }
 
+/// the values of these types are stored as text
+pub fn is_textual(sql_type: &SqlType) -> bool {
+    match sql_type {
+        SqlType::Char
+        | SqlType::Varchar
+        | SqlType::Tinytext
+        | SqlType::Mediumtext
+        | SqlType::Text
+        | SqlType::Enum(_, _) => true,
+        _ => false,
+    }
+}
+
+/// the text of these types can be parsed with `parse_value`,
+/// the text of the other types is left for the database to parse
+pub fn is_parsable(sql_type: &SqlType) -> bool {
+    match sql_type {
+        SqlType::Bool
+        | SqlType::Tinyint
+        | SqlType::Smallint
+        | SqlType::Int
+        | SqlType::Bigint
+        | SqlType::Real
+        | SqlType::Float
+        | SqlType::Double
+        | SqlType::Numeric
+        | SqlType::Uuid
+        | SqlType::Date
+        | SqlType::Timestamp
+        | SqlType::TimestampTz
+        | SqlType::Time
+        | SqlType::TimeTz => true,
+        _ => is_textual(sql_type),
+    }
+}
+
+/// the value of the count column of the result of a SELECT COUNT(*)
+pub fn get_count(dao: &Dao, column: &str) -> usize {
+    match dao.get_value(column) {
+        Some(Value::Bigint(count)) => *count as usize,
+        Some(Value::Int(count)) => *count as usize,
+        _ => 0,
+    }
+}
+
 pub fn cast_rows(
     rows: Rows,
     column_datatypes: &BTreeMap<String, SqlType>,
@@ -65,73 +118,304 @@ pub fn cast_record(
     new_rec
 }
 
+/// the separator of the primary key values in a record id
+const RECORD_ID_SEPARATOR: char = ',';
+/// escapes the separator when it is part of the primary key value
+const RECORD_ID_ESCAPE: char = '\\';
+
+/// encode the primary key values into a record id,
+/// values are separated by comma, comma and backslash in the value are escaped with backslash
+pub fn encode_record_id(pk_values: &[&Value]) -> String {
+    let mut record_id = String::new();
+    for (i, pk_value) in pk_values.iter().enumerate() {
+        if i > 0 {
+            record_id.push(RECORD_ID_SEPARATOR);
+        }
+        for ch in value_to_record_id_part(pk_value).chars() {
+            if ch == RECORD_ID_SEPARATOR || ch == RECORD_ID_ESCAPE {
+                record_id.push(RECORD_ID_ESCAPE);
+            }
+            record_id.push(ch);
+        }
+    }
+    record_id
+}
+
+/// the string representation of a primary key value in a record id
+fn value_to_record_id_part(value: &Value) -> String {
+    match value {
+        Value::Date(v) => v.format("%Y-%m-%d").to_string(),
+        Value::Timestamp(v) => v.to_rfc3339(),
+        Value::DateTime(v) => v.format("%Y-%m-%dT%H:%M:%S%.f").to_string(),
+        Value::Time(v) => v.format("%H:%M:%S%.f").to_string(),
+        Value::Text(v) => v.to_string(),
+        Value::Char(v) => v.to_string(),
+        Value::Uuid(v) => v.to_string(),
+        Value::BigDecimal(v) => v.to_string(),
+        Value::Bool(v) => v.to_string(),
+        Value::Tinyint(v) => v.to_string(),
+        Value::Smallint(v) => v.to_string(),
+        Value::Int(v) => v.to_string(),
+        Value::Bigint(v) => v.to_string(),
+        Value::Float(v) => v.to_string(),
+        Value::Double(v) => v.to_string(),
+        _ => value.to_string(),
+    }
+}
+
+/// split the record id into its primary key parts, unescaping each part
+fn split_record_id(record_id: &str) -> Vec<String> {
+    let mut splinters = vec![];
+    let mut current = String::new();
+    let mut chars = record_id.chars();
+    while let Some(ch) = chars.next() {
+        if ch == RECORD_ID_ESCAPE {
+            if let Some(escaped) = chars.next() {
+                current.push(escaped);
+            }
+        } else if ch == RECORD_ID_SEPARATOR {
+            splinters.push(current);
+            current = String::new();
+        } else {
+            current.push(ch);
+        }
+    }
+    splinters.push(current);
+    splinters
+}
+
+/// parse the string into a value of this sql type,
+/// the formats postgresql accepts for booleans and timestamps are accepted too
+pub fn parse_value(
+    splinter: &str,
+    sql_type: &SqlType,
+) -> Result<Value, IntelError> {
+    fn parse_error<E: fmt::Display>(
+        sql_type: &SqlType,
+        splinter: &str,
+        e: E,
+    ) -> IntelError {
+        IntelError::ParamParseError(format!(
+            "Invalid for type {:?}: {}, Error: {}",
+            sql_type, splinter, e
+        ))
+    }
+    let value = match *sql_type {
+        SqlType::Bool => {
+            match splinter.to_lowercase().as_str() {
+                "true" | "t" | "1" | "yes" | "y" | "on" => Value::Bool(true),
+                "false" | "f" | "0" | "no" | "n" | "off" => {
+                    Value::Bool(false)
+                }
+                _ => {
+                    return Err(parse_error(sql_type, splinter, "not a bool"))
+                }
+            }
+        }
+        SqlType::Tinyint => {
+            Value::Tinyint(
+                splinter
+                    .parse()
+                    .map_err(|e| parse_error(sql_type, splinter, e))?,
+            )
+        }
+        SqlType::Smallint => {
+            Value::Smallint(
+                splinter
+                    .parse()
+                    .map_err(|e| parse_error(sql_type, splinter, e))?,
+            )
+        }
+        SqlType::Int => {
+            Value::Int(
+                splinter
+                    .parse()
+                    .map_err(|e| parse_error(sql_type, splinter, e))?,
+            )
+        }
+        SqlType::Bigint => {
+            Value::Bigint(
+                splinter
+                    .parse()
+                    .map_err(|e| parse_error(sql_type, splinter, e))?,
+            )
+        }
+        SqlType::Real | SqlType::Float => {
+            Value::Float(
+                splinter
+                    .parse()
+                    .map_err(|e| parse_error(sql_type, splinter, e))?,
+            )
+        }
+        SqlType::Double => {
+            Value::Double(
+                splinter
+                    .parse()
+                    .map_err(|e| parse_error(sql_type, splinter, e))?,
+            )
+        }
+        SqlType::Numeric => {
+            Value::BigDecimal(
+                BigDecimal::from_str(splinter)
+                    .map_err(|e| parse_error(sql_type, splinter, e))?,
+            )
+        }
+        SqlType::Uuid => {
+            Value::Uuid(
+                Uuid::parse_str(splinter)
+                    .map_err(|e| parse_error(sql_type, splinter, e))?,
+            )
+        }
+        SqlType::Date => {
+            Value::Date(
+                NaiveDate::parse_from_str(splinter, "%Y-%m-%d")
+                    .map_err(|e| parse_error(sql_type, splinter, e))?,
+            )
+        }
+        SqlType::Timestamp | SqlType::TimestampTz => {
+            let timestamp = DateTime::parse_from_rfc3339(splinter)
+                .or_else(|_| {
+                    DateTime::parse_from_str(splinter, "%Y-%m-%d %H:%M:%S%.f%z")
+                })
+                .map(|ts| ts.with_timezone(&Utc))
+                .or_else(|_| {
+                    NaiveDateTime::parse_from_str(
+                        splinter,
+                        "%Y-%m-%dT%H:%M:%S%.f",
+                    )
+                    .or_else(|_| {
+                        NaiveDateTime::parse_from_str(
+                            splinter,
+                            "%Y-%m-%d %H:%M:%S%.f",
+                        )
+                    })
+                    .map(|ts| DateTime::<Utc>::from_utc(ts, Utc))
+                })
+                .map_err(|e| parse_error(sql_type, splinter, e))?;
+            Value::Timestamp(timestamp)
+        }
+        SqlType::Time | SqlType::TimeTz => {
+            Value::Time(
+                NaiveTime::parse_from_str(splinter, "%H:%M:%S%.f")
+                    .map_err(|e| parse_error(sql_type, splinter, e))?,
+            )
+        }
+        SqlType::Char
+        | SqlType::Varchar
+        | SqlType::Tinytext
+        | SqlType::Mediumtext
+        | SqlType::Text
+        | SqlType::Enum(_, _) => Value::Text(splinter.to_string()),
+        _ => {
+            return Err(parse_error(
+                sql_type,
+                splinter,
+                "type is not supported in record id",
+            ));
+        }
+    };
+    Ok(value)
+}
+
 /// extract record id from comma separated value
 pub fn extract_record_id<'a>(
     record_id: &str,
     pk_types: &[&SqlType],
     pk_columns: &[&'a ColumnName],
 ) -> Result<Vec<(&'a ColumnName, Value)>, IntelError> {
-    let splinters: Vec<&str> = record_id.split(',').collect();
+    let splinters = split_record_id(record_id);
+    if splinters.len() != pk_types.len() || pk_columns.len() != pk_types.len()
+    {
+        return Err(IntelError::ParamParseError(format!(
+            "record id: {} does not match the {} primary columns",
+            record_id,
+            pk_columns.len()
+        )));
+    }
     let mut record_id = Vec::with_capacity(splinters.len());
-    assert_eq!(splinters.len(), pk_types.len());
-    assert_eq!(pk_columns.len(), pk_types.len());
     for (i, splinter) in splinters.iter().enumerate() {
-        let pk_type = pk_types[i];
-        let pk_column = pk_columns[i];
-        let value = match *pk_type {
-            SqlType::Int => {
-                let v = splinter.parse();
-                match v {
-                    Ok(v) => Value::Int(v),
-                    Err(e) => {
-                        return Err(IntelError::ParamParseError(format!(
-                            "Invalid for type {:?}: {}, Error: {}",
-                            pk_type, splinter, e
-                        )));
-                    }
-                }
-            }
-            SqlType::Smallint => {
-                let v = splinter.parse();
-                match v {
-                    Ok(v) => Value::Smallint(v),
-                    Err(e) => {
-                        return Err(IntelError::ParamParseError(format!(
-                            "Invalid for type {:?}: {}, Error: {}",
-                            pk_type, splinter, e
-                        )));
-                    }
-                }
-            }
-            SqlType::Uuid => {
-                let uuid = Uuid::parse_str(splinter);
-                match uuid {
-                    Ok(uuid) => Value::Uuid(uuid),
-                    Err(e) => {
-                        return Err(IntelError::ParamParseError(format!(
-                            "Invalid for type {:?}: {}, Error: {}",
-                            pk_type, splinter, e
-                        )));
-                    }
-                }
-            }
-            SqlType::Numeric => {
-                let v = BigDecimal::from_str(splinter);
-                match v {
-                    Ok(v) => Value::BigDecimal(v),
-                    Err(e) => {
-                        return Err(IntelError::ParamParseError(format!(
-                            "Invalid for type {:?}: {}, Error: {}",
-                            pk_type, splinter, e
-                        )));
-                    }
-                }
-            }
-
-            SqlType::Varchar => Value::Text(splinter.to_string()),
-            _ => panic!("primary with type {:?} is not yet covered", pk_type),
-        };
-        record_id.push((pk_column, value));
+        let value = parse_value(splinter, pk_types[i])?;
+        record_id.push((pk_columns[i], value));
     }
     Ok(record_id)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn postgres_formats() {
+        assert_eq!(
+            parse_value("on", &SqlType::Bool).unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            parse_value("No", &SqlType::Bool).unwrap(),
+            Value::Bool(false)
+        );
+        let timestamp = Value::Timestamp(DateTime::<Utc>::from_utc(
+            NaiveDate::from_ymd(2019, 3, 1).and_hms(10, 0, 0),
+            Utc,
+        ));
+        assert_eq!(
+            parse_value("2019-03-01 10:00:00", &SqlType::Timestamp).unwrap(),
+            timestamp
+        );
+        assert_eq!(
+            parse_value("2019-03-01 18:00:00+0800", &SqlType::TimestampTz)
+                .unwrap(),
+            timestamp
+        );
+    }
+
+    #[test]
+    fn record_id_with_separator_in_value() {
+        let text = Value::Text("Smith, John\\Jr".to_string());
+        let id = Value::Int(42);
+        let record_id = encode_record_id(&[&text, &id]);
+        assert_eq!(record_id, "Smith\\, John\\\\Jr,42");
+        let name_column = ColumnName::from("name");
+        let id_column = ColumnName::from("id");
+        let extracted = extract_record_id(
+            &record_id,
+            &[&SqlType::Text, &SqlType::Int],
+            &[&name_column, &id_column],
+        )
+        .unwrap();
+        assert_eq!(extracted, vec![(&name_column, text), (&id_column, id)]);
+    }
+
+    #[test]
+    fn record_id_of_dates_and_bigint() {
+        let date = Value::Date(NaiveDate::from_ymd(2019, 3, 1));
+        let big = Value::Bigint(9_000_000_000);
+        let record_id = encode_record_id(&[&date, &big]);
+        let date_column = ColumnName::from("day");
+        let big_column = ColumnName::from("seq");
+        let extracted = extract_record_id(
+            &record_id,
+            &[&SqlType::Date, &SqlType::Bigint],
+            &[&date_column, &big_column],
+        )
+        .unwrap();
+        assert_eq!(extracted, vec![(&date_column, date), (&big_column, big)]);
+    }
+
+    #[test]
+    fn mismatched_record_id() {
+        let id_column = ColumnName::from("id");
+        let extracted =
+            extract_record_id("1,2", &[&SqlType::Int], &[&id_column]);
+        assert!(extracted.is_err());
+    }
+
+    #[test]
+    fn only_supported_types_are_parsable() {
+        assert!(is_parsable(&SqlType::Int));
+        assert!(is_parsable(&SqlType::Timestamp));
+        assert!(is_parsable(&SqlType::Text));
+        assert!(!is_parsable(&SqlType::Json));
+        assert!(!is_parsable(&SqlType::Blob));
+    }
+}
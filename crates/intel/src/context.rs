@@ -1,4 +1,6 @@
 use crate::{
+    identifier::IdentifierRegistry,
+    media::MediaRegistry,
     window::GroupedWindow,
     Window,
 };
@@ -12,6 +14,10 @@ pub struct Context {
     pub tables: HashMap<TableName, Table>,
     pub windows: HashMap<TableName, Window>,
     pub grouped_window: Vec<GroupedWindow>,
+    /// the identifier displays of the tables on the DB_URL
+    pub identifiers: IdentifierRegistry,
+    /// the media kind of the columns on the DB_URL
+    pub media: MediaRegistry,
 }
 
 impl Context {
@@ -1,4 +1,5 @@
 use crate::{
+    error::IntelError,
     window::GroupedWindow,
     Window,
 };
@@ -14,6 +15,10 @@ use serde::{
     Deserialize,
     Serialize,
 };
+use std::{
+    fmt,
+    str::FromStr,
+};
 
 /// an arranged value with respect  to the fields arrangement in tab
 pub type DataRow = Vec<Value>;
@@ -31,6 +36,10 @@ pub struct AppData {
 pub struct Page {
     /// page number
     pub page: usize,
+    /// the names of the values in the rows, which includes the display
+    /// of the foreign key columns and the uri of the media columns
+    /// after the columns of the table
+    pub columns: Vec<String>,
     /// rows on this page
     pub rows: Vec<DataRow>,
 }
@@ -39,9 +48,31 @@ impl Page {
     fn from_rows(rows: Rows) -> Self {
         Page {
             page: 1,
+            columns: rows.columns,
             rows: rows.data,
         }
     }
+
+    /// the index of the value of this column in the rows
+    pub fn column_index(&self, column_name: &str) -> Option<usize> {
+        self.columns.iter().position(|column| column == column_name)
+    }
+
+    /// the index of the display of this foreign key column in the rows
+    pub fn display_index(&self, column_name: &str) -> Option<usize> {
+        let display_column = display_column_name(column_name);
+        self.columns
+            .iter()
+            .position(|column| *column == display_column)
+    }
+
+    /// the index of the media uri of this column in the rows
+    pub fn media_index(&self, column_name: &str) -> Option<usize> {
+        let media_column = media_column_name(column_name);
+        self.columns
+            .iter()
+            .position(|column| *column == media_column)
+    }
 }
 
 /// Convert the dao into a vec of value
@@ -73,6 +104,8 @@ pub struct WindowData {
     pub main_tab_total_rows: usize,
     /// current page of the main tab rows
     pub main_tab_current_page: usize,
+    /// the cursor of the page next to the current page
+    pub main_tab_cursor: Option<Cursor>,
     /// Contains the main tab record detail when in detailed view
     /// FIXME: record detail is only using Dao
     pub record_detail: Option<RecordDetail>,
@@ -169,6 +202,30 @@ impl WindowData {
             },
         );
     }
+
+    /// set the page of the has_many tab,
+    /// the first page replaces the pages previously fetched
+    pub fn set_has_many_page(&mut self, index: usize, page: usize, rows: Rows) {
+        if page == 1 {
+            self.has_many_tab_total_rows[index] = rows.count.unwrap_or(0);
+            self.has_many_tab_data[index] = vec![Page::from_rows(rows)];
+        } else {
+            self.has_many_tab_data[index].push(Page::from_rows(rows));
+        }
+        self.has_many_tab_current_page[index] = page;
+    }
+
+    /// set the page of the indirect tab,
+    /// the first page replaces the pages previously fetched
+    pub fn set_indirect_page(&mut self, index: usize, page: usize, rows: Rows) {
+        if page == 1 {
+            self.indirect_tab_total_rows[index] = rows.count.unwrap_or(0);
+            self.indirect_tab_data[index] = vec![Page::from_rows(rows)];
+        } else {
+            self.indirect_tab_data[index].push(Page::from_rows(rows));
+        }
+        self.indirect_tab_current_page[index] = page;
+    }
 }
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
@@ -189,6 +246,11 @@ pub struct FrozenData {
 pub struct QueryResult {
     pub window: Option<Window>,
     pub rows: Rows,
+    /// the cursor of the next page, None when there are no more rows
+    /// or the rows can only be paged by page number
+    pub cursor: Option<Cursor>,
+    /// how the count of the rows is obtained, None when it is not counted
+    pub count_kind: Option<CountKind>,
 }
 
 impl QueryResult {
@@ -196,11 +258,105 @@ impl QueryResult {
     pub fn with_rows(window: Option<&Window>, rows: Rows) -> Self {
         QueryResult {
             window: window.map(Clone::clone),
+            count_kind: rows.count.map(|_| CountKind::Exact),
             rows,
+            cursor: None,
         }
     }
 }
 
+/// the total rows is either counted or estimated from the statistics
+/// of the database which is cheaper for huge tables
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum CountKind {
+    Exact,
+    Estimated,
+}
+
+/// the values of the sort columns and the primary columns
+/// of the last row of a page, the next page starts after this row
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Cursor {
+    pub values: Vec<Value>,
+}
+
+/// which page of the rows to fetch
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum PageRequest {
+    /// the page number, the rows are skipped using OFFSET
+    Number(usize),
+    /// the rows after the cursor of the previous page
+    After(Cursor),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum Direction {
+    Asc,
+    Desc,
+}
+
+/// order the rows by this column
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Order {
+    pub column_name: ColumnName,
+    pub direction: Direction,
+}
+
+/// the orders of the rows, the first order takes precedence,
+/// written in the url as `?sort=name.asc,created.desc`.
+/// The direction can be left out, `?sort=film.title` is ascending
+#[derive(Debug, Default, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Sort {
+    pub orders: Vec<Order>,
+}
+
+impl fmt::Display for Sort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, order) in self.orders.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            let direction = match order.direction {
+                Direction::Asc => "asc",
+                Direction::Desc => "desc",
+            };
+            write!(f, "{}.{}", order.column_name.complete_name(), direction)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Sort {
+    type Err = IntelError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut orders = vec![];
+        for order in s.split(',').filter(|order| !order.is_empty()) {
+            let (column, direction) = match order.rfind('.') {
+                Some(index) => {
+                    match order[index + 1..].to_lowercase().as_str() {
+                        "asc" => (&order[..index], Direction::Asc),
+                        "desc" => (&order[..index], Direction::Desc),
+                        _ => (order, Direction::Asc),
+                    }
+                }
+                None => (order, Direction::Asc),
+            };
+            if column.is_empty() {
+                return Err(IntelError::ParamParseError(format!(
+                    "missing sort column: {}",
+                    order
+                )));
+            }
+            orders.push(Order {
+                column_name: ColumnName::from(column),
+                direction,
+            });
+        }
+        Ok(Sort { orders })
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct RecordDetail {
     pub window: Window,
@@ -211,21 +367,48 @@ pub struct RecordDetail {
     pub indirect: Vec<(TableName, TableName, Rows)>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+impl RecordDetail {
+    /// the values of the primary columns of the record,
+    /// used for retrieving the next pages of the related records
+    pub fn primary_dao(&self) -> Dao {
+        let mut dao = Dao::new();
+        for field in self.window.main_tab.fields.iter() {
+            if field.is_primary {
+                for column_name in field.column_names() {
+                    if let Some(value) =
+                        self.record.get_value(&column_name.name)
+                    {
+                        dao.insert_value(&column_name.name, value);
+                    }
+                }
+            }
+        }
+        dao
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Deserialize, Serialize)]
 pub enum RecordAction {
     Unlink,
     LinkExisting,
     LinkNew,
-    Edited,    // only used in the main record
+    Edited,    // the main record, or the linker fields of indirect records
     CreateNew, // only used in the main record
+    Deleted,   // only used in the journal of deleted records
+    Restored,  // only used in the journal of undone changes
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct RecordChangeset {
     pub record: Dao,
+    /// the values of the record as it was originally read,
+    /// used to detect if the record has been modified by someone else
+    /// in the meantime
+    pub original: Option<Dao>,
     pub action: RecordAction,
     pub one_ones: Vec<(TableName, Option<Dao>)>,
-    pub has_many: Vec<(TableName, RecordAction, Rows)>,
+    // vec ( table, referring columns, action, rows )
+    pub has_many: Vec<(TableName, Vec<ColumnName>, RecordAction, Rows)>,
     // vec ( table, via linker, action, rows )
     pub indirect: Vec<(TableName, TableName, RecordAction, Rows)>,
 }
@@ -234,7 +417,61 @@ pub struct RecordChangeset {
 pub struct SaveContainer {
     pub for_insert: (TableName, Rows),
     pub for_update: (TableName, Rows),
+    /// the originally read values of the rows for update,
+    /// in the same order as the rows for update
+    pub original_for_update: Option<Rows>,
+}
+/// the result of saving or deleting records,
+/// the changes can be reverted with the undo id within the grace period
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Modified<T> {
+    pub result: T,
+    pub undo_id: Option<String>,
+}
+
+/// what happens to the dependent rows of a table
+/// when the selected records are deleted
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum ImpactKind {
+    /// the dependent rows are deleted along with the record
+    Delete,
+    /// the dependent rows are kept, but their reference is set to null,
+    /// or the linker rows are removed leaving the indirect records
+    Unlink,
+    /// the dependent rows prevents the record from being deleted
+    Block,
 }
+
+/// the number of dependent rows in a table affected by the delete
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct TableImpact {
+    pub table_name: TableName,
+    pub kind: ImpactKind,
+    pub count: usize,
+}
+
+/// the preview of the cascading effect of deleting the selected records
+/// of the main table, shown to the user before confirming the delete
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct CascadeImpact {
+    pub table_name: TableName,
+    pub one_ones: Vec<TableImpact>,
+    pub has_many: Vec<TableImpact>,
+    /// the impact on the linker table of each indirect table
+    pub indirect: Vec<TableImpact>,
+}
+
+impl CascadeImpact {
+    /// the delete can not proceed when any of the dependent rows blocks it
+    pub fn is_blocked(&self) -> bool {
+        self.one_ones
+            .iter()
+            .chain(self.has_many.iter())
+            .chain(self.indirect.iter())
+            .any(|impact| impact.kind == ImpactKind::Block && impact.count > 0)
+    }
+}
+
 /// the dropdown data and the description on
 /// how will it be displayed as defined in IdentifierDisplay
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -246,6 +483,44 @@ pub struct DropdownInfo {
     pub display: IdentifierDisplay,
 }
 
+/// the name of the column in the rows holding the display
+/// of the record referred by this foreign key column
+pub fn display_column_name(column_name: &str) -> String {
+    format!("{}.display", column_name)
+}
+
+/// the name of the column in the rows holding the uri
+/// of the media of this binary or image url column
+pub fn media_column_name(column_name: &str) -> String {
+    format!("{}.media", column_name)
+}
+
+/// the name of the extra column of the linker table
+/// in the rows of the indirect records, ie: `film_actor.role`
+pub fn linker_column_name(linker: &TableName, column_name: &str) -> String {
+    format!("{}.{}", linker.name, column_name)
+}
+
+/// the name of the column in the tree records holding
+/// the identifier display of the record
+pub const TREE_DISPLAY_COLUMN: &str = "tree.display";
+
+/// the name of the column in the tree records which tells
+/// if the record has records under it
+pub const TREE_HAS_CHILDREN_COLUMN: &str = "tree.has_children";
+
+/// the records in the hierarchy of a table that refers to itself,
+/// with the primary key and the display of each record
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TreeRecords {
+    /// the parent of the record up to the root, the root comes first,
+    /// empty when the records are the roots
+    pub ancestors: Rows,
+    /// the records directly under the record, or the roots
+    /// which has no parent
+    pub children: Rows,
+}
+
 /// lookup for same table are the same regardless of which field they are referred
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Lookup(pub Vec<(TableName, Rows)>);
@@ -292,6 +567,11 @@ mod tests {
         "schema": "public",
         "alias": null
       },
+      [
+        {
+          "name": "city_id"
+        }
+      ],
       "Edited",
       {
         "columns": [
@@ -313,6 +593,11 @@ mod tests {
         "schema": "public",
         "alias": null
       },
+      [
+        {
+          "name": "city_id"
+        }
+      ],
       "LinkNew",
       {
         "columns": [
@@ -336,6 +621,7 @@ mod tests {
         dao.insert("city", "Akishima");
         let changeset = RecordChangeset {
             record: dao,
+            original: None,
             action: RecordAction::Edited,
             one_ones: vec![],
             has_many: vec![],
@@ -402,6 +688,11 @@ mod tests {
         "name": "inventory",
         "schema": "public"
       },
+      [
+        {
+          "name": "film_id"
+        }
+      ],
       "Edited",
       {
         "columns": [
@@ -583,6 +874,7 @@ mod tests {
         dao.insert("city", "Akishima");
         let changeset = RecordChangeset {
             record: dao,
+            original: None,
             action: RecordAction::Edited,
             one_ones: vec![],
             has_many: vec![],
@@ -649,6 +941,11 @@ mod tests {
         "schema": "public",
         "alias": null
       },
+      [
+        {
+          "name": "film_id"
+        }
+      ],
       "Edited",
       {
         "columns": [
@@ -844,6 +1141,7 @@ mod tests {
         dao.insert("city", "Akishima");
         let changeset = RecordChangeset {
             record: dao,
+            original: None,
             action: RecordAction::Edited,
             one_ones: vec![],
             has_many: vec![],
@@ -855,4 +1153,24 @@ mod tests {
         println!("result: {:#?}", result);
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn sort_in_url() {
+        let sort: Sort = "last_name.asc,created.desc".parse().unwrap();
+        assert_eq!(sort.orders.len(), 2);
+        assert_eq!(sort.orders[0].column_name.name, "last_name");
+        assert_eq!(sort.orders[1].direction, Direction::Desc);
+        assert_eq!(sort.to_string(), "last_name.asc,created.desc");
+        assert!(".desc".parse::<Sort>().is_err());
+    }
+
+    #[test]
+    fn sort_by_qualified_column() {
+        let sort: Sort = "film.title,film.length.DESC".parse().unwrap();
+        assert_eq!(sort.orders[0].column_name.complete_name(), "film.title");
+        assert_eq!(sort.orders[0].direction, Direction::Asc);
+        assert_eq!(sort.orders[1].column_name.complete_name(), "film.length");
+        assert_eq!(sort.orders[1].direction, Direction::Desc);
+        assert_eq!(sort.to_string(), "film.title.asc,film.length.desc");
+    }
 }
@@ -4,21 +4,37 @@
 use crate::{
     common,
     data_container::{
+        self,
         RecordAction,
         RecordChangeset,
         SaveContainer,
     },
-    error::IntelError,
+    data_read::cascade_impact,
+    error::{
+        ChangesetStep,
+        ColumnConflict,
+        IntelError,
+    },
+    journal::Journal,
+    media,
+    plugin,
     tab::{
         self,
+        HasManyTab,
         IndirectTab,
         Tab,
     },
-    table_intel,
+    table_intel::{
+        self,
+        TableIntel,
+    },
+    validation,
     window::Window,
+    Context,
 };
 use rustorm::{
     self,
+    Column,
     ColumnName,
     Dao,
     DaoManager,
@@ -30,12 +46,15 @@ use rustorm::{
 };
 
 /// delete the records with the following record_ids
-/// return the total number of records deleted
+/// return the deleted records and the journal of the deleted rows
+/// including the rows of the referring tables that are cascaded by the database
 pub fn delete_records(
+    context: &Context,
     dm: &mut DaoManager,
+    db_url: &str,
     main_table: &Table,
     record_ids: &[String],
-) -> Result<Rows, IntelError> {
+) -> Result<(Rows, Journal), IntelError> {
     let pk_types = &main_table.get_primary_column_types();
     let primary_columns = &main_table.get_primary_column_names();
     let mut record_id_values = Vec::with_capacity(record_ids.len());
@@ -44,16 +63,88 @@ pub fn delete_records(
             common::extract_record_id(rid, pk_types, primary_columns)?;
         record_id_values.push(record_id_value);
     }
-    if primary_columns.len() == 1 {
-        let rows = delete_records_from_single_primary_column(
+    if record_id_values.is_empty() {
+        return Ok((Rows::new(vec![]), Journal::new()));
+    }
+    in_transaction(dm, |dm| {
+        let mut journal = Journal::new();
+        let cascaded_rows = cascade_impact::get_cascaded_rows(
+            context,
             dm,
+            db_url,
             main_table,
             &record_id_values,
         )?;
-        Ok(rows)
-    } else {
-        panic!("not yet handled composite primary key")
+        let rows = if primary_columns.len() == 1 {
+            delete_records_from_single_primary_column(
+                dm,
+                main_table,
+                &record_id_values,
+            )?
+        } else {
+            delete_records_from_composite_primary_columns(
+                dm,
+                main_table,
+                &record_id_values,
+            )?
+        };
+        // the cascaded rows are journaled before the records they refer to,
+        // so they are restored after them on undo
+        for cascaded in cascaded_rows.iter() {
+            if cascaded.is_deleted {
+                journal.record_deleted_rows(
+                    &cascaded.table_name,
+                    RecordAction::Deleted,
+                    &cascaded.rows,
+                );
+            } else {
+                record_unlinked_rows(
+                    context,
+                    dm,
+                    db_url,
+                    &cascaded.table_name,
+                    &cascaded.rows,
+                    &mut journal,
+                )?;
+            }
+        }
+        journal.record_deleted_rows(
+            &main_table.name,
+            RecordAction::Deleted,
+            &rows,
+        );
+        Ok((rows, journal))
+    })
+}
+
+/// the rows set to null by the database are journaled as updated,
+/// with their current values after the delete as the after image.
+/// A row that is also deleted by another cascade is journaled as deleted
+fn record_unlinked_rows(
+    context: &Context,
+    dm: &mut DaoManager,
+    db_url: &str,
+    table_name: &TableName,
+    rows: &Rows,
+    journal: &mut Journal,
+) -> Result<(), IntelError> {
+    let table = context.get_table(table_name).ok_or(IntelError::NotFound)?;
+    for before in rows.iter() {
+        match select_record(dm, db_url, table, &before)? {
+            Some(after) => {
+                journal.record(
+                    table_name,
+                    RecordAction::Unlink,
+                    Some(before),
+                    Some(after),
+                )
+            }
+            None => {
+                journal.record_delete(table_name, RecordAction::Deleted, before)
+            }
+        }
     }
+    Ok(())
 }
 
 fn delete_records_from_single_primary_column(
@@ -85,89 +176,396 @@ fn delete_records_from_single_primary_column(
     Ok(rows)
 }
 
+/// each record id is matched with all of its primary columns,
+/// ie: WHERE (a = $1 AND b = $2) OR (a = $3 AND b = $4)
+fn delete_records_from_composite_primary_columns(
+    dm: &mut DaoManager,
+    main_table: &Table,
+    record_ids: &[Vec<(&ColumnName, Value)>],
+) -> Result<Rows, DbError> {
+    let table_name = &main_table.name;
+    let mut sql = format!("DELETE FROM {} ", table_name.complete_name());
+    sql += "WHERE ";
+    let mut pk_values: Vec<Value> = vec![];
+    for (i, record_id) in record_ids.iter().enumerate() {
+        if i > 0 {
+            sql += "OR ";
+        }
+        sql += "(";
+        for (j, (pk_column, pk_value)) in record_id.iter().enumerate() {
+            if j > 0 {
+                sql += "AND ";
+            }
+            sql += &format!("{} = ${} ", pk_column.name, pk_values.len() + 1);
+            pk_values.push(pk_value.to_owned());
+        }
+        sql += ") ";
+    }
+    sql += "RETURNING *";
+    let bpk_values: Vec<&Value> = pk_values.iter().collect();
+    let rows = dm.execute_sql_with_return(&sql, &bpk_values)?;
+    Ok(rows)
+}
+
+/// insert and update the rows of the container in one transaction,
+/// return the inserted records followed by the updated records
+/// and the journal of the updated rows
 pub fn save_container(
+    context: &Context,
     dm: &mut DaoManager,
-    tables: &[Table],
+    db_url: &str,
     container: &SaveContainer,
-) -> Result<(), IntelError> {
+) -> Result<(Vec<Dao>, Journal), IntelError> {
     let &(ref table_name_for_insert, ref rows_insert) = &container.for_insert;
     let &(ref table_name_for_update, ref rows_update) = &container.for_update;
-    let table_for_insert =
-        table_intel::get_table(table_name_for_insert, tables).unwrap();
-    let table_for_update =
-        table_intel::get_table(table_name_for_update, tables).unwrap();
-    if rows_insert.iter().count() > 0 {
-        insert_rows_to_table(dm, table_for_insert, rows_insert)?;
-    }
-    update_records_in_table(dm, table_for_update, rows_update)?;
-    Ok(())
+    let originals_update = container.original_for_update.as_ref();
+    let table_for_insert = context
+        .get_table(table_name_for_insert)
+        .ok_or(IntelError::NotFound)?;
+    let table_for_update = context
+        .get_table(table_name_for_update)
+        .ok_or(IntelError::NotFound)?;
+    in_transaction(dm, |dm| {
+        let mut journal = Journal::new();
+        let mut records = vec![];
+        if rows_insert.iter().count() > 0 {
+            let inserted = insert_rows_to_table(
+                dm,
+                db_url,
+                table_for_insert,
+                rows_insert,
+            )?;
+            journal.record_inserted_rows(
+                &table_for_insert.name,
+                RecordAction::CreateNew,
+                &inserted,
+            );
+            records.extend(inserted.iter());
+        }
+        let updated = update_records_in_table(
+            dm,
+            db_url,
+            table_for_update,
+            rows_update,
+            originals_update,
+            &mut journal,
+        )?;
+        records.extend(updated);
+        Ok((records, journal))
+    })
 }
 
+/// save the main record and its related one_one, has_many and indirect records
+/// in one transaction, if any of the steps fails, the whole changeset is rolled back.
+/// return the main record as it is after the save
+/// and the journal of the changed rows
 pub fn save_changeset(
+    context: &Context,
     dm: &mut DaoManager,
-    tables: &[Table],
+    db_url: &str,
     window: &Window,
     table: &Table,
     changeset: &RecordChangeset,
-) -> Result<(), IntelError> {
+) -> Result<(Dao, Journal), IntelError> {
+    in_transaction(dm, |dm| {
+        let mut journal = Journal::new();
+        let record = save_changeset_records(
+            context,
+            dm,
+            db_url,
+            window,
+            table,
+            changeset,
+            &mut journal,
+        )?;
+        Ok((record, journal))
+    })
+}
+
+fn save_changeset_records(
+    context: &Context,
+    dm: &mut DaoManager,
+    db_url: &str,
+    window: &Window,
+    table: &Table,
+    changeset: &RecordChangeset,
+    journal: &mut Journal,
+) -> Result<Dao, IntelError> {
     let updated_record = match &changeset.action {
         RecordAction::CreateNew => {
-            insert_record_to_table(dm, table, &changeset.record)?
+            insert_record_to_table(dm, db_url, table, &changeset.record).map(
+                |inserted| {
+                    journal.record_insert(
+                        &table.name,
+                        RecordAction::CreateNew,
+                        inserted.clone(),
+                    );
+                    inserted
+                },
+            )
         }
         RecordAction::Edited => {
-            update_record_in_table(dm, table, &changeset.record)?
+            update_record_in_table(
+                dm,
+                db_url,
+                table,
+                &changeset.record,
+                changeset.original.as_ref(),
+                journal,
+            )
         }
-        _ => panic!("unhandled case: {:?}", changeset.action),
-    };
+        _ => {
+            Err(IntelError::ParamParseError(format!(
+                "unhandled record action: {:?}",
+                changeset.action
+            )))
+        }
+    }
+    .map_err(|e| {
+        IntelError::changeset_error(ChangesetStep::MainRecord, &table.name, e)
+    })?;
     save_one_ones(
+        context,
         dm,
-        tables,
+        db_url,
         table,
         &updated_record,
         &window.one_one_tabs,
         &changeset.one_ones,
+        journal,
     )?;
     save_has_many(
+        context,
         dm,
-        tables,
+        db_url,
         table,
         &updated_record,
         &window.has_many_tabs,
         &changeset.has_many,
+        journal,
     )?;
     save_indirect(
+        context,
         dm,
-        tables,
+        db_url,
         table,
         &updated_record,
         &window.indirect_tabs,
         &changeset.indirect,
+        journal,
     )?;
-    Ok(())
+    Ok(updated_record)
+}
+
+/// restore the rows in the journal to how they are before the changes,
+/// inserted rows are deleted,
+/// in the reverse order the changes happened, in one transaction.
+/// return the restored records and the journal of the undo
+pub fn undo_journal(
+    context: &Context,
+    dm: &mut DaoManager,
+    db_url: &str,
+    journal: &Journal,
+) -> Result<(Vec<(TableName, Dao)>, Journal), IntelError> {
+    in_transaction(dm, |dm| {
+        let mut undo_journal = Journal::new();
+        let mut restored = vec![];
+        for entry in journal.entries.iter().rev() {
+            let table = context
+                .get_table(&entry.table_name)
+                .ok_or(IntelError::NotFound)?;
+            // the row must still be as it was left by the modification,
+            // otherwise the changes made after it would be overwritten
+            let key = entry.before.as_ref().or_else(|| entry.after.as_ref());
+            let current = match key {
+                Some(key) => select_record(dm, db_url, table, key)?,
+                None => None,
+            };
+            match (&current, &entry.after) {
+                (Some(current), Some(after)) => {
+                    check_update_conflict(table, current, after)?
+                }
+                (Some(current), None) => {
+                    if let Some(before) = &entry.before {
+                        check_update_conflict(table, current, before)?
+                    }
+                }
+                (None, Some(_)) => return Err(IntelError::NotFound),
+                (None, None) => (),
+            }
+            match (&entry.before, &entry.after) {
+                (Some(before), _) => {
+                    let record = restore_record_to_table(dm, table, before)?;
+                    undo_journal.record(
+                        &table.name,
+                        RecordAction::Restored,
+                        entry.after.clone(),
+                        Some(record.clone()),
+                    );
+                    restored.push((entry.table_name.clone(), record));
+                }
+                (None, Some(after)) => {
+                    delete_record_from_table(
+                        dm,
+                        table,
+                        after,
+                        RecordAction::Deleted,
+                        &mut undo_journal,
+                    )?;
+                }
+                (None, None) => (),
+            }
+        }
+        Ok((restored, undo_journal))
+    })
+}
+
+/// insert the record back with all of its columns,
+/// or update the row back to the values of the record if it still exists
+/// Warning: This only works for postgresql 9.5 and up
+fn restore_record_to_table(
+    dm: &mut DaoManager,
+    table: &Table,
+    record: &Dao,
+) -> Result<Dao, DbError> {
+    let columns: Vec<&Column> = table
+        .columns
+        .iter()
+        .filter(|col| record.get_value(&col.name.name).is_some())
+        .collect();
+    let mut params = vec![];
+    let mut sql = format!("INSERT INTO {} (", table.complete_name());
+    for (i, col) in columns.iter().enumerate() {
+        if i > 0 {
+            sql += ", ";
+        }
+        sql += &col.name.name;
+    }
+    sql += ") VALUES (";
+    for (i, col) in columns.iter().enumerate() {
+        let col_sql_type = col.get_sql_type();
+        if i > 0 {
+            sql += ", ";
+        }
+        sql += &format!("${}", params.len() + 1);
+        if col.cast_as().is_some() || col_sql_type.is_array_type() {
+            sql += &format!("::{}", col_sql_type.name());
+        }
+        let value =
+            record.get_value(&col.name.name).expect("must have a value");
+        params.push(rustorm::common::cast_type(&value, &col_sql_type));
+    }
+    sql += ") ON CONFLICT (";
+    let primary_columns = table.get_primary_column_names();
+    for (i, pk) in primary_columns.iter().enumerate() {
+        if i > 0 {
+            sql += ", ";
+        }
+        sql += &pk.name;
+    }
+    sql += ") ";
+    let non_pk_columns: Vec<&&Column> = columns
+        .iter()
+        .filter(|col| !primary_columns.contains(&&col.name))
+        .collect();
+    if non_pk_columns.is_empty() {
+        sql += "DO NOTHING ";
+    } else {
+        sql += "DO UPDATE SET ";
+        for (i, col) in non_pk_columns.iter().enumerate() {
+            if i > 0 {
+                sql += ", ";
+            }
+            sql += &format!("{} = EXCLUDED.{}", col.name.name, col.name.name);
+        }
+        sql += " ";
+    }
+    sql += "RETURNING *";
+    let bparams: Vec<&Value> = params.iter().collect();
+    let rows = dm.execute_sql_with_return(&sql, &bparams)?;
+    let restored = rows.iter().next().unwrap_or_else(|| record.clone());
+    Ok(restored)
+}
+
+/// an open database transaction, rolled back when it is dropped
+/// without being committed, which also happens when the code inside it panics
+struct Transaction<'a> {
+    dm: &'a mut DaoManager,
+    is_committed: bool,
 }
 
+impl<'a> Transaction<'a> {
+    fn begin(dm: &'a mut DaoManager) -> Result<Self, IntelError> {
+        dm.execute_sql_with_return("BEGIN", &[])?;
+        Ok(Transaction {
+            dm,
+            is_committed: false,
+        })
+    }
+
+    fn commit(mut self) -> Result<(), IntelError> {
+        self.dm.execute_sql_with_return("COMMIT", &[])?;
+        self.is_committed = true;
+        Ok(())
+    }
+}
+
+impl<'a> Drop for Transaction<'a> {
+    fn drop(&mut self) {
+        if !self.is_committed {
+            if let Err(rollback_err) =
+                self.dm.execute_sql_with_return("ROLLBACK", &[])
+            {
+                error!("unable to rollback: {:?}", rollback_err);
+            }
+        }
+    }
+}
+
+/// run the closure inside a database transaction,
+/// commit when it succeeds, rollback when it fails
+fn in_transaction<T, F>(dm: &mut DaoManager, f: F) -> Result<T, IntelError>
+where
+    F: FnOnce(&mut DaoManager) -> Result<T, IntelError>,
+{
+    let mut transaction = Transaction::begin(dm)?;
+    let ret = f(&mut *transaction.dm)?;
+    transaction.commit()?;
+    Ok(ret)
+}
+
+#[allow(clippy::too_many_arguments)]
 fn save_one_ones(
+    context: &Context,
     dm: &mut DaoManager,
-    tables: &[Table],
+    db_url: &str,
     main_table: &Table,
     main_record: &Dao,
     _one_one_tabs: &[Tab],
     one_one_records: &[(TableName, Option<Dao>)],
+    journal: &mut Journal,
 ) -> Result<(), IntelError> {
     for (one_one_table_name, one_one_record) in one_one_records {
         if let Some(one_one_record) = one_one_record {
             //TODO: verify that the one_one_table_name belongs to the one_one_tabs
-            if let Some(one_one_table) =
-                table_intel::get_table(one_one_table_name, tables)
+            if let Some(one_one_table) = context.get_table(one_one_table_name)
             {
                 save_one_one_table(
                     dm,
-                    tables,
+                    db_url,
                     main_table,
                     main_record,
                     one_one_table,
                     one_one_record,
-                )?;
+                    journal,
+                )
+                .map_err(|e| {
+                    IntelError::changeset_error(
+                        ChangesetStep::OneOne,
+                        one_one_table_name,
+                        e,
+                    )
+                })?;
             }
         }
     }
@@ -176,84 +574,197 @@ fn save_one_ones(
 
 fn save_one_one_table(
     dm: &mut DaoManager,
-    _tables: &[Table],
+    db_url: &str,
     main_table: &Table,
     main_record: &Dao,
     one_one_table: &Table,
     one_one_record: &Dao,
-) -> Result<Dao, DbError> {
-    upsert_one_one_record_to_table(
+    journal: &mut Journal,
+) -> Result<Dao, IntelError> {
+    let local_referred_pair =
+        one_one_table.get_local_foreign_columns_pair_to_table(&main_table.name);
+    let mut one_one_key = Dao::new();
+    for (one_one_pk, main_pk_name) in local_referred_pair.iter() {
+        let main_pk_value = get_primary_value(main_record, &main_pk_name.name)?;
+        one_one_key.insert_value(&one_one_pk.name, main_pk_value);
+    }
+    let before = select_record(dm, db_url, one_one_table, &one_one_key)?;
+    let saved = upsert_one_one_record_to_table(
         dm,
         main_table,
         main_record,
         one_one_table,
         one_one_record,
-    )
+    )?;
+    let action = if before.is_some() {
+        RecordAction::Edited
+    } else {
+        RecordAction::CreateNew
+    };
+    journal.record(&one_one_table.name, action, before, Some(saved.clone()));
+    Ok(saved)
 }
 
+#[allow(clippy::too_many_arguments)]
 fn save_has_many(
+    context: &Context,
     dm: &mut DaoManager,
-    tables: &[Table],
+    db_url: &str,
     main_table: &Table,
     main_record: &Dao,
-    has_many_tabs: &[Tab],
-    has_many_records: &[(TableName, RecordAction, Rows)],
+    has_many_tabs: &[HasManyTab],
+    has_many_records: &[(TableName, Vec<ColumnName>, RecordAction, Rows)],
+    journal: &mut Journal,
 ) -> Result<(), IntelError> {
-    for (has_many_table_name, record_action, has_many_rows) in has_many_records
+    for (
+        has_many_table_name,
+        referring_columns,
+        record_action,
+        has_many_rows,
+    ) in has_many_records
     {
-        let _has_many_tab = tab::find_tab(has_many_tabs, has_many_table_name)
-            .expect("table should belong to the tabs");
-        let has_many_table =
-            table_intel::get_table(has_many_table_name, tables)
-                .expect("table should exist");
+        let has_many_tab = tab::find_has_many_tab(
+            has_many_tabs,
+            has_many_table_name,
+            referring_columns,
+        )
+        .ok_or(IntelError::NotFound)?;
+        let has_many_table = context
+            .get_table(has_many_table_name)
+            .ok_or(IntelError::NotFound)?;
         save_has_many_table(
             dm,
-            tables,
+            db_url,
             main_table,
             main_record,
             has_many_table,
+            &has_many_tab.referring_columns,
             record_action,
             &has_many_rows,
-        )?;
+            journal,
+        )
+        .map_err(|e| {
+            IntelError::changeset_error(
+                ChangesetStep::HasMany,
+                has_many_table_name,
+                e,
+            )
+        })?;
     }
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 fn save_has_many_table(
     dm: &mut DaoManager,
-    _tables: &[Table],
-    _main_table: &Table,
-    _main_record: &Dao,
+    db_url: &str,
+    main_table: &Table,
+    main_record: &Dao,
     has_many_table: &Table,
+    referring_columns: &[ColumnName],
     record_action: &RecordAction,
     has_many_rows: &Rows,
+    journal: &mut Journal,
 ) -> Result<(), IntelError> {
     match record_action {
         RecordAction::Unlink => {
-            delete_from_table(dm, has_many_table, has_many_rows)?;
+            delete_from_table(dm, has_many_table, has_many_rows, journal)?;
         }
         RecordAction::LinkNew => {
             if has_many_rows.iter().count() > 0 {
-                insert_rows_to_table(dm, has_many_table, has_many_rows)?;
+                let has_many_rows = link_rows_to_main_record(
+                    main_table,
+                    main_record,
+                    has_many_table,
+                    referring_columns,
+                    has_many_rows,
+                )?;
+                let inserted = insert_rows_to_table(
+                    dm,
+                    db_url,
+                    has_many_table,
+                    &has_many_rows,
+                )?;
+                journal.record_inserted_rows(
+                    &has_many_table.name,
+                    RecordAction::LinkNew,
+                    &inserted,
+                );
             }
         }
         RecordAction::Edited => {
-            update_records_in_table(dm, has_many_table, has_many_rows)?;
+            update_records_in_table(
+                dm,
+                db_url,
+                has_many_table,
+                has_many_rows,
+                None,
+                journal,
+            )?;
         }
         _ => {
-            panic!("unexpected record action: {:?} in has_many", record_action)
+            return Err(IntelError::ParamParseError(format!(
+                "unexpected record action: {:?} in has_many",
+                record_action
+            )))
         }
     }
     Ok(())
 }
 
+/// set the referring columns of the new has_many rows
+/// to the values of the main record it is linked to
+fn link_rows_to_main_record(
+    main_table: &Table,
+    main_record: &Dao,
+    has_many_table: &Table,
+    referring_columns: &[ColumnName],
+    rows: &Rows,
+) -> Result<Rows, IntelError> {
+    let fk = has_many_table
+        .get_foreign_keys()
+        .into_iter()
+        .find(|fk| {
+            fk.foreign_table == main_table.name
+                && fk.columns.as_slice() == referring_columns
+        })
+        .ok_or(IntelError::NotFound)?;
+    let local_referred_pair: Vec<(&ColumnName, &ColumnName)> =
+        fk.columns.iter().zip(fk.referred_columns.iter()).collect();
+    let mut columns: Vec<String> = rows.columns.clone();
+    for local in fk.columns.iter() {
+        if !columns.contains(&local.name) {
+            columns.push(local.name.to_string());
+        }
+    }
+    let mut linked_rows = Rows::new(columns.clone());
+    for mut record in rows.iter() {
+        copy_fk_values(&mut record, &local_referred_pair, main_record)?;
+        let row = columns
+            .iter()
+            .map(|column| {
+                record.get_value(column).cloned().unwrap_or(Value::Nil)
+            })
+            .collect();
+        linked_rows.push(row);
+    }
+    Ok(linked_rows)
+}
+
 fn delete_from_table(
     dm: &mut DaoManager,
     table: &Table,
     rows: &Rows,
+    journal: &mut Journal,
 ) -> Result<(), IntelError> {
     for dao in rows.iter() {
-        delete_record_from_table(dm, table, &dao)?;
+        delete_record_from_table(
+            dm,
+            table,
+            &dao,
+            RecordAction::Unlink,
+            journal,
+        )?;
     }
     Ok(())
 }
@@ -262,6 +773,8 @@ fn delete_record_from_table(
     dm: &mut DaoManager,
     table: &Table,
     record: &Dao,
+    action: RecordAction,
+    journal: &mut Journal,
 ) -> Result<(), IntelError> {
     let mut params: Vec<&Value> = vec![];
     let mut sql = String::from("DELETE FROM ");
@@ -272,71 +785,97 @@ fn delete_record_from_table(
         if i > 0 {
             sql += "AND ";
         }
-        let pk_value = record
-            .get_value(&col.name)
-            .expect("must have primary column values");
-        sql += &format!("{} = ${}", col.name, i + 1);
+        let pk_value = get_primary_value(record, &col.name)?;
+        sql += &format!("{} = ${} ", col.name, i + 1);
         params.push(pk_value);
     }
-    println!("sql: {}", sql);
-    println!("params: {:?}", params);
-    dm.execute_sql_with_return(&sql, &params)?;
+    sql += "RETURNING *";
+    let rows = dm.execute_sql_with_return(&sql, &params)?;
+    journal.record_deleted_rows(&table.name, action, &rows);
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 fn save_indirect(
+    context: &Context,
     dm: &mut DaoManager,
-    tables: &[Table],
+    db_url: &str,
     main_table: &Table,
     main_record: &Dao,
     _indirect_tabs: &[IndirectTab],
     indirect_records: &[(TableName, TableName, RecordAction, Rows)],
+    journal: &mut Journal,
 ) -> Result<(), IntelError> {
     for (indirect_tablename, via_tablename, record_action, rows) in
         indirect_records
     {
-        let indirect_table = table_intel::get_table(indirect_tablename, tables)
-            .expect("indirect table should exist");
-        let linker_table = table_intel::get_table(via_tablename, tables)
-            .expect("via table should exists");
-        match record_action {
+        let indirect_table = context
+            .get_table(indirect_tablename)
+            .ok_or(IntelError::NotFound)?;
+        let linker_table = context
+            .get_table(via_tablename)
+            .ok_or(IntelError::NotFound)?;
+        let saved = match record_action {
             RecordAction::Unlink => {
                 unlink_from_indirect_table(
                     dm,
-                    tables,
                     main_table,
                     main_record,
                     indirect_table,
                     linker_table,
                     rows,
-                )?;
+                    journal,
+                )
             }
             RecordAction::LinkNew => {
                 link_new_for_indirect_table(
                     dm,
-                    tables,
+                    db_url,
                     main_table,
                     main_record,
                     indirect_table,
                     linker_table,
                     rows,
-                )?;
+                    journal,
+                )
             }
             RecordAction::LinkExisting => {
                 link_existing_for_indirect_table(
                     dm,
-                    tables,
                     main_table,
                     main_record,
                     indirect_table,
                     linker_table,
                     rows,
-                )?;
+                    journal,
+                )
+            }
+            RecordAction::Edited => {
+                update_linker_fields(
+                    dm,
+                    db_url,
+                    main_table,
+                    main_record,
+                    indirect_table,
+                    linker_table,
+                    rows,
+                    journal,
+                )
             }
             _ => {
-                println!("unexpected action {:?}", record_action);
+                Err(IntelError::ParamParseError(format!(
+                    "unexpected record action: {:?} in indirect",
+                    record_action
+                )))
             }
-        }
+        };
+        saved.map_err(|e| {
+            IntelError::changeset_error(
+                ChangesetStep::Indirect,
+                indirect_tablename,
+                e,
+            )
+        })?;
     }
     Ok(())
 }
@@ -344,12 +883,12 @@ fn save_indirect(
 /// delete the entry from the linker table
 fn unlink_from_indirect_table(
     dm: &mut DaoManager,
-    _tables: &[Table],
     main_table: &Table,
     main_record: &Dao,
     indirect_table: &Table,
     linker_table: &Table,
     rows: &Rows,
+    journal: &mut Journal,
 ) -> Result<(), IntelError> {
     for indirect_record in rows.iter() {
         let linker_record = create_linker_record(
@@ -359,25 +898,73 @@ fn unlink_from_indirect_table(
             indirect_table,
             &indirect_record,
         )?;
-        delete_record_from_table(dm, linker_table, &linker_record)?;
+        delete_record_from_table(
+            dm,
+            linker_table,
+            &linker_record,
+            RecordAction::Unlink,
+            journal,
+        )?;
     }
     Ok(())
 }
 
 /// create an entry to the indirect table
 /// and create an entry into the linker table
+#[allow(clippy::too_many_arguments)]
 fn link_new_for_indirect_table(
     dm: &mut DaoManager,
-    _tables: &[Table],
+    db_url: &str,
+    main_table: &Table,
+    main_record: &Dao,
+    indirect_table: &Table,
+    linker_table: &Table,
+    rows: &Rows,
+    journal: &mut Journal,
+) -> Result<(), IntelError> {
+    for indirect_record in rows.iter() {
+        let indirect_record = insert_record_to_table(
+            dm,
+            db_url,
+            indirect_table,
+            &indirect_record,
+        )?;
+        journal.record_insert(
+            &indirect_table.name,
+            RecordAction::LinkNew,
+            indirect_record.clone(),
+        );
+        let linker_record = create_linker_record(
+            main_table,
+            main_record,
+            linker_table,
+            indirect_table,
+            &indirect_record,
+        )?;
+        let linker_record =
+            insert_record_to_linker_table(dm, linker_table, &linker_record)?;
+        journal.record_insert(
+            &linker_table.name,
+            RecordAction::LinkNew,
+            linker_record,
+        );
+    }
+    Ok(())
+}
+
+/// update the extra fields of the linker records of the indirect records
+#[allow(clippy::too_many_arguments)]
+fn update_linker_fields(
+    dm: &mut DaoManager,
+    db_url: &str,
     main_table: &Table,
     main_record: &Dao,
     indirect_table: &Table,
     linker_table: &Table,
     rows: &Rows,
+    journal: &mut Journal,
 ) -> Result<(), IntelError> {
     for indirect_record in rows.iter() {
-        let indirect_record =
-            insert_record_to_table(dm, indirect_table, &indirect_record)?;
         let linker_record = create_linker_record(
             main_table,
             main_record,
@@ -385,12 +972,20 @@ fn link_new_for_indirect_table(
             indirect_table,
             &indirect_record,
         )?;
-        insert_record_to_linker_table(dm, linker_table, &linker_record)?;
+        update_record_in_table(
+            dm,
+            db_url,
+            linker_table,
+            &linker_record,
+            None,
+            journal,
+        )?;
     }
     Ok(())
 }
 
-/// create a record in linker table using the primary key of main and indirect record
+/// create a record in linker table using the primary key of main and indirect record,
+/// the extra fields of the linker are taken from the indirect record
 fn create_linker_record(
     main_table: &Table,
     main_record: &Dao,
@@ -398,37 +993,60 @@ fn create_linker_record(
     indirect_table: &Table,
     indirect_record: &Dao,
 ) -> Result<Dao, IntelError> {
-    let main_fk_pair =
-        linker_table.get_local_foreign_columns_pair_to_table(&main_table.name);
-    let indirect_fk_pair = linker_table
-        .get_local_foreign_columns_pair_to_table(&indirect_table.name);
-    assert_eq!(main_fk_pair.len(), 1);
-    assert_eq!(indirect_fk_pair.len(), 1);
-    let (main_linker_local, main_linker_refferred) = main_fk_pair[0];
-    let (indirect_linker_local, indirect_linker_refferred) =
-        indirect_fk_pair[0];
-    let main_pk_value = main_record
-        .get_value(&main_linker_refferred.name)
-        .expect("must have a value");
-    let indirect_pk_value = indirect_record
-        .get_value(&indirect_linker_refferred.name)
-        .expect("must have a value");
+    let (main_reference, indirect_reference) = TableIntel(linker_table)
+        .get_linker_references(&main_table.name, &indirect_table.name)
+        .ok_or(IntelError::NotFound)?;
+    let main_fk_pair = table_intel::reference_pairs(&main_reference);
+    let indirect_fk_pair = table_intel::reference_pairs(&indirect_reference);
     let mut linker_record = Dao::new();
-    linker_record.insert_value(&main_linker_local.name, main_pk_value);
-    linker_record.insert_value(&indirect_linker_local.name, indirect_pk_value);
+    copy_fk_values(&mut linker_record, &main_fk_pair, main_record)?;
+    copy_fk_values(&mut linker_record, &indirect_fk_pair, indirect_record)?;
+    for column in TableIntel(linker_table).get_linker_extra_columns() {
+        let linker_column_name = data_container::linker_column_name(
+            &linker_table.name,
+            &column.name.name,
+        );
+        if let Some(value) = indirect_record.get_value(&linker_column_name) {
+            linker_record.insert_value(&column.name.name, value);
+        }
+    }
     Ok(linker_record)
 }
 
+/// set the local columns of the foreign key
+/// with the values of the referred columns of the record,
+/// all the columns of a composite foreign key are set
+fn copy_fk_values(
+    local_record: &mut Dao,
+    local_referred_pair: &[(&ColumnName, &ColumnName)],
+    referred_record: &Dao,
+) -> Result<(), IntelError> {
+    if local_referred_pair.is_empty() {
+        return Err(IntelError::NotFound);
+    }
+    for (local, referred) in local_referred_pair.iter() {
+        let value =
+            referred_record.get_value(&referred.name).ok_or_else(|| {
+                IntelError::ParamParseError(format!(
+                    "missing value of {}",
+                    referred.name
+                ))
+            })?;
+        local_record.insert_value(&local.name, value);
+    }
+    Ok(())
+}
+
 /// create an entry to the linker table
 /// linking existing record from the indirect table
 fn link_existing_for_indirect_table(
     dm: &mut DaoManager,
-    _tables: &[Table],
     main_table: &Table,
     main_record: &Dao,
     indirect_table: &Table,
     linker_table: &Table,
     rows: &Rows,
+    journal: &mut Journal,
 ) -> Result<(), IntelError> {
     for indirect_record in rows.iter() {
         let linker_record = create_linker_record(
@@ -438,7 +1056,13 @@ fn link_existing_for_indirect_table(
             indirect_table,
             &indirect_record,
         )?;
-        insert_record_to_linker_table(dm, linker_table, &linker_record)?;
+        let linker_record =
+            insert_record_to_linker_table(dm, linker_table, &linker_record)?;
+        journal.record_insert(
+            &linker_table.name,
+            RecordAction::LinkExisting,
+            linker_record,
+        );
     }
     Ok(())
 }
@@ -446,27 +1070,60 @@ fn link_existing_for_indirect_table(
 /// triggered by the main tab
 fn update_records_in_table(
     dm: &mut DaoManager,
+    db_url: &str,
     main_table: &Table,
     rows: &Rows,
+    originals: Option<&Rows>,
+    journal: &mut Journal,
 ) -> Result<Vec<Dao>, IntelError> {
+    let originals: Vec<Dao> = originals
+        .map(|rows| rows.iter().collect())
+        .unwrap_or_default();
     let mut records = vec![];
-    for record in rows.iter() {
-        let updated_record = update_record_in_table(dm, main_table, &record)?;
+    for (i, record) in rows.iter().enumerate() {
+        let updated_record = update_record_in_table(
+            dm,
+            db_url,
+            main_table,
+            &record,
+            originals.get(i),
+            journal,
+        )?;
         records.push(updated_record);
     }
     Ok(records)
 }
 
+/// update only the columns that are present in the record,
+/// and are changed from the original if it is supplied.
+/// when the original is supplied
+/// the update fails with a conflict if the row has been changed since it was read
 fn update_record_in_table(
     dm: &mut DaoManager,
+    db_url: &str,
     main_table: &Table,
     record: &Dao,
-) -> Result<Dao, DbError> {
+    original: Option<&Dao>,
+    journal: &mut Journal,
+) -> Result<Dao, IntelError> {
+    validation::validate_record(dm, db_url, main_table, record, false)?;
+    let current = select_record(dm, db_url, main_table, record)?
+        .ok_or(IntelError::NotFound)?;
+    if let Some(original) = original {
+        check_update_conflict(main_table, &current, original)?;
+    }
     let table_name = &main_table.name;
     let mut params = vec![];
-    let mut sql = format!("UPDATE {} ", table_name.complete_name());
-    let columns = main_table.get_non_primary_columns();
-    sql += "SET ";
+    let columns: Vec<&Column> = main_table
+        .get_non_primary_columns()
+        .into_iter()
+        .filter(|col| is_changed_column(col, record, original))
+        .collect();
+    if columns.is_empty() {
+        // nothing is changed, return the record as it is
+        return Ok(current);
+    }
+    let mut sql = format!("UPDATE {} SET ", table_name.complete_name());
     for (i, col) in columns.iter().enumerate() {
         let col_sql_type = col.get_sql_type();
         if i > 0 {
@@ -500,72 +1157,202 @@ fn update_record_in_table(
             sql += "AND ";
         }
         sql += &format!("{} = ${} ", pk.name.name, non_pk_columns_len + i + 1);
-        let pk_value = record.get_value(&pk.name.name);
-        assert!(pk_value.is_some());
-        let pk_value = pk_value.unwrap();
+        let pk_value = get_primary_value(record, &pk.name.name)?;
         let casted_pk_value =
             rustorm::common::cast_type(&pk_value, &pk.get_sql_type());
         params.push(casted_pk_value);
     }
     sql += "RETURNING *";
+    let bparams: Vec<&Value> = params.iter().collect();
+    let updated_record = dm.execute_sql_with_one_return(&sql, &bparams)?;
+    journal.record_update(
+        table_name,
+        Some(current),
+        Some(updated_record.clone()),
+    );
+    Ok(updated_record)
+}
+
+/// the column is changed when the record has a value for it,
+/// and it is different from the originally read value if there is any
+fn is_changed_column(
+    col: &Column,
+    record: &Dao,
+    original: Option<&Dao>,
+) -> bool {
+    // the client has only the uri of the bytes of the binary columns
+    if media::is_binary(&col.get_sql_type()) {
+        return false;
+    }
+    let value = match record.get_value(&col.name.name) {
+        Some(value) => value,
+        None => return false,
+    };
+    let original_value =
+        original.and_then(|original| original.get_value(&col.name.name));
+    match original_value {
+        Some(original_value) => {
+            let sql_type = col.get_sql_type();
+            rustorm::common::cast_type(&value, &sql_type)
+                != rustorm::common::cast_type(&original_value, &sql_type)
+        }
+        None => true,
+    }
+}
 
-    println!("sql: {}", sql);
-    println!("params: {:?}", params);
+/// select the row of the record as it is currently in the database,
+/// the row is locked until the end of the transaction in postgresql
+fn select_record(
+    dm: &mut DaoManager,
+    db_url: &str,
+    main_table: &Table,
+    record: &Dao,
+) -> Result<Option<Dao>, IntelError> {
+    let mut params = vec![];
+    let mut sql = String::from("SELECT ");
+    for (i, col) in main_table.columns.iter().enumerate() {
+        if i > 0 {
+            sql += ", ";
+        }
+        sql += &col.name.name;
+        if let Some(cast) = col.cast_as() {
+            sql += &format!("::{} AS {}", cast.name(), col.name.name);
+        }
+    }
+    sql += &format!(" FROM {} ", main_table.complete_name());
+    let primary_columns = &main_table.get_primary_columns();
+    for (i, pk) in primary_columns.iter().enumerate() {
+        if i == 0 {
+            sql += "WHERE ";
+        } else {
+            sql += "AND ";
+        }
+        sql += &format!("{} = ${} ", pk.name.name, i + 1);
+        let pk_value = get_primary_value(record, &pk.name.name)?;
+        let casted_pk_value =
+            rustorm::common::cast_type(&pk_value, &pk.get_sql_type());
+        params.push(casted_pk_value);
+    }
+    if db_url.starts_with("postgres") {
+        sql += "FOR UPDATE";
+    }
     let bparams: Vec<&Value> = params.iter().collect();
-    dm.execute_sql_with_one_return(&sql, &bparams)
+    let current = dm.execute_sql_with_maybe_one_return(&sql, &bparams)?;
+    Ok(current)
+}
+
+/// compare the current values of the record to the originally read values,
+/// the binary columns are not compared since their bytes are not read
+fn check_update_conflict(
+    main_table: &Table,
+    current: &Dao,
+    original: &Dao,
+) -> Result<(), IntelError> {
+    let columns = main_table
+        .columns
+        .iter()
+        .filter(|col| original.get_value(&col.name.name).is_some())
+        .filter(|col| !media::is_binary(&col.get_sql_type()));
+    let mut conflicts = vec![];
+    for col in columns {
+        let sql_type = col.get_sql_type();
+        let original_value = original
+            .get_value(&col.name.name)
+            .map(|v| rustorm::common::cast_type(&v, &sql_type))
+            .unwrap_or(Value::Nil);
+        let current_value = current
+            .get_value(&col.name.name)
+            .map(|v| rustorm::common::cast_type(&v, &sql_type))
+            .unwrap_or(Value::Nil);
+        if original_value != current_value {
+            conflicts.push(ColumnConflict {
+                column_name: col.name.clone(),
+                original: original_value,
+                current: current_value,
+            });
+        }
+    }
+    if conflicts.is_empty() {
+        Ok(())
+    } else {
+        Err(IntelError::UpdateConflict {
+            table_name: main_table.name.clone(),
+            conflicts,
+        })
+    }
 }
 
 /// insert rows all at once in one query
 fn insert_rows_to_table(
     dm: &mut DaoManager,
+    db_url: &str,
     table: &Table,
     rows: &Rows,
 ) -> Result<Rows, IntelError> {
-    let table_name = &table.name;
+    let rows = &plugin::apply_defaults_to_rows(table, rows)?;
+    if rows.data.is_empty() {
+        return Ok(Rows::new(vec![]));
+    }
+    for dao in rows.iter() {
+        validation::validate_record(dm, db_url, table, &dao, true)?;
+    }
+    let (sql, params) = build_insert_rows_sql(table, rows)?;
+    let bparams: Vec<&Value> = params.iter().collect();
+    let rows = dm.execute_sql_with_return(&sql, &bparams)?;
+    Ok(rows)
+}
+
+/// the generated columns are left out when none of the rows has their value,
+/// otherwise the rows without their value use the DEFAULT
+/// ie: INSERT INTO t (a, b) VALUES ($1, $2), ($3, DEFAULT) RETURNING *
+fn build_insert_rows_sql(
+    table: &Table,
+    rows: &Rows,
+) -> Result<(String, Vec<Value>), IntelError> {
+    let is_generated =
+        |col: &Column| col.is_not_null() && col.has_generated_default();
+    let columns: Vec<&Column> = table
+        .get_non_primary_columns()
+        .into_iter()
+        .filter(|col| !(is_generated(col) && are_all_nil(&col.name.name, rows)))
+        .collect();
+    let column_names: Vec<&str> =
+        columns.iter().map(|col| col.name.name.as_str()).collect();
+    let mut sql = format!(
+        "INSERT INTO {} ({}) VALUES ",
+        table.complete_name(),
+        column_names.join(", ")
+    );
     let mut params = vec![];
-    let mut sql = format!("INSERT INTO {} ", table_name.complete_name());
-    let columns = &table.get_non_primary_columns();
-    sql += "(";
-    for (i, col) in columns.iter().enumerate() {
-        if are_all_nil(&col.name.name, rows)
-            && col.is_not_null()
-            && col.has_generated_default()
-        {
-        } else {
-            if i > 0 {
+    for (i, dao) in rows.iter().enumerate() {
+        if i > 0 {
+            sql += ", ";
+        }
+        sql += "(";
+        for (j, col) in columns.iter().enumerate() {
+            let value = dao.get_value(&col.name.name).ok_or_else(|| {
+                IntelError::ParamParseError(format!(
+                    "missing value of column {}",
+                    col.name.name
+                ))
+            })?;
+            if j > 0 {
                 sql += ", ";
             }
-            sql += &format!("{} ", col.name.name);
-        }
-    }
-    sql += ") ";
-    sql += "VALUES (";
-    for dao in rows.iter() {
-        for (i, col) in columns.iter().enumerate() {
-            let value = dao.get_value(&col.name.name);
-            assert!(value.is_some());
-            let value = value.unwrap();
-            if value == &Value::Nil
-                && col.is_not_null()
-                && col.has_generated_default()
-            {
+            if *value == Value::Nil && is_generated(col) {
+                sql += "DEFAULT";
             } else {
-                if i > 0 {
-                    sql += ", ";
-                }
-                sql += &format!("${} ", params.len() + 1);
-                let casted_value =
-                    rustorm::common::cast_type(&value, &col.get_sql_type());
-                params.push(casted_value);
+                sql += &format!("${}", params.len() + 1);
+                params.push(rustorm::common::cast_type(
+                    &value,
+                    &col.get_sql_type(),
+                ));
             }
         }
+        sql += ")";
     }
-    sql += ") RETURNING *";
-    println!("sql: {}", sql);
-    println!("params: {:?}", params);
-    let bparams: Vec<&Value> = params.iter().collect();
-    let rows = dm.execute_sql_with_return(&sql, &bparams)?;
-    Ok(rows)
+    sql += " RETURNING *";
+    Ok((sql, params))
 }
 
 /// check if all values in these columns are nill,
@@ -583,12 +1370,14 @@ fn are_all_nil(column: &str, rows: &Rows) -> bool {
 /// insert rows 1 by 1
 pub fn insert_records_to_table1(
     dm: &mut DaoManager,
+    db_url: &str,
     main_table: &Table,
     rows: &Rows,
 ) -> Result<Vec<Dao>, IntelError> {
     let mut records = vec![];
     for dao in rows.iter() {
-        let updated_record = insert_record_to_table(dm, main_table, &dao)?;
+        let updated_record =
+            insert_record_to_table(dm, db_url, main_table, &dao)?;
         records.push(updated_record);
     }
     Ok(records)
@@ -596,9 +1385,12 @@ pub fn insert_records_to_table1(
 
 fn insert_record_to_table(
     dm: &mut DaoManager,
+    db_url: &str,
     main_table: &Table,
     record: &Dao,
-) -> Result<Dao, DbError> {
+) -> Result<Dao, IntelError> {
+    let record = &plugin::apply_defaults(main_table, record)?;
+    validation::validate_record(dm, db_url, main_table, record, true)?;
     let table_name = &main_table.name;
     let mut params = vec![];
     let mut sql = format!("INSERT INTO {} ", table_name.complete_name());
@@ -640,10 +1432,9 @@ fn insert_record_to_table(
         }
     }
     sql += ") RETURNING *";
-    println!("sql: {}", sql);
-    println!("params: {:?}", params);
     let bparams: Vec<&Value> = params.iter().collect();
-    dm.execute_sql_with_one_return(&sql, &bparams)
+    let inserted = dm.execute_sql_with_one_return(&sql, &bparams)?;
+    Ok(inserted)
 }
 
 fn insert_record_to_linker_table(
@@ -654,46 +1445,42 @@ fn insert_record_to_linker_table(
     let table_name = &linker_table.name;
     let mut params = vec![];
     let mut sql = format!("INSERT INTO {} ", table_name.complete_name());
-    let columns = &linker_table.get_primary_columns();
-    sql += "(";
-    for (i, col) in columns.iter().enumerate() {
-        let value = record.get_value(&col.name.name);
-        if let Some(value) = value {
-            if value.is_nil()
-                && col.is_not_null()
-                && col.has_generated_default()
-            {
-            } else {
-                if i > 0 {
-                    sql += ", ";
+    // the keys and the extra fields of the linker that are supplied
+    let columns: Vec<&Column> = linker_table
+        .columns
+        .iter()
+        .filter(|col| {
+            match record.get_value(&col.name.name) {
+                Some(value) => {
+                    !(value.is_nil()
+                        && col.is_not_null()
+                        && col.has_generated_default())
                 }
-                sql += &format!("{} ", col.name.name);
+                None => false,
             }
+        })
+        .collect();
+    sql += "(";
+    for (i, col) in columns.iter().enumerate() {
+        if i > 0 {
+            sql += ", ";
         }
+        sql += &format!("{} ", col.name.name);
     }
     sql += ") ";
     sql += "VALUES (";
     for (i, col) in columns.iter().enumerate() {
-        let value = record.get_value(&col.name.name);
-        if let Some(value) = value {
-            if value.is_nil()
-                && col.is_not_null()
-                && col.has_generated_default()
-            {
-            } else {
-                if i > 0 {
-                    sql += ", ";
-                }
-                sql += &format!("${} ", params.len() + 1);
-                let casted_value =
-                    rustorm::common::cast_type(&value, &col.get_sql_type());
-                params.push(casted_value);
-            }
+        if i > 0 {
+            sql += ", ";
         }
+        sql += &format!("${} ", params.len() + 1);
+        let value =
+            record.get_value(&col.name.name).expect("must have a value");
+        let casted_value =
+            rustorm::common::cast_type(&value, &col.get_sql_type());
+        params.push(casted_value);
     }
     sql += ") RETURNING *";
-    println!("sql: {}", sql);
-    println!("params: {:?}", params);
     let bparams: Vec<&Value> = params.iter().collect();
     dm.execute_sql_with_one_return(&sql, &bparams)
 }
@@ -707,16 +1494,14 @@ fn upsert_one_one_record_to_table(
     main_record: &Dao,
     one_one_table: &Table,
     one_one_record: &Dao,
-) -> Result<Dao, DbError> {
+) -> Result<Dao, IntelError> {
     let local_referred_pair =
         one_one_table.get_local_foreign_columns_pair_to_table(&main_table.name);
 
     let mut one_one_record = one_one_record.clone();
 
     for (one_one_pk, main_pk_name) in local_referred_pair.iter() {
-        let main_pk_value = main_record
-            .get_value(&main_pk_name.name)
-            .expect("should have value");
+        let main_pk_value = get_primary_value(main_record, &main_pk_name.name)?;
         one_one_record.insert_value(&one_one_pk.name, main_pk_value);
     }
 
@@ -808,16 +1593,124 @@ fn upsert_one_one_record_to_table(
         );
         let main_pk =
             main_table.get_column(main_pk_name).expect("should exist");
-        let pk_value = main_record.get_value(&main_pk.name.name);
-        assert!(pk_value.is_some());
-        let pk_value = pk_value.unwrap();
+        let pk_value = get_primary_value(main_record, &main_pk.name.name)?;
         let casted_pk_value =
             rustorm::common::cast_type(&pk_value, &main_pk.get_sql_type());
         params.push(casted_pk_value);
     }
     sql += "RETURNING *";
-    println!("sql: {}", sql);
-    println!("params: {:?}", params);
     let bparams: Vec<&Value> = params.iter().collect();
-    dm.execute_sql_with_one_return(&sql, &bparams)
+    Ok(dm.execute_sql_with_one_return(&sql, &bparams)?)
+}
+
+/// the value of the primary column which locates the row of the record,
+/// the record sent by the client may not have it
+fn get_primary_value<'r>(
+    record: &'r Dao,
+    column_name: &str,
+) -> Result<&'r Value, IntelError> {
+    record.get_value(column_name).ok_or_else(|| {
+        IntelError::ParamParseError(format!(
+            "missing value of primary column {}",
+            column_name
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustorm::{
+        column::ColumnSpecification,
+        types::SqlType,
+    };
+
+    fn column(table_name: &TableName, name: &str, sql_type: SqlType) -> Column {
+        Column {
+            table: table_name.clone(),
+            name: ColumnName::from(name),
+            comment: None,
+            specification: ColumnSpecification {
+                sql_type,
+                capacity: None,
+                constraints: vec![],
+            },
+            stat: None,
+        }
+    }
+
+    #[test]
+    fn insert_several_rows() {
+        let table_name = TableName::from("public.film_actor");
+        let table = Table {
+            name: table_name.clone(),
+            comment: None,
+            columns: vec![
+                column(&table_name, "actor_id", SqlType::Int),
+                column(&table_name, "film_id", SqlType::Int),
+            ],
+            is_view: false,
+            table_key: vec![],
+        };
+        let mut rows =
+            Rows::new(vec!["actor_id".to_string(), "film_id".to_string()]);
+        rows.push(vec![Value::Int(1), Value::Int(10)]);
+        rows.push(vec![Value::Int(2), Value::Int(20)]);
+        let (sql, params) =
+            build_insert_rows_sql(&table, &rows).expect("must build");
+        assert_eq!(
+            sql,
+            "INSERT INTO public.film_actor (actor_id, film_id) \
+             VALUES ($1, $2), ($3, $4) RETURNING *"
+        );
+        assert_eq!(
+            params,
+            vec![Value::Int(1), Value::Int(10), Value::Int(2), Value::Int(20)]
+        );
+    }
+
+    #[test]
+    fn copy_composite_fk_values() {
+        let film_id = ColumnName::from("film_id");
+        let store_id = ColumnName::from("store_id");
+        let id = ColumnName::from("id");
+        let store = ColumnName::from("store");
+        let mut referred_record = Dao::new();
+        referred_record.insert_value("id", &Value::Int(1));
+        referred_record.insert_value("store", &Value::Int(2));
+        let mut local_record = Dao::new();
+        copy_fk_values(
+            &mut local_record,
+            &[(&film_id, &id), (&store_id, &store)],
+            &referred_record,
+        )
+        .expect("must copy");
+        assert_eq!(local_record.get_value("film_id"), Some(&Value::Int(1)));
+        assert_eq!(local_record.get_value("store_id"), Some(&Value::Int(2)));
+    }
+
+    #[test]
+    fn copy_fk_values_with_missing_referred_value() {
+        let film_id = ColumnName::from("film_id");
+        let store_id = ColumnName::from("store_id");
+        let id = ColumnName::from("id");
+        let store = ColumnName::from("store");
+        let mut referred_record = Dao::new();
+        referred_record.insert_value("id", &Value::Int(1));
+        let mut local_record = Dao::new();
+        let result = copy_fk_values(
+            &mut local_record,
+            &[(&film_id, &id), (&store_id, &store)],
+            &referred_record,
+        );
+        match result {
+            Err(IntelError::ParamParseError(message)) => {
+                assert_eq!(message, "missing value of store")
+            }
+            _ => panic!("expecting a missing value error"),
+        }
+        assert!(
+            copy_fk_values(&mut local_record, &[], &referred_record).is_err()
+        );
+    }
 }
@@ -1,30 +1,46 @@
 use crate::{
     data_container::{
         AppData,
+        CascadeImpact,
+        CountKind,
+        Cursor,
+        PageRequest,
         QueryResult,
         RecordDetail,
+        Sort,
+        TreeRecords,
         WindowData,
     },
     error::IntelError,
-    query_builder::Query,
+    filter::Filter,
+    media,
+    query_builder::{
+        self,
+        Query,
+    },
     query_parser,
     Context,
 };
 use rustorm::{
+    ColumnName,
     Dao,
     DaoManager,
     DatabaseName,
     DbError,
     EntityManager,
     Rows,
+    Table,
     TableName,
+    Value,
 };
 use sqlparser::{
     dialect::GenericSqlDialect,
     sqlparser::Parser,
 };
 
+pub(crate) mod cascade_impact;
 mod detail_record;
+mod tree_record;
 
 pub fn get_database_name(
     em: &mut EntityManager,
@@ -50,6 +66,113 @@ pub fn fetch_detail(
     )
 }
 
+/// the page of the has_many records referring to the selected record
+/// through these columns that matches the filter, in this sort order
+#[allow(clippy::too_many_arguments)]
+pub fn fetch_has_many_records(
+    context: &Context,
+    em: &mut EntityManager,
+    dm: &mut DaoManager,
+    table_name: &TableName,
+    primary_dao: &Dao,
+    has_many_table_name: &TableName,
+    referring_columns: &[ColumnName],
+    filter: &Filter,
+    sort: &Sort,
+    page: usize,
+    page_size: usize,
+) -> Result<Rows, IntelError> {
+    let main_table =
+        context.get_table(table_name).ok_or(IntelError::NotFound)?;
+    detail_record::get_has_many_records(
+        context,
+        em,
+        dm,
+        main_table,
+        has_many_table_name,
+        referring_columns,
+        primary_dao,
+        filter,
+        sort,
+        page,
+        page_size,
+    )
+}
+
+/// the page of the indirect records of the selected record
+/// that matches the filter, in this sort order
+#[allow(clippy::too_many_arguments)]
+pub fn fetch_indirect_records(
+    context: &Context,
+    em: &mut EntityManager,
+    dm: &mut DaoManager,
+    table_name: &TableName,
+    primary_dao: &Dao,
+    linker_table_name: &TableName,
+    indirect_table_name: &TableName,
+    filter: &Filter,
+    sort: &Sort,
+    page: usize,
+    page_size: usize,
+) -> Result<Rows, IntelError> {
+    let main_table =
+        context.get_table(table_name).ok_or(IntelError::NotFound)?;
+    detail_record::get_indirect_records(
+        context,
+        em,
+        dm,
+        main_table,
+        indirect_table_name,
+        linker_table_name,
+        primary_dao,
+        filter,
+        sort,
+        page,
+        page_size,
+    )
+}
+
+/// the ancestors and the page of the children of the record in the hierarchy
+/// formed by these columns of the table referring to itself,
+/// the roots of the hierarchy are listed when there is no record
+#[allow(clippy::too_many_arguments)]
+pub fn fetch_tree_records(
+    context: &Context,
+    dm: &mut DaoManager,
+    db_url: &str,
+    table_name: &TableName,
+    parent_columns: &[ColumnName],
+    record: Option<&Dao>,
+    page: usize,
+    page_size: usize,
+) -> Result<TreeRecords, IntelError> {
+    let table = context.get_table(table_name).ok_or(IntelError::NotFound)?;
+    tree_record::get_tree_records(
+        context,
+        dm,
+        db_url,
+        table,
+        parent_columns,
+        record,
+        page,
+        page_size,
+    )
+}
+
+/// count the dependent rows that would be deleted, unlinked
+/// or would block the delete of the records with these record ids
+pub fn get_cascade_impact(
+    context: &Context,
+    dm: &mut DaoManager,
+    db_url: &str,
+    table_name: &TableName,
+    record_ids: &[String],
+) -> Result<CascadeImpact, IntelError> {
+    cascade_impact::get_cascade_impact(
+        context, dm, db_url, table_name, record_ids,
+    )
+}
+
 pub fn execute_sql_query(
     context: &Context,
     em: &mut EntityManager,
@@ -79,50 +202,161 @@ pub fn execute_sql_query(
     Ok(QueryResult::with_rows(window, rows))
 }
 
+/// the page of the main table rows, the total rows is only counted
+/// on the first page since it stays the same on the next pages.
+/// The count is estimated when the table has more rows than
+/// the estimate threshold and there is no filter
+#[allow(clippy::too_many_arguments)]
 pub fn get_window_main_table_data(
     context: &Context,
     em: &mut EntityManager,
     dm: &mut DaoManager,
+    db_url: &str,
     table_name: &TableName,
-    page: usize,
+    filter: &Filter,
+    sort: &Sort,
+    page: &PageRequest,
     page_size: usize,
+    estimate_threshold: Option<usize>,
 ) -> Result<QueryResult, IntelError> {
-    let rows =
-        fetch_main_table_data(context, em, dm, table_name, page, page_size)?;
+    let (mut rows, cursor) = fetch_main_table_data(
+        context, dm, table_name, filter, sort, page, page_size,
+    )?;
+    let count_kind = if *page == PageRequest::Number(1) {
+        let (count, count_kind) = count_main_table_rows(
+            context,
+            em,
+            dm,
+            db_url,
+            table_name,
+            filter,
+            estimate_threshold,
+        )?;
+        rows.count = Some(count);
+        Some(count_kind)
+    } else {
+        None
+    };
     let window = context.get_window(table_name);
-    Ok(QueryResult::with_rows(window, rows))
+    let mut query_result = QueryResult::with_rows(window, rows);
+    query_result.cursor = cursor;
+    query_result.count_kind = count_kind;
+    Ok(query_result)
 }
 
+/// the page of the main table rows and the cursor of the next page,
+/// a page requested by cursor seeks directly to the rows after it
+/// instead of skipping all the rows before it
 fn fetch_main_table_data(
     context: &Context,
-    em: &mut EntityManager,
     dm: &mut DaoManager,
     table_name: &TableName,
-    page: usize,
+    filter: &Filter,
+    sort: &Sort,
+    page: &PageRequest,
     page_size: usize,
-) -> Result<Rows, IntelError> {
-    let main_table = context
-        .get_table(table_name)
-        .expect("there should be table");
+) -> Result<(Rows, Option<Cursor>), IntelError> {
+    let main_table =
+        context.get_table(table_name).ok_or(IntelError::NotFound)?;
 
     let mut query = Query::new(context, dm);
     query.select();
     query.enumerate_columns(&main_table);
+    query.enumerate_display_columns(&main_table);
     query.from(table_name);
-    query.set_page(page, page_size);
+    query.join_display_tables(&main_table);
+    query.add_filter(main_table, filter)?;
+    if let PageRequest::After(cursor) = page {
+        query.add_keyset_condition(main_table, sort, cursor)?;
+    }
+    query.set_sort(main_table, sort)?;
+    match page {
+        PageRequest::Number(page) => query.set_page(*page, page_size),
+        PageRequest::After(_) => query.set_limit(page_size),
+    }
     let mut rows = query.collect_rows()?;
+    media::to_media_uris(context, main_table, &mut rows);
     println!("Returning: {} rows", rows.data.len());
-    let row_count = em.get_total_records(table_name)?;
-    rows.count = Some(row_count);
-    Ok(rows)
+    let cursor =
+        query_builder::get_next_cursor(main_table, sort, &rows, page_size)?;
+    Ok((rows, cursor))
+}
+
+/// the total rows of the main table that passes the filter
+fn count_main_table_rows(
+    context: &Context,
+    em: &mut EntityManager,
+    dm: &mut DaoManager,
+    db_url: &str,
+    table_name: &TableName,
+    filter: &Filter,
+    estimate_threshold: Option<usize>,
+) -> Result<(usize, CountKind), IntelError> {
+    let main_table =
+        context.get_table(table_name).ok_or(IntelError::NotFound)?;
+    if !filter.is_empty() {
+        let mut count_query = Query::new(context, dm);
+        count_query.select_count(main_table);
+        count_query.from(table_name);
+        count_query.add_filter(main_table, filter)?;
+        let count = count_query.collect_count()?;
+        return Ok((count, CountKind::Exact));
+    }
+    if let Some(threshold) = estimate_threshold {
+        if let Some(estimate) = get_estimated_count(dm, db_url, main_table) {
+            if estimate > threshold {
+                return Ok((estimate, CountKind::Estimated));
+            }
+        }
+    }
+    let count = em.get_total_records(table_name)?;
+    Ok((count, CountKind::Exact))
+}
+
+/// the row count from the statistics of the table, which is updated
+/// on ANALYZE and VACUUM. None when it is not available,
+/// such as when the table is never analyzed or it is not postgresql.
+/// A failure to read the statistics is logged, the rows are counted instead
+fn get_estimated_count(
+    dm: &mut DaoManager,
+    db_url: &str,
+    table: &Table,
+) -> Option<usize> {
+    if !db_url.starts_with("postgres") {
+        return None;
+    }
+    let sql = "SELECT reltuples::bigint AS count \
+               FROM pg_class WHERE oid = $1::text::regclass";
+    let params = [Value::Text(table.complete_name())];
+    let bparams: Vec<&Value> = params.iter().collect();
+    match dm.execute_sql_with_one_return(sql, &bparams) {
+        Ok(dao) => {
+            match dao.get_value("count") {
+                Some(Value::Bigint(count)) if *count >= 0 => {
+                    Some(*count as usize)
+                }
+                _ => None,
+            }
+        }
+        Err(e) => {
+            warn!(
+                "unable to estimate the row count of {}: {:?}",
+                table.complete_name(),
+                e
+            );
+            None
+        }
+    }
 }
 
 pub fn retrieve_app_data(
     context: &Context,
     em: &mut EntityManager,
     dm: &mut DaoManager,
+    db_url: &str,
     table_name: Option<TableName>,
     page_size: usize,
+    estimate_threshold: Option<usize>,
 ) -> Result<AppData, IntelError> {
     let grouped_window = context.grouped_window.clone();
     println!("table_name: {:#?}", table_name);
@@ -131,18 +365,23 @@ pub fn retrieve_app_data(
     } else {
         &grouped_window[0].window_names[0].table_name
     };
-    let rows = fetch_main_table_data(
+    let query_result = get_window_main_table_data(
         context,
         em,
         dm,
+        db_url,
         retrieve_table_name,
-        1,
+        &Filter::default(),
+        &Sort::default(),
+        &PageRequest::Number(1),
         page_size,
+        estimate_threshold,
     )?;
     let first_window = context
         .get_window(retrieve_table_name)
         .expect("expecting a window");
-    let first_window_data = WindowData::from_rows(rows);
+    let mut first_window_data = WindowData::from_rows(query_result.rows);
+    first_window_data.main_tab_cursor = query_result.cursor;
     Ok(AppData {
         grouped_window,
         windows: vec![first_window.clone()],
@@ -0,0 +1,694 @@
+//! preview of the dependent rows that are affected
+//! when the selected records are deleted
+use crate::{
+    common,
+    data_container::{
+        CascadeImpact,
+        ImpactKind,
+        TableImpact,
+    },
+    error::IntelError,
+    table_intel::TableIntel,
+    Context,
+    TableName,
+};
+use rustorm::{
+    table::ForeignKey,
+    ColumnName,
+    Dao,
+    DaoManager,
+    Rows,
+    Table,
+    Value,
+};
+
+/// walk the 1:1, has_many and linker tables of the main table
+/// and count the rows referring to the selected records
+pub fn get_cascade_impact(
+    context: &Context,
+    dm: &mut DaoManager,
+    db_url: &str,
+    table_name: &TableName,
+    record_ids: &[String],
+) -> Result<CascadeImpact, IntelError> {
+    let main_table =
+        context.get_table(table_name).ok_or(IntelError::NotFound)?;
+    let pk_types = &main_table.get_primary_column_types();
+    let primary_columns = &main_table.get_primary_column_names();
+    let mut record_id_values = Vec::with_capacity(record_ids.len());
+    for rid in record_ids.iter() {
+        let record_id_value: Vec<(&ColumnName, Value)> =
+            common::extract_record_id(rid, pk_types, primary_columns)?;
+        record_id_values.push(record_id_value);
+    }
+    let tables: Vec<Table> = context.tables.values().cloned().collect();
+    let table_intel = TableIntel(main_table);
+
+    let mut one_ones = vec![];
+    for one_one_table in table_intel.get_one_one_tables(&tables) {
+        one_ones.push(get_table_impact(
+            dm,
+            db_url,
+            main_table,
+            one_one_table,
+            &record_id_values,
+            false,
+        )?);
+    }
+    let mut has_many = vec![];
+    for has_many_table in table_intel.get_has_many_tables(&tables) {
+        has_many.push(get_table_impact(
+            dm,
+            db_url,
+            main_table,
+            has_many_table,
+            &record_id_values,
+            false,
+        )?);
+    }
+    let mut indirect = vec![];
+    for indirect_table in table_intel.get_indirect_tables(&tables) {
+        indirect.push(get_table_impact(
+            dm,
+            db_url,
+            main_table,
+            indirect_table.linker,
+            &record_id_values,
+            true,
+        )?);
+    }
+    Ok(CascadeImpact {
+        table_name: main_table.name.clone(),
+        one_ones,
+        has_many,
+        indirect,
+    })
+}
+
+fn get_table_impact(
+    dm: &mut DaoManager,
+    db_url: &str,
+    main_table: &Table,
+    referring_table: &Table,
+    record_ids: &[Vec<(&ColumnName, Value)>],
+    is_linker: bool,
+) -> Result<TableImpact, IntelError> {
+    let delete_rules =
+        get_delete_rules(dm, db_url, main_table, referring_table)?;
+    let foreign_keys: Vec<&ForeignKey> =
+        delete_rules.iter().map(|(fk, _rule)| *fk).collect();
+    let count =
+        count_referring_rows(dm, referring_table, &foreign_keys, record_ids)?;
+    Ok(to_table_impact(
+        referring_table,
+        &delete_rules,
+        count,
+        is_linker,
+    ))
+}
+
+/// the impact on the referring table is of the most restrictive rule
+/// among its foreign keys to the main table
+fn to_table_impact(
+    referring_table: &Table,
+    delete_rules: &[(&ForeignKey, Option<String>)],
+    count: usize,
+    is_linker: bool,
+) -> TableImpact {
+    let rules: Vec<Option<&str>> = delete_rules
+        .iter()
+        .map(|(_fk, rule)| rule.as_ref().map(String::as_str))
+        .collect();
+    let kind = to_impact_kind(most_restrictive_rule(&rules), is_linker);
+    TableImpact {
+        table_name: referring_table.name.clone(),
+        kind,
+        count,
+    }
+}
+
+/// the rows of a referring table which the database deletes
+/// or sets to null when the records they refer to are deleted
+pub(crate) struct CascadedRows {
+    pub table_name: TableName,
+    /// the rows are deleted, otherwise only their reference is set to null
+    pub is_deleted: bool,
+    pub rows: Rows,
+}
+
+/// the rows of the tables that refers to the records, down to the rows
+/// that refers to the rows deleted by a cascade, which are listed before
+/// the rows they refer to. Rows that would block the delete are not included
+pub(crate) fn get_cascaded_rows(
+    context: &Context,
+    dm: &mut DaoManager,
+    db_url: &str,
+    main_table: &Table,
+    record_ids: &[Vec<(&ColumnName, Value)>],
+) -> Result<Vec<CascadedRows>, IntelError> {
+    let mut cascaded_rows = vec![];
+    let mut visited = vec![];
+    collect_cascaded_rows(
+        context,
+        dm,
+        db_url,
+        main_table,
+        record_ids,
+        &mut visited,
+        &mut cascaded_rows,
+    )?;
+    Ok(cascaded_rows)
+}
+
+/// the visited rows are not collected again,
+/// so rows that refer to each other in a cycle are only collected once
+fn collect_cascaded_rows(
+    context: &Context,
+    dm: &mut DaoManager,
+    db_url: &str,
+    main_table: &Table,
+    record_ids: &[Vec<(&ColumnName, Value)>],
+    visited: &mut Vec<(TableName, Dao)>,
+    cascaded_rows: &mut Vec<CascadedRows>,
+) -> Result<(), IntelError> {
+    let referring_tables = context.tables.values().filter(|table| {
+        table
+            .get_foreign_keys()
+            .iter()
+            .any(|fk| fk.foreign_table == main_table.name)
+    });
+    for referring_table in referring_tables {
+        let mut delete_rules = vec![];
+        for (fk, rule) in
+            get_delete_rules(dm, db_url, main_table, referring_table)?
+        {
+            match rule.as_ref().map(String::as_str) {
+                Some("CASCADE") => delete_rules.push((fk, true)),
+                Some("SET NULL") | Some("SET DEFAULT") => {
+                    delete_rules.push((fk, false))
+                }
+                _ => (),
+            }
+        }
+        // a row deleted through one foreign key and set to null
+        // through another is collected as deleted
+        delete_rules.sort_by_key(|(_fk, is_deleted)| !is_deleted);
+        for (fk, is_deleted) in delete_rules {
+            collect_referring_rows(
+                context,
+                dm,
+                db_url,
+                referring_table,
+                fk,
+                is_deleted,
+                record_ids,
+                visited,
+                cascaded_rows,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// the rows referring to the records through this foreign key,
+/// followed by the rows referring to them when they are deleted
+#[allow(clippy::too_many_arguments)]
+fn collect_referring_rows(
+    context: &Context,
+    dm: &mut DaoManager,
+    db_url: &str,
+    referring_table: &Table,
+    fk: &ForeignKey,
+    is_deleted: bool,
+    record_ids: &[Vec<(&ColumnName, Value)>],
+    visited: &mut Vec<(TableName, Dao)>,
+    cascaded_rows: &mut Vec<CascadedRows>,
+) -> Result<(), IntelError> {
+    let selected =
+        select_referring_rows(dm, referring_table, &[fk], record_ids)?;
+    let mut rows = Rows::new(selected.columns.clone());
+    for (row, dao) in selected.data.iter().zip(selected.iter()) {
+        let key = (referring_table.name.clone(), dao);
+        if !visited.contains(&key) {
+            visited.push(key);
+            rows.push(row.clone());
+        }
+    }
+    if rows.data.is_empty() {
+        return Ok(());
+    }
+    if is_deleted {
+        let row_ids = to_record_values(referring_table, &rows);
+        collect_cascaded_rows(
+            context,
+            dm,
+            db_url,
+            referring_table,
+            &row_ids,
+            visited,
+            cascaded_rows,
+        )?;
+    }
+    cascaded_rows.push(CascadedRows {
+        table_name: referring_table.name.clone(),
+        is_deleted,
+        rows,
+    });
+    Ok(())
+}
+
+/// all the column values of the rows, to look up the rows referring to them
+fn to_record_values<'t>(
+    table: &'t Table,
+    rows: &Rows,
+) -> Vec<Vec<(&'t ColumnName, Value)>> {
+    rows.iter()
+        .map(|dao| {
+            table
+                .columns
+                .iter()
+                .filter_map(|column| {
+                    dao.get_value(&column.name.name)
+                        .map(|value| (&column.name, value.clone()))
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// the linker rows deleted by the cascade only unlink the indirect records,
+/// the rules that are not known are assumed to block the delete
+fn to_impact_kind(delete_rule: Option<&str>, is_linker: bool) -> ImpactKind {
+    match delete_rule {
+        Some("CASCADE") if is_linker => ImpactKind::Unlink,
+        Some("CASCADE") => ImpactKind::Delete,
+        Some("SET NULL") | Some("SET DEFAULT") => ImpactKind::Unlink,
+        _ => ImpactKind::Block,
+    }
+}
+
+/// build the condition matching the rows of the referring table
+/// that refers to any of the record ids through any of the foreign keys
+/// ie: (a = $1 AND b = $2) OR (a = $3 AND b = $4) OR (c = $5 AND d = $6)
+fn referring_condition(
+    foreign_keys: &[&ForeignKey],
+    record_ids: &[Vec<(&ColumnName, Value)>],
+) -> Result<(String, Vec<Value>), IntelError> {
+    let mut conditions = vec![];
+    let mut params: Vec<Value> = vec![];
+    for fk in foreign_keys.iter() {
+        for record_id in record_ids.iter() {
+            let mut pairs = vec![];
+            for (local, foreign) in
+                fk.columns.iter().zip(fk.referred_columns.iter())
+            {
+                let value = record_id
+                    .iter()
+                    .find(|(column, _)| column.name == foreign.name)
+                    .map(|(_, value)| value)
+                    .ok_or(IntelError::NotFound)?;
+                params.push(value.clone());
+                pairs.push(format!("{} = ${}", local.name, params.len()));
+            }
+            conditions.push(format!("({})", pairs.join(" AND ")));
+        }
+    }
+    Ok((conditions.join(" OR "), params))
+}
+
+/// count the rows of the referring table that refers to any of the record ids
+fn count_referring_rows(
+    dm: &mut DaoManager,
+    referring_table: &Table,
+    foreign_keys: &[&ForeignKey],
+    record_ids: &[Vec<(&ColumnName, Value)>],
+) -> Result<usize, IntelError> {
+    if record_ids.is_empty() || foreign_keys.is_empty() {
+        return Ok(0);
+    }
+    let (condition, params) = referring_condition(foreign_keys, record_ids)?;
+    let sql = format!(
+        "SELECT COUNT(*) AS count FROM {} WHERE {}",
+        referring_table.complete_name(),
+        condition
+    );
+    let bparams: Vec<&Value> = params.iter().collect();
+    let dao = dm.execute_sql_with_one_return(&sql, &bparams)?;
+    Ok(common::get_count(&dao, "count"))
+}
+
+/// select all the columns of the rows of the referring table
+/// that refers to any of the record ids
+fn select_referring_rows(
+    dm: &mut DaoManager,
+    referring_table: &Table,
+    foreign_keys: &[&ForeignKey],
+    record_ids: &[Vec<(&ColumnName, Value)>],
+) -> Result<Rows, IntelError> {
+    if record_ids.is_empty() || foreign_keys.is_empty() {
+        return Ok(Rows::new(vec![]));
+    }
+    let (condition, params) = referring_condition(foreign_keys, record_ids)?;
+    let mut sql = String::from("SELECT ");
+    for (i, column) in referring_table.columns.iter().enumerate() {
+        if i > 0 {
+            sql += ", ";
+        }
+        sql += &column.name.name;
+        if let Some(cast) = column.cast_as() {
+            sql += &format!("::{} AS {}", cast.name(), column.name.name);
+        }
+    }
+    sql += &format!(
+        " FROM {} WHERE {}",
+        referring_table.complete_name(),
+        condition
+    );
+    let bparams: Vec<&Value> = params.iter().collect();
+    let rows = dm.execute_sql_with_return(&sql, &bparams)?;
+    Ok(rows)
+}
+
+/// get the ON DELETE rule of each foreign key of the referring table
+/// that refers to the main table. None if it can not be determined
+fn get_delete_rules<'t>(
+    dm: &mut DaoManager,
+    db_url: &str,
+    main_table: &Table,
+    referring_table: &'t Table,
+) -> Result<Vec<(&'t ForeignKey, Option<String>)>, IntelError> {
+    let constraint_rules = if db_url.starts_with("postgres") {
+        get_pg_delete_rules(dm, referring_table)?
+    } else if db_url.starts_with("sqlite") {
+        get_sqlite_delete_rules(dm, main_table, referring_table)?
+    } else {
+        vec![]
+    };
+    Ok(match_delete_rules(
+        main_table,
+        referring_table,
+        &constraint_rules,
+    ))
+}
+
+/// match the foreign keys of the referring table to the main table
+/// with the constraint of the same local columns
+fn match_delete_rules<'t>(
+    main_table: &Table,
+    referring_table: &'t Table,
+    constraint_rules: &[(Vec<String>, String)],
+) -> Vec<(&'t ForeignKey, Option<String>)> {
+    referring_table
+        .get_foreign_keys()
+        .into_iter()
+        .filter(|fk| fk.foreign_table == main_table.name)
+        .map(|fk| {
+            let fk_columns: Vec<&str> =
+                fk.columns.iter().map(|c| c.name.as_str()).collect();
+            let rule = constraint_rules
+                .iter()
+                .find(|(columns, _rule)| *columns == fk_columns)
+                .map(|(_columns, rule)| rule.to_string());
+            (fk, rule)
+        })
+        .collect()
+}
+
+/// the rule that blocks the delete comes first,
+/// then the rule that only unlinks the rows, then the cascade
+fn most_restrictive_rule<'r>(rules: &[Option<&'r str>]) -> Option<&'r str> {
+    let rank = |rule: &Option<&str>| {
+        match rule {
+            Some("CASCADE") => 2,
+            Some("SET NULL") | Some("SET DEFAULT") => 1,
+            _ => 0,
+        }
+    };
+    rules
+        .iter()
+        .min_by_key(|rule| rank(rule))
+        .and_then(|rule| *rule)
+}
+
+/// the local columns and the ON DELETE rule of each foreign key
+/// constraint of the table, looked up by the constraint name.
+/// The table without a schema is in the current schema
+fn get_pg_delete_rules(
+    dm: &mut DaoManager,
+    table: &Table,
+) -> Result<Vec<(Vec<String>, String)>, IntelError> {
+    let mut params = vec![Value::Text(table.name.name.to_string())];
+    let schema_condition = match &table.name.schema {
+        Some(schema) => {
+            params.push(Value::Text(schema.to_string()));
+            "$2"
+        }
+        None => "current_schema()",
+    };
+    let sql = format!(
+        "SELECT rc.constraint_name::text AS constraint_name, \
+         rc.delete_rule::text AS delete_rule, \
+         kcu.column_name::text AS column_name \
+         FROM information_schema.referential_constraints rc \
+         JOIN information_schema.key_column_usage kcu \
+         ON kcu.constraint_schema = rc.constraint_schema \
+         AND kcu.constraint_name = rc.constraint_name \
+         WHERE kcu.table_name::text = $1 \
+         AND kcu.table_schema::text = {} \
+         ORDER BY rc.constraint_name, kcu.ordinal_position",
+        schema_condition
+    );
+    let bparams: Vec<&Value> = params.iter().collect();
+    let rows = dm.execute_sql_with_return(&sql, &bparams)?;
+    let constraint_columns = rows.iter().filter_map(|dao| {
+        Some((
+            get_text(&dao, "constraint_name")?,
+            get_text(&dao, "column_name")?,
+            get_text(&dao, "delete_rule")?,
+        ))
+    });
+    Ok(group_constraint_columns(constraint_columns))
+}
+
+/// sqlite has no constraint names,
+/// the foreign keys are identified by their id in the table
+fn get_sqlite_delete_rules(
+    dm: &mut DaoManager,
+    main_table: &Table,
+    referring_table: &Table,
+) -> Result<Vec<(Vec<String>, String)>, IntelError> {
+    let sql = format!("PRAGMA foreign_key_list({})", referring_table.name.name);
+    let rows = dm.execute_sql_with_return(&sql, &[])?;
+    let constraint_columns = rows
+        .iter()
+        .filter(|dao| {
+            get_text(dao, "table").as_ref() == Some(&main_table.name.name)
+        })
+        .filter_map(|dao| {
+            Some((
+                get_text(&dao, "id")?,
+                get_text(&dao, "from")?,
+                get_text(&dao, "on_delete")?,
+            ))
+        });
+    Ok(group_constraint_columns(constraint_columns))
+}
+
+fn get_text(dao: &Dao, column: &str) -> Option<String> {
+    match dao.get_value(column)? {
+        Value::Text(text) => Some(text.to_string()),
+        Value::Smallint(v) => Some(v.to_string()),
+        Value::Int(v) => Some(v.to_string()),
+        Value::Bigint(v) => Some(v.to_string()),
+        _ => None,
+    }
+}
+
+/// collect the columns of each constraint, the rows of the same constraint
+/// are listed one after the other in the order of the columns
+fn group_constraint_columns(
+    constraint_columns: impl Iterator<Item = (String, String, String)>,
+) -> Vec<(Vec<String>, String)> {
+    let mut grouped: Vec<(String, Vec<String>, String)> = vec![];
+    for (constraint, column, rule) in constraint_columns {
+        match grouped.last_mut() {
+            Some((last, columns, _rule)) if *last == constraint => {
+                columns.push(column)
+            }
+            _ => grouped.push((constraint, vec![column], rule)),
+        }
+    }
+    grouped
+        .into_iter()
+        .map(|(_constraint, columns, rule)| (columns, rule))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustorm::{
+        column::ColumnSpecification,
+        table::TableKey,
+        types::SqlType,
+        Column,
+    };
+
+    fn table(name: &str, fks: &[(&str, &str)]) -> Table {
+        let table_name = TableName::from(name);
+        let mut column_names = vec![format!("{}_id", table_name.name)];
+        column_names.extend(fks.iter().map(|(column, _)| column.to_string()));
+        let columns = column_names
+            .iter()
+            .map(|column| {
+                Column {
+                    table: table_name.clone(),
+                    name: ColumnName::from(column.as_str()),
+                    comment: None,
+                    specification: ColumnSpecification {
+                        sql_type: SqlType::Int,
+                        capacity: None,
+                        constraints: vec![],
+                    },
+                    stat: None,
+                }
+            })
+            .collect();
+        let table_key = fks
+            .iter()
+            .map(|(column, foreign_table)| {
+                let foreign_table = TableName::from(*foreign_table);
+                let referred_column = format!("{}_id", foreign_table.name);
+                TableKey::ForeignKey(ForeignKey {
+                    name: None,
+                    columns: vec![ColumnName::from(*column)],
+                    foreign_table,
+                    referred_columns: vec![ColumnName::from(
+                        referred_column.as_str(),
+                    )],
+                })
+            })
+            .collect();
+        Table {
+            name: table_name,
+            comment: None,
+            columns,
+            is_view: false,
+            table_key,
+        }
+    }
+
+    fn impact(
+        main_table: &Table,
+        referring_table: &Table,
+        constraint_rules: &[(&str, &str)],
+        is_linker: bool,
+    ) -> ImpactKind {
+        let constraint_rules: Vec<(Vec<String>, String)> = constraint_rules
+            .iter()
+            .map(|(column, rule)| (vec![column.to_string()], rule.to_string()))
+            .collect();
+        let delete_rules =
+            match_delete_rules(main_table, referring_table, &constraint_rules);
+        to_table_impact(referring_table, &delete_rules, 1, is_linker).kind
+    }
+
+    #[test]
+    fn impact_of_the_referring_tables() {
+        let store = table("public.store", &[]);
+        let staff = table("public.staff", &[("store_id", "public.store")]);
+        let customer =
+            table("public.customer", &[("store_id", "public.store")]);
+        let inventory =
+            table("public.inventory", &[("store_id", "public.store")]);
+        let transfer = table(
+            "public.transfer",
+            &[
+                ("from_store_id", "public.store"),
+                ("to_store_id", "public.store"),
+            ],
+        );
+        assert_eq!(
+            impact(&store, &staff, &[("store_id", "CASCADE")], false),
+            ImpactKind::Delete
+        );
+        assert_eq!(
+            impact(&store, &staff, &[("store_id", "CASCADE")], true),
+            ImpactKind::Unlink
+        );
+        assert_eq!(
+            impact(&store, &customer, &[("store_id", "SET NULL")], false),
+            ImpactKind::Unlink
+        );
+        assert_eq!(
+            impact(&store, &inventory, &[("store_id", "RESTRICT")], false),
+            ImpactKind::Block
+        );
+        assert_eq!(impact(&store, &inventory, &[], false), ImpactKind::Block);
+        // the rule of each foreign key to the same table is kept apart
+        let transfer_rules =
+            [("from_store_id", "CASCADE"), ("to_store_id", "SET NULL")];
+        assert_eq!(
+            impact(&store, &transfer, &transfer_rules, false),
+            ImpactKind::Unlink
+        );
+        let transfer_rules =
+            [("from_store_id", "CASCADE"), ("to_store_id", "RESTRICT")];
+        assert_eq!(
+            impact(&store, &transfer, &transfer_rules, false),
+            ImpactKind::Block
+        );
+
+        let store_id = ColumnName::from("store_id");
+        let record_ids = vec![
+            vec![(&store_id, Value::Int(1))],
+            vec![(&store_id, Value::Int(2))],
+        ];
+        let foreign_keys = transfer.get_foreign_keys();
+        let (condition, params) =
+            referring_condition(&foreign_keys, &record_ids).unwrap();
+        assert_eq!(
+            condition,
+            "(from_store_id = $1) OR (from_store_id = $2) \
+             OR (to_store_id = $3) OR (to_store_id = $4)"
+        );
+        assert_eq!(
+            params,
+            vec![Value::Int(1), Value::Int(2), Value::Int(1), Value::Int(2)]
+        );
+    }
+
+    #[test]
+    fn most_restrictive_of_the_foreign_keys() {
+        assert_eq!(
+            most_restrictive_rule(&[Some("CASCADE"), Some("SET NULL")]),
+            Some("SET NULL")
+        );
+        assert_eq!(
+            most_restrictive_rule(&[Some("CASCADE"), Some("NO ACTION")]),
+            Some("NO ACTION")
+        );
+        assert_eq!(most_restrictive_rule(&[Some("CASCADE"), None]), None);
+        assert_eq!(most_restrictive_rule(&[]), None);
+    }
+
+    #[test]
+    fn columns_are_grouped_by_constraint() {
+        let rows = vec![
+            ("fk_a".to_string(), "a1".to_string(), "CASCADE".to_string()),
+            ("fk_a".to_string(), "a2".to_string(), "CASCADE".to_string()),
+            ("fk_b".to_string(), "b".to_string(), "SET NULL".to_string()),
+        ];
+        let grouped = group_constraint_columns(rows.into_iter());
+        assert_eq!(
+            grouped,
+            vec![
+                (
+                    vec!["a1".to_string(), "a2".to_string()],
+                    "CASCADE".to_string()
+                ),
+                (vec!["b".to_string()], "SET NULL".to_string()),
+            ]
+        );
+    }
+}
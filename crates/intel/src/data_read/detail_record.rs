@@ -1,11 +1,18 @@
 use crate::{
-    data_container::RecordDetail,
+    data_container::{
+        RecordDetail,
+        Sort,
+    },
     error::IntelError,
+    filter::Filter,
+    media,
     query_builder::Query,
+    table_intel::TableIntel,
     Context,
     TableName,
 };
 use rustorm::{
+    ColumnName,
     Dao,
     DaoManager,
     EntityManager,
@@ -23,18 +30,16 @@ pub fn get_selected_record_detail(
     page_size: usize,
 ) -> Result<RecordDetail, IntelError> {
     let main_table =
-        context.get_table(table_name).expect("should have a table");
+        context.get_table(table_name).ok_or(IntelError::NotFound)?;
 
     let mut query = Query::new(context, dm);
     query.add_table_datatypes(&main_table);
     query.select();
     query.enumerate_columns(&main_table);
     query.from(&main_table.name);
-    query.add_dao_filter(&main_table.name, primary_dao);
+    query.add_dao_filter(main_table, primary_dao)?;
 
-    let window = context
-        .get_window(table_name)
-        .expect("should have a window");
+    let window = context.get_window(table_name).ok_or(IntelError::NotFound)?;
 
     let record: Dao = query.collect_one_record()?;
 
@@ -63,12 +68,17 @@ pub fn get_selected_record_detail(
             em,
             dm,
             main_table,
-            &has_many_tab.table_name,
+            &has_many_tab.tab.table_name,
+            &has_many_tab.referring_columns,
             primary_dao,
+            &Filter::default(),
+            &Sort::default(),
+            1,
             page_size,
         )?;
         println!("about to push many record: {:?}", many_record);
-        has_many_records.push((has_many_tab.table_name.clone(), many_record));
+        has_many_records
+            .push((has_many_tab.tab.table_name.clone(), many_record));
         println!("pushed");
     }
     println!("Getting indirect");
@@ -83,6 +93,9 @@ pub fn get_selected_record_detail(
             &indirect_tab.tab.table_name,
             &indirect_tab.linker,
             primary_dao,
+            &Filter::default(),
+            &Sort::default(),
+            1,
             page_size,
         )?;
         indirect_records.push((
@@ -118,64 +131,133 @@ fn get_one_one_record(
     query.select();
     query.enumerate_columns(&one_one_table);
     query.from(&main_table.name);
-    query.left_join(&main_table.name, &one_one_table.name);
-    query.add_dao_filter(&main_table.name, &primary_dao);
+    query.left_join(&main_table.name, &one_one_table.name)?;
+    query.add_dao_filter(main_table, primary_dao)?;
     query.set_limit(page_size);
 
     let one_one_record = query.collect_maybe_record()?;
     Ok(one_one_record)
 }
 
-fn get_has_many_records(
+#[allow(clippy::too_many_arguments)]
+pub(super) fn get_has_many_records(
     context: &Context,
-    em: &mut EntityManager,
+    _em: &mut EntityManager,
     dm: &mut DaoManager,
     main_table: &Table,
     has_many_table_name: &TableName,
+    referring_columns: &[ColumnName],
     primary_dao: &Dao,
+    filter: &Filter,
+    sort: &Sort,
+    page: usize,
     page_size: usize,
 ) -> Result<Rows, IntelError> {
     let has_many_table = context
         .get_table(has_many_table_name)
-        .expect("table should exist");
+        .ok_or(IntelError::NotFound)?;
     let mut query = Query::new(context, dm);
     query.select();
     query.enumerate_columns(&has_many_table);
+    query.enumerate_display_columns(&has_many_table);
 
+    // the main table is aliased since it can also be the has_many table
+    let main_alias = format!("main_{}", main_table.name.name);
     query.add_table_datatypes(&has_many_table);
-    query.from(&main_table.name);
-    query.left_join(&main_table.name, &has_many_table.name);
-    query.add_dao_filter(&main_table.name, primary_dao);
-    query.set_limit(page_size);
+    query.from_aliased(&main_table.name, &main_alias);
+    query.left_join_referring(
+        &main_table.name,
+        &has_many_table.name,
+        referring_columns,
+    )?;
+    query.join_display_tables(&has_many_table);
+    query.add_dao_filter(main_table, primary_dao)?;
+    query.add_filter(has_many_table, filter)?;
+    query.set_sort(has_many_table, sort)?;
+    query.set_page(page, page_size);
     let mut rows = query.collect_rows()?;
-    rows.count = Some(em.get_total_records(&has_many_table.name)?);
+    media::to_media_uris(context, has_many_table, &mut rows);
+
+    let mut count_query = Query::new(context, dm);
+    count_query.select_count(has_many_table);
+    count_query.from_aliased(&main_table.name, &main_alias);
+    count_query.left_join_referring(
+        &main_table.name,
+        &has_many_table.name,
+        referring_columns,
+    )?;
+    count_query.add_dao_filter(main_table, primary_dao)?;
+    count_query.add_filter(has_many_table, filter)?;
+    rows.count = Some(count_query.collect_count()?);
     Ok(rows)
 }
 
-fn get_indirect_records(
+#[allow(clippy::too_many_arguments)]
+pub(super) fn get_indirect_records(
     context: &Context,
-    em: &mut EntityManager,
+    _em: &mut EntityManager,
     dm: &mut DaoManager,
     main_table: &Table,
     indirect_table_name: &TableName,
     linker_table: &TableName,
     primary_dao: &Dao,
+    filter: &Filter,
+    sort: &Sort,
+    page: usize,
     page_size: usize,
 ) -> Result<Rows, IntelError> {
     let indirect_table = context
         .get_table(indirect_table_name)
-        .expect("table should exist");
+        .ok_or(IntelError::NotFound)?;
+    let linker = context
+        .get_table(linker_table)
+        .ok_or(IntelError::NotFound)?;
+    let (main_reference, indirect_reference) = TableIntel(linker)
+        .get_linker_references(&main_table.name, &indirect_table.name)
+        .ok_or(IntelError::NotFound)?;
+    // the main table is aliased since it can also be the indirect table
+    let main_alias = format!("main_{}", main_table.name.name);
     let mut query = Query::new(context, dm);
     query.select();
     query.enumerate_columns(&indirect_table);
+    query.enumerate_linker_columns(linker);
+    query.enumerate_display_columns(&indirect_table);
 
     query.add_table_datatypes(&indirect_table);
-    query.from(&main_table.name);
-    query.left_join(&main_table.name, &linker_table);
-    query.left_join(&linker_table, &indirect_table.name);
-    query.add_dao_filter(&main_table.name, primary_dao);
-    query.set_limit(page_size);
+    query.from_aliased(&main_table.name, &main_alias);
+    query.left_join_referring(
+        &main_table.name,
+        linker_table,
+        main_reference.0,
+    )?;
+    query.left_join_referred(
+        linker_table,
+        &indirect_table.name,
+        indirect_reference.0,
+    )?;
+    query.join_display_tables(&indirect_table);
+    query.add_dao_filter(main_table, primary_dao)?;
+    query.add_filter(indirect_table, filter)?;
+    query.set_sort(indirect_table, sort)?;
+    query.set_page(page, page_size);
     let mut rows = query.collect_rows()?;
-    rows.count = Some(em.get_total_records(&indirect_table.name)?);
+    media::to_media_uris(context, indirect_table, &mut rows);
+
+    let mut count_query = Query::new(context, dm);
+    count_query.select_count(indirect_table);
+    count_query.from_aliased(&main_table.name, &main_alias);
+    count_query.left_join_referring(
+        &main_table.name,
+        linker_table,
+        main_reference.0,
+    )?;
+    count_query.left_join_referred(
+        linker_table,
+        &indirect_table.name,
+        indirect_reference.0,
+    )?;
+    count_query.add_dao_filter(main_table, primary_dao)?;
+    count_query.add_filter(indirect_table, filter)?;
+    rows.count = Some(count_query.collect_count()?);
     Ok(rows)
 }
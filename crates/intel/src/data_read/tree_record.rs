@@ -0,0 +1,346 @@
+//! the records of a table that refers to itself arranged as a tree,
+//! ie: the employees under a manager.
+//! The ancestors are fetched with a recursive query on postgresql and sqlite,
+//! on the other databases the parents are fetched one at a time
+use crate::{
+    data_container::{
+        TreeRecords,
+        TREE_DISPLAY_COLUMN,
+        TREE_HAS_CHILDREN_COLUMN,
+    },
+    error::IntelError,
+    lookup,
+    query_builder::{
+        self,
+        Query,
+    },
+    table_intel::TableIntel,
+    Context,
+};
+use rustorm::{
+    ColumnName,
+    Dao,
+    DaoManager,
+    Rows,
+    Table,
+    Value,
+};
+
+/// the alias of the table in the tree queries
+const NODE: &str = "node";
+
+/// the ancestors are not followed further than this,
+/// which also stops the cycles in the records
+const MAX_DEPTH: usize = 32;
+
+/// the foreign key of the table to itself
+struct SelfReference<'a> {
+    parent_columns: &'a [ColumnName],
+    referred_columns: &'a [ColumnName],
+}
+
+pub(super) fn get_tree_records(
+    context: &Context,
+    dm: &mut DaoManager,
+    db_url: &str,
+    table: &Table,
+    parent_columns: &[ColumnName],
+    record: Option<&Dao>,
+    page: usize,
+    page_size: usize,
+) -> Result<TreeRecords, IntelError> {
+    let self_reference = TableIntel(table)
+        .get_self_references()
+        .into_iter()
+        .find(|(local, _referred)| local.as_slice() == parent_columns)
+        .map(|(local, referred)| {
+            SelfReference {
+                parent_columns: local,
+                referred_columns: referred,
+            }
+        })
+        .ok_or(IntelError::NotFound)?;
+    let ancestors = match record {
+        Some(record) if is_recursive_supported(db_url) => {
+            fetch_ancestors(context, dm, table, &self_reference, record)?
+        }
+        Some(record) => {
+            walk_ancestors(context, dm, table, &self_reference, record)?
+        }
+        None => Rows::new(vec![]),
+    };
+    let children = fetch_children(
+        context,
+        dm,
+        table,
+        &self_reference,
+        record,
+        page,
+        page_size,
+    )?;
+    Ok(TreeRecords {
+        ancestors,
+        children,
+    })
+}
+
+/// WITH RECURSIVE is supported on these databases
+fn is_recursive_supported(db_url: &str) -> bool {
+    db_url.starts_with("postgres") || db_url.starts_with("sqlite")
+}
+
+/// the primary columns and the display of the node
+fn node_columns(context: &Context, table: &Table) -> String {
+    let display = query_builder::display_expression(
+        NODE,
+        &lookup::display_or_pk(context, table),
+    );
+    let mut columns: Vec<String> = table
+        .get_primary_column_names()
+        .iter()
+        .map(|pk| format!("{}.{}", NODE, pk.name))
+        .collect();
+    columns.push(format!("{} AS \"{}\"", display, TREE_DISPLAY_COLUMN));
+    columns.join(", ")
+}
+
+/// the condition of the columns of the node having the values of the record
+fn add_node_condition(
+    query: &mut Query,
+    columns: &[ColumnName],
+    record_columns: &[ColumnName],
+    record: &Dao,
+) -> Result<(), IntelError> {
+    for (i, (column, record_column)) in
+        columns.iter().zip(record_columns.iter()).enumerate()
+    {
+        let value = record.get_value(&record_column.name).ok_or_else(|| {
+            IntelError::ParamParseError(format!(
+                "missing value of {}",
+                record_column.name
+            ))
+        })?;
+        if i > 0 {
+            query.append("AND ");
+        }
+        query.append(&format!("{}.{} = ", NODE, column.name));
+        query.add_param(value);
+    }
+    Ok(())
+}
+
+/// the ancestors of the record in a single recursive query
+fn fetch_ancestors(
+    context: &Context,
+    dm: &mut DaoManager,
+    table: &Table,
+    self_reference: &SelfReference,
+    record: &Dao,
+) -> Result<Rows, IntelError> {
+    let pks: Vec<ColumnName> = table
+        .get_primary_column_names()
+        .into_iter()
+        .cloned()
+        .collect();
+    let columns = format!(
+        "{}, {}",
+        node_columns(context, table),
+        self_reference
+            .parent_columns
+            .iter()
+            .map(|parent| format!("{}.{}", NODE, parent.name))
+            .collect::<Vec<String>>()
+            .join(", ")
+    );
+    let mut query = Query::new(context, dm);
+    query.add_table_datatypes(table);
+    query.append(&format!(
+        "WITH RECURSIVE ancestor AS (\nSELECT {}, 0 AS tree_depth \
+         FROM {} AS {} WHERE ",
+        columns,
+        table.safe_complete_name(),
+        NODE
+    ));
+    add_node_condition(&mut query, &pks, &pks, record)?;
+    query.append(&format!(
+        "\nUNION ALL\nSELECT {}, ancestor.tree_depth + 1 FROM {} AS {} \
+         JOIN ancestor ON ",
+        columns,
+        table.safe_complete_name(),
+        NODE
+    ));
+    let join_condition = self_reference
+        .referred_columns
+        .iter()
+        .zip(self_reference.parent_columns.iter())
+        .map(|(referred, parent)| {
+            format!("{}.{} = ancestor.{}", NODE, referred.name, parent.name)
+        })
+        .collect::<Vec<String>>()
+        .join(" AND ");
+    query.append(&join_condition);
+    query.append(&format!(" WHERE ancestor.tree_depth < {}\n)", MAX_DEPTH));
+    query.append(&format!(
+        "\nSELECT {}, \"{}\" FROM ancestor WHERE tree_depth > 0 \
+         ORDER BY tree_depth DESC",
+        pks.iter()
+            .map(|pk| pk.name.to_string())
+            .collect::<Vec<String>>()
+            .join(", "),
+        TREE_DISPLAY_COLUMN
+    ));
+    let rows = query.collect_rows()?;
+    Ok(rows)
+}
+
+/// the ancestors of the record, fetching the parent one at a time
+fn walk_ancestors(
+    context: &Context,
+    dm: &mut DaoManager,
+    table: &Table,
+    self_reference: &SelfReference,
+    record: &Dao,
+) -> Result<Rows, IntelError> {
+    let pks: Vec<ColumnName> = table
+        .get_primary_column_names()
+        .into_iter()
+        .cloned()
+        .collect();
+    let mut columns: Vec<String> =
+        pks.iter().map(|pk| pk.name.to_string()).collect();
+    columns.push(TREE_DISPLAY_COLUMN.to_string());
+
+    let mut ancestors = vec![];
+    let mut current =
+        fetch_node(context, dm, table, self_reference, &pks, &pks, record)?;
+    while let Some(node) = current {
+        if ancestors.len() >= MAX_DEPTH {
+            break;
+        }
+        let has_parent = self_reference.parent_columns.iter().all(|parent| {
+            match node.get_value(&parent.name) {
+                Some(Value::Nil) | None => false,
+                Some(_) => true,
+            }
+        });
+        current = if has_parent {
+            fetch_node(
+                context,
+                dm,
+                table,
+                self_reference,
+                self_reference.referred_columns,
+                self_reference.parent_columns,
+                &node,
+            )?
+        } else {
+            None
+        };
+        if let Some(parent) = &current {
+            ancestors.push(
+                columns
+                    .iter()
+                    .map(|column| {
+                        parent.get_value(column).cloned().unwrap_or(Value::Nil)
+                    })
+                    .collect(),
+            );
+        }
+    }
+    let mut rows = Rows::new(columns);
+    for row in ancestors.into_iter().rev() {
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+/// the node which columns has the values of the record columns,
+/// together with the columns referring to its parent
+fn fetch_node(
+    context: &Context,
+    dm: &mut DaoManager,
+    table: &Table,
+    self_reference: &SelfReference,
+    columns: &[ColumnName],
+    record_columns: &[ColumnName],
+    record: &Dao,
+) -> Result<Option<Dao>, IntelError> {
+    let mut query = Query::new(context, dm);
+    query.add_table_datatypes(table);
+    query.select();
+    query.append(&node_columns(context, table));
+    for parent in self_reference.parent_columns.iter() {
+        query.append(&format!(", {}.{}", NODE, parent.name));
+    }
+    query.append(&format!(
+        "\nFROM {} AS {} WHERE ",
+        table.safe_complete_name(),
+        NODE
+    ));
+    add_node_condition(&mut query, columns, record_columns, record)?;
+    let node = query.collect_maybe_record()?;
+    Ok(node)
+}
+
+/// the page of the records directly under the record,
+/// the roots when there is no record, ordered by their display
+fn fetch_children(
+    context: &Context,
+    dm: &mut DaoManager,
+    table: &Table,
+    self_reference: &SelfReference,
+    record: Option<&Dao>,
+    page: usize,
+    page_size: usize,
+) -> Result<Rows, IntelError> {
+    let has_children = self_reference
+        .parent_columns
+        .iter()
+        .zip(self_reference.referred_columns.iter())
+        .map(|(parent, referred)| {
+            format!("child.{} = {}.{}", parent.name, NODE, referred.name)
+        })
+        .collect::<Vec<String>>()
+        .join(" AND ");
+    let mut query = Query::new(context, dm);
+    query.add_table_datatypes(table);
+    query.select();
+    query.append(&node_columns(context, table));
+    query.append(&format!(
+        ", EXISTS (SELECT 1 FROM {} AS child WHERE {}) AS \"{}\"",
+        table.safe_complete_name(),
+        has_children,
+        TREE_HAS_CHILDREN_COLUMN
+    ));
+    query.append(&format!(
+        "\nFROM {} AS {} WHERE ",
+        table.safe_complete_name(),
+        NODE
+    ));
+    match record {
+        Some(record) => {
+            add_node_condition(
+                &mut query,
+                self_reference.parent_columns,
+                self_reference.referred_columns,
+                record,
+            )?;
+        }
+        None => {
+            let is_root = self_reference
+                .parent_columns
+                .iter()
+                .map(|parent| format!("{}.{} IS NULL", NODE, parent.name))
+                .collect::<Vec<String>>()
+                .join(" AND ");
+            query.append(&is_root);
+        }
+    }
+    query.append(&format!("\nORDER BY \"{}\"", TREE_DISPLAY_COLUMN));
+    for pk in table.get_primary_column_names() {
+        query.append(&format!(", {}.{}", NODE, pk.name));
+    }
+    query.set_page(page, page_size);
+    let rows = query.collect_rows()?;
+    Ok(rows)
+}
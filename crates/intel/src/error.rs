@@ -1,4 +1,10 @@
-use rustorm::DbError;
+use crate::validation::FieldError;
+use rustorm::{
+    ColumnName,
+    DbError,
+    TableName,
+    Value,
+};
 use std::{
     error::Error,
     fmt,
@@ -7,11 +13,68 @@ use std::{
 #[derive(Debug)]
 pub enum IntelError {
     CacheServiceError,
+    /// the lock of the plugin registry is poisoned
+    PluginRegistryError,
     DbError(DbError),
     ParamParseError(String),
     /// no matching window
     NoMatching,
     NotFound,
+    /// a step of saving a changeset failed,
+    /// all of the changes in the changeset are rolled back
+    ChangesetError {
+        step: ChangesetStep,
+        table_name: TableName,
+        error: Box<IntelError>,
+    },
+    /// the record has been modified by someone else
+    /// since it was read, nothing is written
+    UpdateConflict {
+        table_name: TableName,
+        conflicts: Vec<ColumnConflict>,
+    },
+    /// the values of the record are not valid for the table,
+    /// nothing is written
+    ValidationError {
+        table_name: TableName,
+        errors: Vec<FieldError>,
+    },
+}
+
+/// a column which value in the database no longer matches
+/// the value that was originally read
+#[derive(Debug)]
+pub struct ColumnConflict {
+    pub column_name: ColumnName,
+    pub original: Value,
+    pub current: Value,
+}
+
+/// the sub step in saving a record changeset
+#[derive(Debug)]
+pub enum ChangesetStep {
+    MainRecord,
+    OneOne,
+    HasMany,
+    Indirect,
+}
+
+impl IntelError {
+    /// wrap the error that happened while saving the table in this step of the changeset
+    pub fn changeset_error<E>(
+        step: ChangesetStep,
+        table_name: &TableName,
+        error: E,
+    ) -> Self
+    where
+        E: Into<IntelError>,
+    {
+        IntelError::ChangesetError {
+            step,
+            table_name: table_name.clone(),
+            error: Box::new(error.into()),
+        }
+    }
 }
 
 impl fmt::Display for IntelError {
@@ -1,3 +1,10 @@
+use crate::{
+    media::{
+        self,
+        MediaKind,
+    },
+    table_intel,
+};
 use rustorm::Column;
 
 use rustorm::{
@@ -21,6 +28,8 @@ pub struct Field {
     pub is_primary: bool,
     /// column name
     pub column_detail: ColumnDetail,
+    /// the value is an image or a file
+    pub media: Option<MediaKind>,
 }
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
@@ -123,6 +132,7 @@ impl Field {
             info: None,
             is_primary: in_primary,
             column_detail,
+            media: media::get_hinted_media_kind(column),
         }
     }
 
@@ -130,6 +140,20 @@ impl Field {
         self.column_detail.column_names()
     }
 
+    /// the names of the values of this field in the rows,
+    /// the values of a compound field are in the names of its columns
+    pub fn row_column_names(&self) -> Vec<String> {
+        match self.column_detail {
+            ColumnDetail::Simple(..) => vec![self.name.to_string()],
+            ColumnDetail::Compound(_) => {
+                self.column_names()
+                    .iter()
+                    .map(|column_name| column_name.name.to_string())
+                    .collect()
+            }
+        }
+    }
+
     pub fn first_column_name(&self) -> Option<&ColumnName> {
         self.column_detail.first_column_name()
     }
@@ -138,8 +162,8 @@ impl Field {
     /// will be merge into 1 field
     /// such as this: a lookup to the table
     /// that uses composite foreign key
-    /// the field name will be the table name
-    /// it looks up to
+    /// the field name will be the role of the foreign key,
+    /// so the foreign keys to the same table are told apart
     /// This is the reason why column_detail is needed for cases where
     /// the referencing columns is a composite key
     pub fn from_has_one_table(
@@ -157,8 +181,10 @@ impl Field {
             table.get_primary_column_names().contains(&&column.name)
         });
         let column_detail: ColumnDetail = ColumnDetail::from(columns);
+        let column_names: Vec<ColumnName> =
+            columns.iter().map(|column| column.name.clone()).collect();
         Field {
-            name: referred_table.name.name.to_string(),
+            name: table_intel::role_name(&column_names, &referred_table.name),
             description: if !columns_comment.is_empty() {
                 Some(columns_comment)
             } else {
@@ -167,6 +193,7 @@ impl Field {
             info: referred_table.comment.to_owned(),
             is_primary: in_primary,
             column_detail,
+            media: None,
         }
     }
 
@@ -0,0 +1,251 @@
+//! the filter of the rows typed in the search of each column,
+//! the search is parsed according to the data type of the column
+//!
+//! | search        | condition                                  |
+//! |---------------|--------------------------------------------|
+//! | `null`        | the column is null                         |
+//! | `!null`       | the column is not null                     |
+//! | `=abc`        | equal to abc                               |
+//! | `a,b,c`       | in a, b or c, on non-text columns          |
+//! | `10..20`      | from 10 to 20, either side can be omitted  |
+//! | `abc*`        | starts with abc, on text columns           |
+//! | `abc`         | contains abc on text columns, equal to abc on the others |
+//!
+//! A date without time on a timestamp column covers the whole day.
+use crate::{
+    common,
+    error::IntelError,
+};
+use chrono::{
+    DateTime,
+    NaiveDate,
+    Utc,
+};
+use rustorm::{
+    types::SqlType,
+    ColumnName,
+    Value,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum Condition {
+    /// the text of the column contains this, case insensitive
+    Contains(String),
+    /// the text of the column starts with this, case insensitive
+    StartsWith(String),
+    Equal(Value),
+    In(Vec<Value>),
+    /// inclusive range, an open side is not bounded
+    Range(Option<Value>, Option<Value>),
+    IsNull,
+    IsNotNull,
+}
+
+/// the condition on this column
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct ColumnFilter {
+    pub column_name: ColumnName,
+    pub condition: Condition,
+}
+
+/// the rows must satisfy all of the column filters
+#[derive(Debug, Default, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Filter {
+    pub column_filters: Vec<ColumnFilter>,
+}
+
+impl Filter {
+    pub fn is_empty(&self) -> bool {
+        self.column_filters.is_empty()
+    }
+}
+
+impl ColumnFilter {
+    /// parse the search typed in the column, None if the search is blank
+    pub fn parse(
+        column_name: &ColumnName,
+        sql_type: &SqlType,
+        search: &str,
+    ) -> Result<Option<Self>, IntelError> {
+        let search = search.trim();
+        if search.is_empty() {
+            return Ok(None);
+        }
+        let condition = parse_condition(sql_type, search)?;
+        Ok(Some(ColumnFilter {
+            column_name: column_name.clone(),
+            condition,
+        }))
+    }
+}
+
+fn parse_condition(
+    sql_type: &SqlType,
+    search: &str,
+) -> Result<Condition, IntelError> {
+    let lower = search.to_lowercase();
+    if lower == "null" {
+        return Ok(Condition::IsNull);
+    }
+    if lower == "!null" {
+        return Ok(Condition::IsNotNull);
+    }
+    if search.starts_with('=') {
+        return parse_equal(search[1..].trim(), sql_type);
+    }
+    if common::is_textual(sql_type) {
+        if search.ends_with('*') {
+            let prefix = search.trim_end_matches('*');
+            return Ok(Condition::StartsWith(prefix.to_string()));
+        }
+        return Ok(Condition::Contains(search.to_string()));
+    }
+    if search.contains(',') {
+        let mut values = vec![];
+        for splinter in search.split(',') {
+            values.push(common::parse_value(splinter.trim(), sql_type)?);
+        }
+        return Ok(Condition::In(values));
+    }
+    if let Some(index) = search.find("..") {
+        let from = parse_range_side(&search[..index], sql_type, false)?;
+        let to = parse_range_side(&search[index + 2..], sql_type, true)?;
+        return Ok(Condition::Range(from, to));
+    }
+    parse_equal(search, sql_type)
+}
+
+/// a date on a timestamp column is equal to any time of that day
+fn parse_equal(
+    splinter: &str,
+    sql_type: &SqlType,
+) -> Result<Condition, IntelError> {
+    match day_bounds(splinter, sql_type) {
+        Some((start, end)) => Ok(Condition::Range(Some(start), Some(end))),
+        None => Ok(Condition::Equal(common::parse_value(splinter, sql_type)?)),
+    }
+}
+
+/// the upper side of a range includes the whole day of a date
+fn parse_range_side(
+    splinter: &str,
+    sql_type: &SqlType,
+    is_upper: bool,
+) -> Result<Option<Value>, IntelError> {
+    let splinter = splinter.trim();
+    if splinter.is_empty() {
+        return Ok(None);
+    }
+    match day_bounds(splinter, sql_type) {
+        Some((_start, end)) if is_upper => Ok(Some(end)),
+        Some((start, _end)) => Ok(Some(start)),
+        None => Ok(Some(common::parse_value(splinter, sql_type)?)),
+    }
+}
+
+/// the first and the last instant of the day, when a timestamp column is
+/// searched with a date only
+fn day_bounds(splinter: &str, sql_type: &SqlType) -> Option<(Value, Value)> {
+    match *sql_type {
+        SqlType::Timestamp | SqlType::TimestampTz => {
+            let date = NaiveDate::parse_from_str(splinter, "%Y-%m-%d").ok()?;
+            let start = date.and_hms(0, 0, 0);
+            let end = date.and_hms_micro(23, 59, 59, 999_999);
+            Some((
+                Value::Timestamp(DateTime::<Utc>::from_utc(start, Utc)),
+                Value::Timestamp(DateTime::<Utc>::from_utc(end, Utc)),
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// escape the wildcards of LIKE, the escape character is the backslash
+pub(crate) fn escape_like(search: &str) -> String {
+    let mut escaped = String::new();
+    for c in search.chars() {
+        if c == '%' || c == '_' || c == '\\' {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(sql_type: &SqlType, search: &str) -> Condition {
+        ColumnFilter::parse(&ColumnName::from("col"), sql_type, search)
+            .expect("must parse")
+            .expect("must not be blank")
+            .condition
+    }
+
+    #[test]
+    fn text_search() {
+        assert_eq!(
+            parse(&SqlType::Text, "john"),
+            Condition::Contains("john".to_string())
+        );
+        assert_eq!(
+            parse(&SqlType::Varchar, "jo*"),
+            Condition::StartsWith("jo".to_string())
+        );
+        assert_eq!(parse(&SqlType::Text, "null"), Condition::IsNull);
+        assert_eq!(
+            parse(&SqlType::Text, "Smith, John"),
+            Condition::Contains("Smith, John".to_string())
+        );
+    }
+
+    #[test]
+    fn typed_search() {
+        assert_eq!(
+            parse(&SqlType::Int, "10..20"),
+            Condition::Range(Some(Value::Int(10)), Some(Value::Int(20)))
+        );
+        assert_eq!(
+            parse(&SqlType::Int, "1,2"),
+            Condition::In(vec![Value::Int(1), Value::Int(2)])
+        );
+        assert_eq!(
+            parse(&SqlType::Bool, "yes"),
+            Condition::Equal(Value::Bool(true))
+        );
+        assert!(
+            ColumnFilter::parse(&ColumnName::from("col"), &SqlType::Int, "abc")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn date_search_on_timestamp() {
+        let day = NaiveDate::from_ymd(2019, 3, 1);
+        let start = Value::Timestamp(DateTime::<Utc>::from_utc(
+            day.and_hms(0, 0, 0),
+            Utc,
+        ));
+        let end = Value::Timestamp(DateTime::<Utc>::from_utc(
+            day.and_hms_micro(23, 59, 59, 999_999),
+            Utc,
+        ));
+        assert_eq!(
+            parse(&SqlType::Timestamp, "2019-03-01"),
+            Condition::Range(Some(start.clone()), Some(end.clone()))
+        );
+        assert_eq!(
+            parse(&SqlType::TimestampTz, "2019-03-01..2019-03-01"),
+            Condition::Range(Some(start.clone()), Some(end))
+        );
+        assert_eq!(
+            parse(&SqlType::Timestamp, "2019-03-01.."),
+            Condition::Range(Some(start), None)
+        );
+    }
+}
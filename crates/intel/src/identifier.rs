@@ -0,0 +1,347 @@
+//! the identifier display of the tables which columns are not named
+//! as the usual identifiers such as `name` or `title`.
+//! The text columns of a sample of the records are scored by their uniqueness,
+//! how often they are null and how long they are, the best one is used.
+//! The display registered by the application always wins over the derived ones.
+//! The displays are kept in the cache of each DB_URL, next to its tables.
+//!
+//! ```ignore
+//! identifier::register_display(
+//!     db_url,
+//!     &TableName::from("public.product"),
+//!     vec![ColumnName::from("sku"), ColumnName::from("label")],
+//!     Some(" - ".to_string()),
+//! );
+//! ```
+use crate::{
+    cache,
+    common,
+    data_container::{
+        DropdownInfo,
+        IdentifierDisplay,
+    },
+    error::IntelError,
+    tab::Tab,
+    window::Window,
+};
+use rustorm::{
+    types::SqlType,
+    Column,
+    ColumnName,
+    DaoManager,
+    Table,
+    TableName,
+    Value,
+};
+use std::collections::HashMap;
+
+/// the number of records sampled from each table
+const SAMPLE_SIZE: usize = 1000;
+/// the least ratio of distinct values to the non null values
+const MIN_UNIQUENESS: f64 = 0.8;
+/// the most ratio of null values in the sample
+const MAX_NULL_RATIO: f64 = 0.2;
+/// longer texts are descriptions rather than identifiers
+const MAX_LENGTH: f64 = 64.0;
+
+/// the identifier displays of the tables on one DB_URL
+#[derive(Debug, Default, Clone)]
+pub struct IdentifierRegistry {
+    /// registered by the application
+    overrides: HashMap<TableName, IdentifierDisplay>,
+    /// inferred from the sample of the records
+    inferred: HashMap<TableName, IdentifierDisplay>,
+}
+
+/// the statistics of a text column in the sample of the records
+#[derive(Debug, PartialEq)]
+pub struct ColumnSample {
+    pub column_name: ColumnName,
+    pub total: usize,
+    pub distinct: usize,
+    pub present: usize,
+    pub average_length: f64,
+}
+
+impl ColumnSample {
+    /// how well the column identifies the records, the higher the better,
+    /// None if it can not be used as identifier
+    pub fn score(&self) -> Option<f64> {
+        if self.total == 0 || self.present == 0 {
+            return None;
+        }
+        let uniqueness = self.distinct as f64 / self.present as f64;
+        let null_ratio = 1.0 - self.present as f64 / self.total as f64;
+        if uniqueness < MIN_UNIQUENESS
+            || null_ratio > MAX_NULL_RATIO
+            || self.average_length < 1.0
+            || self.average_length > MAX_LENGTH
+        {
+            return None;
+        }
+        // shorter texts are easier to read in a dropdown
+        let brevity = 1.0 - self.average_length / MAX_LENGTH;
+        Some(uniqueness * (1.0 - null_ratio) * (0.5 + 0.5 * brevity))
+    }
+}
+
+/// the column with the highest score
+fn best_column(samples: &[ColumnSample]) -> Option<&ColumnName> {
+    samples
+        .iter()
+        .filter_map(|sample| {
+            sample.score().map(|score| (&sample.column_name, score))
+        })
+        .fold(None, |best: Option<(&ColumnName, f64)>, (column, score)| {
+            match best {
+                Some((_, best_score)) if best_score >= score => best,
+                _ => Some((column, score)),
+            }
+        })
+        .map(|(column, _score)| column)
+}
+
+/// register the display of the table on this DB_URL,
+/// used instead of the derived display
+pub fn register_display(
+    db_url: &str,
+    table_name: &TableName,
+    columns: Vec<ColumnName>,
+    separator: Option<String>,
+) -> Result<(), IntelError> {
+    match cache::CACHE_POOL.lock() {
+        Ok(mut cache_pool) => {
+            cache_pool.register_display(db_url, table_name, columns, separator);
+            Ok(())
+        }
+        Err(_e) => Err(IntelError::CacheServiceError),
+    }
+}
+
+impl IdentifierRegistry {
+    pub fn register(
+        &mut self,
+        table_name: &TableName,
+        columns: Vec<ColumnName>,
+        separator: Option<String>,
+    ) {
+        self.overrides.insert(
+            table_name.clone(),
+            IdentifierDisplay {
+                columns,
+                pk: vec![],
+                separator,
+            },
+        );
+    }
+
+    /// the display of the table registered by the application,
+    /// otherwise derived from the column names,
+    /// otherwise inferred from the sample of the records
+    pub fn get_display(&self, table: &Table) -> Option<IdentifierDisplay> {
+        if let Some(display) = Self::get_registered(table, &self.overrides) {
+            return Some(display);
+        }
+        match Tab::derive_display(table) {
+            Some(ref display) if !display.columns.is_empty() => {
+                Some(display.clone())
+            }
+            derived => Self::get_registered(table, &self.inferred).or(derived),
+        }
+    }
+
+    pub fn get_dropdowninfo(&self, table: &Table) -> Option<DropdownInfo> {
+        self.get_display(table).map(|display| {
+            DropdownInfo {
+                source: table.name.clone(),
+                display,
+            }
+        })
+    }
+
+    fn get_registered(
+        table: &Table,
+        displays: &HashMap<TableName, IdentifierDisplay>,
+    ) -> Option<IdentifierDisplay> {
+        displays.get(&table.name).map(|display| {
+            IdentifierDisplay {
+                pk: table
+                    .get_primary_column_names()
+                    .into_iter()
+                    .cloned()
+                    .collect(),
+                ..display.clone()
+            }
+        })
+    }
+
+    /// sample the records of the tables that have no identifier display
+    /// and keep the inferred display, the tables which can not be sampled
+    /// are left without identifier
+    pub fn infer(&mut self, dm: &mut DaoManager, tables: &[Table]) {
+        for table in tables.iter() {
+            let has_display = Tab::derive_display(table)
+                .map(|display| !display.columns.is_empty())
+                .unwrap_or(false);
+            if has_display {
+                continue;
+            }
+            let samples = match sample_columns(dm, table) {
+                Ok(samples) => samples,
+                Err(e) => {
+                    println!(
+                        "Unable to sample {}: {}",
+                        table.complete_name(),
+                        e
+                    );
+                    continue;
+                }
+            };
+            if let Some(column) = best_column(&samples) {
+                self.inferred.insert(
+                    table.name.clone(),
+                    IdentifierDisplay {
+                        columns: vec![column.clone()],
+                        pk: vec![],
+                        separator: None,
+                    },
+                );
+            }
+        }
+    }
+
+    /// set the displays of the tabs of the windows derived from the tables
+    pub fn apply_to_windows(&self, windows: &mut [Window], tables: &[Table]) {
+        for window in windows.iter_mut() {
+            for tab in window.tabs_mut() {
+                if let Some(table) =
+                    tables.iter().find(|table| table.name == tab.table_name)
+                {
+                    tab.display = self.get_display(table);
+                }
+            }
+        }
+    }
+}
+
+/// the text columns that are neither part of the primary key
+/// nor referring to other tables.
+/// Enums are left out, they have no LENGTH in postgres
+/// and their few values hardly identify a record
+fn get_candidate_columns(table: &Table) -> Vec<&Column> {
+    let pks = table.get_primary_column_names();
+    let foreign_keys = table.get_foreign_keys();
+    table
+        .columns
+        .iter()
+        .filter(|column| {
+            let sql_type = column.get_sql_type();
+            common::is_textual(&sql_type)
+                && !is_enum(&sql_type)
+                && !pks.contains(&&column.name)
+                && !foreign_keys
+                    .iter()
+                    .any(|fk| fk.columns.contains(&column.name))
+        })
+        .collect()
+}
+
+fn is_enum(sql_type: &SqlType) -> bool {
+    match sql_type {
+        SqlType::Enum(_, _) => true,
+        _ => false,
+    }
+}
+
+fn sample_columns(
+    dm: &mut DaoManager,
+    table: &Table,
+) -> Result<Vec<ColumnSample>, IntelError> {
+    let columns = get_candidate_columns(table);
+    if columns.is_empty() {
+        return Ok(vec![]);
+    }
+    let mut sql = String::from("SELECT COUNT(*) AS total");
+    for (i, column) in columns.iter().enumerate() {
+        sql += &format!(
+            ", COUNT(DISTINCT sample.{column}) AS distinct_{i}, \
+             COUNT(sample.{column}) AS present_{i}, \
+             AVG(LENGTH(sample.{column})) AS length_{i}",
+            column = column.name.name,
+            i = i
+        );
+    }
+    sql += &format!(
+        "\nFROM (SELECT * FROM {} LIMIT {}) AS sample",
+        table.safe_complete_name(),
+        SAMPLE_SIZE
+    );
+    let record = dm.execute_sql_with_one_return(&sql, &[])?;
+    let get_number =
+        |name: &str| record.get_value(name).and_then(to_f64).unwrap_or(0.0);
+    let total = get_number("total") as usize;
+    let samples = columns
+        .iter()
+        .enumerate()
+        .map(|(i, column)| {
+            ColumnSample {
+                column_name: column.name.clone(),
+                total,
+                distinct: get_number(&format!("distinct_{}", i)) as usize,
+                present: get_number(&format!("present_{}", i)) as usize,
+                average_length: get_number(&format!("length_{}", i)),
+            }
+        })
+        .collect();
+    Ok(samples)
+}
+
+/// the aggregates are returned in different numeric types
+/// depending on the database
+fn to_f64(value: &Value) -> Option<f64> {
+    match value {
+        Value::Int(v) => Some(f64::from(*v)),
+        Value::Bigint(v) => Some(*v as f64),
+        Value::Float(v) => Some(f64::from(*v)),
+        Value::Double(v) => Some(*v),
+        Value::BigDecimal(v) => v.to_string().parse().ok(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(
+        name: &str,
+        distinct: usize,
+        present: usize,
+        average_length: f64,
+    ) -> ColumnSample {
+        ColumnSample {
+            column_name: ColumnName::from(name),
+            total: 100,
+            distinct,
+            present,
+            average_length,
+        }
+    }
+
+    #[test]
+    fn short_unique_column_is_chosen() {
+        let samples = vec![
+            sample("notes", 100, 100, 200.0),
+            sample("color", 5, 100, 4.0),
+            sample("label", 100, 95, 20.0),
+            sample("sku", 100, 100, 8.0),
+        ];
+        assert_eq!(best_column(&samples), Some(&ColumnName::from("sku")));
+    }
+
+    #[test]
+    fn mostly_null_column_is_not_identifier() {
+        assert_eq!(sample("nickname", 30, 30, 6.0).score(), None);
+        assert_eq!(best_column(&[sample("nickname", 30, 30, 6.0)]), None);
+    }
+}
@@ -0,0 +1,104 @@
+//! the journal records the before and after image of the rows
+//! that are modified in one save, so the changes can be undone
+//! and written to the audit log
+use crate::data_container::RecordAction;
+use rustorm::{
+    Dao,
+    Rows,
+    TableName,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+/// the change done to a row of a table
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub table_name: TableName,
+    pub action: RecordAction,
+    /// the row before the change, None when the row is inserted
+    pub before: Option<Dao>,
+    /// the row after the change, None when the row is deleted
+    pub after: Option<Dao>,
+}
+
+/// the entries are in the order the changes happened
+#[derive(Debug, Default, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Journal {
+    pub entries: Vec<JournalEntry>,
+}
+
+impl Journal {
+    pub fn new() -> Self {
+        Journal { entries: vec![] }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn record(
+        &mut self,
+        table_name: &TableName,
+        action: RecordAction,
+        before: Option<Dao>,
+        after: Option<Dao>,
+    ) {
+        self.entries.push(JournalEntry {
+            table_name: table_name.clone(),
+            action,
+            before,
+            after,
+        });
+    }
+
+    pub fn record_update(
+        &mut self,
+        table_name: &TableName,
+        before: Option<Dao>,
+        after: Option<Dao>,
+    ) {
+        self.record(table_name, RecordAction::Edited, before, after);
+    }
+
+    pub fn record_insert(
+        &mut self,
+        table_name: &TableName,
+        action: RecordAction,
+        after: Dao,
+    ) {
+        self.record(table_name, action, None, Some(after));
+    }
+
+    pub fn record_inserted_rows(
+        &mut self,
+        table_name: &TableName,
+        action: RecordAction,
+        rows: &Rows,
+    ) {
+        for dao in rows.iter() {
+            self.record_insert(table_name, action.clone(), dao);
+        }
+    }
+
+    pub fn record_delete(
+        &mut self,
+        table_name: &TableName,
+        action: RecordAction,
+        before: Dao,
+    ) {
+        self.record(table_name, action, Some(before), None);
+    }
+
+    pub fn record_deleted_rows(
+        &mut self,
+        table_name: &TableName,
+        action: RecordAction,
+        rows: &Rows,
+    ) {
+        for dao in rows.iter() {
+            self.record_delete(table_name, action.clone(), dao);
+        }
+    }
+}
@@ -4,6 +4,7 @@
 #[macro_use]
 extern crate log;
 
+pub mod audit;
 pub mod cache;
 mod common;
 mod context;
@@ -12,11 +13,21 @@ pub mod data_modify;
 pub mod data_read;
 pub mod error;
 pub mod field;
+pub mod filter;
+pub mod identifier;
+pub mod journal;
+pub mod lookup;
+pub mod media;
+pub mod plugin;
 mod query_builder;
 mod query_parser;
+pub mod quick_find;
 pub mod tab;
 pub mod table_intel;
+pub mod undo;
+pub mod validation;
 pub mod window;
+pub mod window_override;
 
 pub use context::Context;
 pub use data_container::{
@@ -34,7 +45,9 @@ pub use rustorm::{
     Value,
 };
 pub use tab::{
+    HasManyTab,
     IndirectTab,
     Tab,
+    TreeTab,
 };
 pub use window::Window;
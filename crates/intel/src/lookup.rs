@@ -0,0 +1,146 @@
+//! the options of the foreign key fields, the records of the referred table
+//! listed as their primary key and their display, ordered by the display.
+//! The unsearched pages are cached per db_url until the records are modified,
+//! the searched pages are always fetched to keep the cache small
+use crate::{
+    cache,
+    data_container::{
+        IdentifierDisplay,
+        Lookup,
+    },
+    error::IntelError,
+    filter,
+    query_builder::{
+        self,
+        Query,
+    },
+    quick_find,
+    Context,
+};
+use rustorm::{
+    ColumnName,
+    DaoManager,
+    Rows,
+    Table,
+    TableName,
+    Value,
+};
+
+/// the name of the column holding the display of the option
+pub const DISPLAY_COLUMN: &str = "display";
+
+/// identifies a cached page of the lookup options
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct LookupKey {
+    pub source: TableName,
+    pub search: Option<String>,
+    pub page: usize,
+    pub page_size: usize,
+}
+
+/// the page of options of the source table which display contains the search
+pub fn get_lookup(
+    context: &Context,
+    dm: &mut DaoManager,
+    db_url: &str,
+    source: &TableName,
+    search: Option<&str>,
+    page: usize,
+    page_size: usize,
+) -> Result<Lookup, IntelError> {
+    let key = LookupKey {
+        source: source.clone(),
+        search: search
+            .map(str::trim)
+            .filter(|search| !search.is_empty())
+            .map(ToString::to_string),
+        page,
+        page_size,
+    };
+    if key.search.is_some() {
+        let rows = fetch_lookup_rows(context, dm, db_url, &key)?;
+        return Ok(Lookup(vec![(source.clone(), rows)]));
+    }
+    let cached = match cache::CACHE_POOL.lock() {
+        Ok(cache_pool) => cache_pool.get_cached_lookup(db_url, &key),
+        Err(_) => return Err(IntelError::CacheServiceError),
+    };
+    let rows = match cached {
+        Some(rows) => rows,
+        None => {
+            let rows = fetch_lookup_rows(context, dm, db_url, &key)?;
+            match cache::CACHE_POOL.lock() {
+                Ok(mut cache_pool) => {
+                    cache_pool.cache_lookup(db_url, key, rows.clone())
+                }
+                Err(_) => return Err(IntelError::CacheServiceError),
+            }
+            rows
+        }
+    };
+    Ok(Lookup(vec![(source.clone(), rows)]))
+}
+
+/// the identifier display of the table,
+/// the primary key is displayed when the table has no identifier
+pub(crate) fn display_or_pk(
+    context: &Context,
+    table: &Table,
+) -> IdentifierDisplay {
+    match context.identifiers.get_display(table) {
+        Some(display) if !display.columns.is_empty() => display,
+        _ => {
+            let pks: Vec<ColumnName> = table
+                .get_primary_column_names()
+                .into_iter()
+                .cloned()
+                .collect();
+            IdentifierDisplay {
+                columns: pks.clone(),
+                pk: pks,
+                separator: None,
+            }
+        }
+    }
+}
+
+fn fetch_lookup_rows(
+    context: &Context,
+    dm: &mut DaoManager,
+    db_url: &str,
+    key: &LookupKey,
+) -> Result<Rows, IntelError> {
+    let table = context.get_table(&key.source).ok_or(IntelError::NotFound)?;
+    let pks = table.get_primary_column_names();
+    let display = display_or_pk(context, table);
+    let expression =
+        query_builder::display_expression(&table.safe_name(), &display);
+
+    let mut query = Query::new(context, dm);
+    query.select();
+    for pk in pks.iter() {
+        query.append(&format!("{}.{}, ", table.safe_name(), pk.name));
+    }
+    query.append(&format!("{} AS {} ", expression, DISPLAY_COLUMN));
+    query.add_table_datatypes(table);
+    query.from(&table.name);
+    if let Some(search) = &key.search {
+        query.append(&format!(
+            "WHERE {} {} ",
+            expression,
+            quick_find::like_operator(db_url)
+        ));
+        query.add_param(&Value::Text(format!(
+            "%{}%",
+            filter::escape_like(search)
+        )));
+        query.append("ESCAPE '\\' ");
+    }
+    query.append(&format!("\nORDER BY {}", DISPLAY_COLUMN));
+    for pk in pks.iter() {
+        query.append(&format!(", {}.{}", table.safe_name(), pk.name));
+    }
+    query.set_page(key.page, key.page_size);
+    let rows = query.collect_rows()?;
+    Ok(rows)
+}
@@ -0,0 +1,450 @@
+//! detect the columns that holds images and files, from the hints in their
+//! names, the magic numbers of their bytes and the extensions of their urls.
+//! The bytes are not sent in the rows, instead the client is sent the uri
+//! of the media route which serves the bytes of the column of the record.
+use crate::{
+    common,
+    data_container,
+    error::IntelError,
+    window::Window,
+    Context,
+};
+use rustorm::{
+    types::SqlType,
+    Column,
+    ColumnName,
+    DaoManager,
+    Rows,
+    Table,
+    TableName,
+    Value,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::collections::HashMap;
+
+type MediaKey = (TableName, ColumnName);
+
+/// the media kind of the columns on one DB_URL
+/// as detected from the sample of their records
+#[derive(Debug, Default, Clone)]
+pub struct MediaRegistry(HashMap<MediaKey, MediaKind>);
+
+/// the number of records sampled from each table
+const SAMPLE_SIZE: usize = 10;
+
+/// the words in the column name that hints it is an image
+const IMAGE_HINTS: [&str; 8] = [
+    "photo",
+    "avatar",
+    "picture",
+    "image",
+    "img",
+    "thumbnail",
+    "logo",
+    "icon",
+];
+
+const IMAGE_EXTENSIONS: [&str; 7] =
+    ["png", "jpg", "jpeg", "gif", "webp", "svg", "bmp"];
+
+/// the magic numbers at the start of the bytes of the known files
+const MAGIC_NUMBERS: [(&[u8], &str); 6] = [
+    (b"\x89PNG", "image/png"),
+    (b"\xFF\xD8\xFF", "image/jpeg"),
+    (b"GIF8", "image/gif"),
+    (b"BM", "image/bmp"),
+    (b"%PDF", "application/pdf"),
+    (b"PK\x03\x04", "application/zip"),
+];
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum MediaKind {
+    /// the bytes of an image or the url of an image
+    Image,
+    /// the bytes of the other known files such as pdf
+    File,
+}
+
+/// the mime type of the bytes, determined from their magic numbers
+pub fn sniff_mime_type(bytes: &[u8]) -> Option<&'static str> {
+    // webp is a RIFF container, the format is after the size
+    if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        return Some("image/webp");
+    }
+    MAGIC_NUMBERS
+        .iter()
+        .find(|(magic, _mime)| bytes.starts_with(magic))
+        .map(|(_magic, mime)| *mime)
+}
+
+fn media_kind_of_mime(mime: &str) -> MediaKind {
+    if mime.starts_with("image/") {
+        MediaKind::Image
+    } else {
+        MediaKind::File
+    }
+}
+
+/// the url ends with an image extension, the query string is ignored
+pub fn is_image_url(text: &str) -> bool {
+    let text = text.trim();
+    let is_url = text.starts_with("http://")
+        || text.starts_with("https://")
+        || text.starts_with('/');
+    let path = text.split(|c| c == '?' || c == '#').next().unwrap_or("");
+    let extension = path.rsplit('.').next().unwrap_or("").to_lowercase();
+    is_url && path.contains('.') && IMAGE_EXTENSIONS.contains(&&*extension)
+}
+
+/// any of the words of the column name is an image hint,
+/// ie: `profile_photo`, `avatar_url`
+fn has_image_hint(column_name: &str) -> bool {
+    column_name
+        .to_lowercase()
+        .split('_')
+        .any(|word| IMAGE_HINTS.contains(&word))
+}
+
+/// the bytes of these columns are not sent in the rows
+pub fn is_binary(sql_type: &SqlType) -> bool {
+    match sql_type {
+        SqlType::Tinyblob
+        | SqlType::Mediumblob
+        | SqlType::Blob
+        | SqlType::Longblob
+        | SqlType::Varbinary => true,
+        _ => false,
+    }
+}
+
+/// the media kind of the column from the hints in its name
+pub fn get_hinted_media_kind(column: &Column) -> Option<MediaKind> {
+    let sql_type = column.get_sql_type();
+    if (is_binary(&sql_type) || common::is_textual(&sql_type))
+        && has_image_hint(&column.name.name)
+    {
+        Some(MediaKind::Image)
+    } else {
+        None
+    }
+}
+
+impl MediaRegistry {
+    /// the media kind of the column as detected from the sample of the records,
+    /// otherwise from the hints in its name
+    pub fn get_media_kind(
+        &self,
+        table: &Table,
+        column: &Column,
+    ) -> Option<MediaKind> {
+        let key = (table.name.clone(), column.name.clone());
+        match self.0.get(&key) {
+            Some(media_kind) => Some(*media_kind),
+            None => get_hinted_media_kind(column),
+        }
+    }
+
+    /// sample the binary and text columns of the tables
+    /// and keep the media kind of the columns which values are all media
+    pub fn infer(&mut self, dm: &mut DaoManager, tables: &[Table]) {
+        for table in tables.iter() {
+            let columns: Vec<&Column> = table
+                .columns
+                .iter()
+                .filter(|column| {
+                    let sql_type = column.get_sql_type();
+                    is_binary(&sql_type) || common::is_textual(&sql_type)
+                })
+                .collect();
+            if columns.is_empty() {
+                continue;
+            }
+            let sql = format!(
+                "SELECT {} FROM {} LIMIT {}",
+                columns
+                    .iter()
+                    .map(|column| column.name.name.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", "),
+                table.safe_complete_name(),
+                SAMPLE_SIZE
+            );
+            let rows = match dm.execute_sql_with_return(&sql, &[]) {
+                Ok(rows) => rows,
+                Err(e) => {
+                    println!(
+                        "Unable to sample {}: {}",
+                        table.complete_name(),
+                        e
+                    );
+                    continue;
+                }
+            };
+            for column in columns.iter() {
+                let values: Vec<Value> = rows
+                    .iter()
+                    .filter_map(|dao| dao.get_value(&column.name.name).cloned())
+                    .filter(|value| *value != Value::Nil)
+                    .collect();
+                if let Some(media_kind) = detect_media_kind(&values) {
+                    self.0.insert(
+                        (table.name.clone(), column.name.clone()),
+                        media_kind,
+                    );
+                }
+            }
+        }
+    }
+
+    /// set the media kind of the fields of the windows derived from the tables
+    pub fn apply_to_windows(&self, windows: &mut [Window], tables: &[Table]) {
+        for window in windows.iter_mut() {
+            for tab in window.tabs_mut() {
+                let table = match tables
+                    .iter()
+                    .find(|table| table.name == tab.table_name)
+                {
+                    Some(table) => table,
+                    None => continue,
+                };
+                for field in tab.fields.iter_mut() {
+                    let column_names = field.column_names();
+                    if column_names.len() != 1 {
+                        continue;
+                    }
+                    if let Some(column) = table
+                        .columns
+                        .iter()
+                        .find(|column| column.name == *column_names[0])
+                    {
+                        field.media = self.get_media_kind(table, column);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// all of the sampled values are media of the same kind
+fn detect_media_kind(values: &[Value]) -> Option<MediaKind> {
+    let mut detected = None;
+    for value in values.iter() {
+        let media_kind = match value {
+            Value::Blob(bytes) => {
+                sniff_mime_type(bytes).map(media_kind_of_mime)
+            }
+            Value::Text(text) if is_image_url(text) => Some(MediaKind::Image),
+            _ => None,
+        };
+        match (detected, media_kind) {
+            (_, None) => return None,
+            (Some(detected), Some(media_kind)) if detected != media_kind => {
+                return None;
+            }
+            (_, media_kind) => detected = media_kind,
+        }
+    }
+    detected
+}
+
+/// the binary columns are always sent as media,
+/// the text columns only when they hold the urls of images
+fn is_media_column(
+    media: &MediaRegistry,
+    table: &Table,
+    column: &Column,
+) -> bool {
+    let sql_type = column.get_sql_type();
+    is_binary(&sql_type)
+        || (common::is_textual(&sql_type)
+            && media.get_media_kind(table, column) == Some(MediaKind::Image))
+}
+
+/// the uri of the media route which serves the bytes of the column of the record
+fn media_uri(
+    table_name: &TableName,
+    column_name: &ColumnName,
+    record_id: &str,
+) -> String {
+    format!(
+        "/media/{}/{}/?record_id={}",
+        table_name.complete_name(),
+        column_name.name,
+        url::form_urlencoded::byte_serialize(record_id.as_bytes())
+            .collect::<String>()
+    )
+}
+
+/// the uri of the media of this value, the images are `Value::ImageUri`
+/// while the other files are the text of the uri to be downloaded
+fn to_media_uri(
+    media: &MediaRegistry,
+    table: &Table,
+    column: &Column,
+    value: &Value,
+    record_id: Option<&str>,
+) -> Option<Value> {
+    match value {
+        Value::Blob(bytes) => {
+            let uri = media_uri(&table.name, &column.name, record_id?);
+            let media_kind = sniff_mime_type(bytes)
+                .map(media_kind_of_mime)
+                .or_else(|| media.get_media_kind(table, column));
+            match media_kind {
+                Some(MediaKind::Image) => Some(Value::ImageUri(uri)),
+                Some(MediaKind::File) | None => Some(Value::Text(uri)),
+            }
+        }
+        Value::Text(text) if is_image_url(text) => {
+            Some(Value::ImageUri(text.trim().to_string()))
+        }
+        _ => None,
+    }
+}
+
+/// the uris of the media columns are added at the end of the rows
+/// in the `<column>.media` columns, while the bytes of the binary columns
+/// are left out of the rows
+pub fn to_media_uris(context: &Context, table: &Table, rows: &mut Rows) {
+    let media_columns: Vec<(usize, &Column)> = rows
+        .columns
+        .iter()
+        .enumerate()
+        .filter_map(|(index, column_name)| {
+            table
+                .columns
+                .iter()
+                .find(|column| column.name.name == *column_name)
+                .filter(|column| is_media_column(&context.media, table, column))
+                .map(|column| (index, column))
+        })
+        .collect();
+    if media_columns.is_empty() {
+        return;
+    }
+    let primary_indexes: Option<Vec<usize>> = table
+        .get_primary_column_names()
+        .iter()
+        .map(|pk| rows.columns.iter().position(|column| *column == pk.name))
+        .collect();
+    let primary_indexes =
+        primary_indexes.filter(|primary_indexes| !primary_indexes.is_empty());
+    for (_index, column) in media_columns.iter() {
+        rows.columns
+            .push(data_container::media_column_name(&column.name.name));
+    }
+    for row in rows.data.iter_mut() {
+        let record_id = primary_indexes.as_ref().map(|primary_indexes| {
+            let pk_values: Vec<&Value> =
+                primary_indexes.iter().map(|index| &row[*index]).collect();
+            common::encode_record_id(&pk_values)
+        });
+        let mut media_uris = Vec::with_capacity(media_columns.len());
+        for (index, column) in media_columns.iter() {
+            let media_uri = to_media_uri(
+                &context.media,
+                table,
+                column,
+                &row[*index],
+                record_id.as_ref().map(String::as_str),
+            );
+            if is_binary(&column.get_sql_type()) {
+                row[*index] = Value::Nil;
+            }
+            media_uris.push(media_uri.unwrap_or(Value::Nil));
+        }
+        row.extend(media_uris);
+    }
+}
+
+/// the bytes of the binary column of the record and their mime type,
+/// served by the media route
+pub fn get_media(
+    context: &Context,
+    dm: &mut DaoManager,
+    table_name: &TableName,
+    column_name: &str,
+    record_id: &str,
+) -> Result<(String, Vec<u8>), IntelError> {
+    let table = context.get_table(table_name).ok_or(IntelError::NotFound)?;
+    let column = table
+        .columns
+        .iter()
+        .find(|column| column.name.name == column_name)
+        .filter(|column| is_binary(&column.get_sql_type()))
+        .ok_or(IntelError::NotFound)?;
+    let pk_types = &table.get_primary_column_types();
+    let primary_columns = &table.get_primary_column_names();
+    let record_id =
+        common::extract_record_id(record_id, pk_types, primary_columns)?;
+    let mut sql = format!(
+        "SELECT {} FROM {} ",
+        column.name.name,
+        table.safe_complete_name()
+    );
+    for (i, (pk, _value)) in record_id.iter().enumerate() {
+        if i == 0 {
+            sql += "WHERE ";
+        } else {
+            sql += "AND ";
+        }
+        sql += &format!("{} = ${} ", pk.name, i + 1);
+    }
+    let params: Vec<&Value> =
+        record_id.iter().map(|(_pk, value)| value).collect();
+    let record = dm
+        .execute_sql_with_maybe_one_return(&sql, &params)?
+        .ok_or(IntelError::NotFound)?;
+    match record.get_value(&column.name.name) {
+        Some(Value::Blob(bytes)) => {
+            let mime = sniff_mime_type(bytes)
+                .unwrap_or("application/octet-stream")
+                .to_string();
+            Ok((mime, bytes.clone()))
+        }
+        _ => Err(IntelError::NotFound),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sniff_magic_numbers() {
+        assert_eq!(
+            sniff_mime_type(b"\x89PNG\r\n\x1a\n...."),
+            Some("image/png")
+        );
+        assert_eq!(
+            sniff_mime_type(b"RIFF\x00\x00\x00\x00WEBPVP8 "),
+            Some("image/webp")
+        );
+        assert_eq!(sniff_mime_type(b"%PDF-1.4"), Some("application/pdf"));
+        assert_eq!(sniff_mime_type(b"hello"), None);
+    }
+
+    #[test]
+    fn image_urls_and_hints() {
+        assert!(is_image_url("https://cdn.example.com/a/b.JPG?w=100"));
+        assert!(!is_image_url("https://example.com/page.html"));
+        assert!(!is_image_url("logo.png is the file"));
+        assert!(has_image_hint("profile_photo"));
+        assert!(!has_image_hint("description"));
+    }
+
+    #[test]
+    fn media_uri_encodes_record_id() {
+        assert_eq!(
+            media_uri(
+                &TableName::from("public.staff"),
+                &ColumnName::from("picture"),
+                "1,a b"
+            ),
+            "/media/public.staff/picture/?record_id=1%2Ca+b"
+        );
+    }
+}
@@ -0,0 +1,370 @@
+//! application specific validators and default values of fields,
+//! registered per table column by the application embedding diwata
+//!
+//! ```ignore
+//! plugin::register_validator(
+//!     &TableName::from("public.users"),
+//!     &ColumnName::from("email"),
+//!     |value: &Value, _record: &Dao| {
+//!         match value {
+//!             Value::Text(email) if !email.contains('@') => {
+//!                 Err("must contain @".to_string())
+//!             }
+//!             _ => Ok(()),
+//!         }
+//!     },
+//! );
+//! ```
+use crate::{
+    error::IntelError,
+    validation::FieldError,
+};
+use lazy_static::lazy_static;
+use rustorm::{
+    ColumnName,
+    Dao,
+    Rows,
+    Table,
+    TableName,
+    Value,
+};
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc,
+        RwLock,
+    },
+};
+
+lazy_static! {
+    pub static ref PLUGIN_REGISTRY: Arc<RwLock<PluginRegistry>> =
+        { Arc::new(RwLock::new(PluginRegistry::new())) };
+}
+
+/// checks the value of a field, the whole record is supplied
+/// for validations that depends on the other fields
+pub trait FieldValidator: Send + Sync {
+    /// the error message when the value is not valid
+    fn validate(&self, value: &Value, record: &Dao) -> Result<(), String>;
+}
+
+impl<F> FieldValidator for F
+where
+    F: Fn(&Value, &Dao) -> Result<(), String> + Send + Sync,
+{
+    fn validate(&self, value: &Value, record: &Dao) -> Result<(), String> {
+        self(value, record)
+    }
+}
+
+/// computes the value of a field of a new record when it is not supplied
+pub trait DefaultProvider: Send + Sync {
+    fn default_value(&self, record: &Dao) -> Value;
+}
+
+impl<F> DefaultProvider for F
+where
+    F: Fn(&Dao) -> Value + Send + Sync,
+{
+    fn default_value(&self, record: &Dao) -> Value {
+        self(record)
+    }
+}
+
+type FieldKey = (TableName, ColumnName);
+
+#[derive(Default)]
+pub struct PluginRegistry {
+    validators: HashMap<FieldKey, Vec<Box<dyn FieldValidator>>>,
+    defaults: HashMap<FieldKey, Box<dyn DefaultProvider>>,
+}
+
+impl PluginRegistry {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    /// add a validator to this column, a column can have several validators
+    pub fn add_validator<V>(
+        &mut self,
+        table_name: &TableName,
+        column_name: &ColumnName,
+        validator: V,
+    ) where
+        V: FieldValidator + 'static,
+    {
+        self.validators
+            .entry((table_name.clone(), column_name.clone()))
+            .or_insert_with(Vec::new)
+            .push(Box::new(validator));
+    }
+
+    /// set the default value provider of this column,
+    /// replacing the previous one
+    pub fn set_default<D>(
+        &mut self,
+        table_name: &TableName,
+        column_name: &ColumnName,
+        provider: D,
+    ) where
+        D: DefaultProvider + 'static,
+    {
+        self.defaults.insert(
+            (table_name.clone(), column_name.clone()),
+            Box::new(provider),
+        );
+    }
+
+    /// the errors of the registered validators of the columns in this record
+    pub fn get_field_errors(
+        &self,
+        table: &Table,
+        record: &Dao,
+    ) -> Vec<FieldError> {
+        let mut errors = vec![];
+        for column in table.columns.iter() {
+            let value = match record.get_value(&column.name.name) {
+                Some(value) => value,
+                None => continue,
+            };
+            let key = (table.name.clone(), column.name.clone());
+            if let Some(validators) = self.validators.get(&key) {
+                for validator in validators.iter() {
+                    if let Err(message) = validator.validate(value, record) {
+                        errors.push(FieldError {
+                            column_name: column.name.clone(),
+                            message,
+                        });
+                    }
+                }
+            }
+        }
+        errors
+    }
+
+    /// fill in the columns of the new record that are missing or null
+    /// with the value of their default providers
+    pub fn apply_defaults(&self, table: &Table, record: &Dao) -> Dao {
+        let mut record = record.clone();
+        for column in table.columns.iter() {
+            let key = (table.name.clone(), column.name.clone());
+            let is_empty = match record.get_value(&column.name.name) {
+                Some(value) => *value == Value::Nil,
+                None => true,
+            };
+            if is_empty {
+                if let Some(provider) = self.defaults.get(&key) {
+                    let value = provider.default_value(&record);
+                    record.insert_value(&column.name.name, &value);
+                }
+            }
+        }
+        record
+    }
+
+    /// apply the defaults to each of the new rows,
+    /// the columns that are filled in by the defaults are added to the rows
+    pub fn apply_defaults_to_rows(&self, table: &Table, rows: &Rows) -> Rows {
+        let records: Vec<Dao> = rows
+            .iter()
+            .map(|record| self.apply_defaults(table, &record))
+            .collect();
+        let mut columns: Vec<String> = rows.columns.clone();
+        for record in records.iter() {
+            for (column, _value) in record.0.iter() {
+                if !columns.contains(column) {
+                    columns.push(column.to_string());
+                }
+            }
+        }
+        let mut new_rows = Rows::new(columns.clone());
+        for record in records.iter() {
+            let row = columns
+                .iter()
+                .map(|column| {
+                    record.get_value(column).cloned().unwrap_or(Value::Nil)
+                })
+                .collect();
+            new_rows.push(row);
+        }
+        new_rows
+    }
+}
+
+/// register a validator of the column to the global plugin registry
+pub fn register_validator<V>(
+    table_name: &TableName,
+    column_name: &ColumnName,
+    validator: V,
+) -> Result<(), IntelError>
+where
+    V: FieldValidator + 'static,
+{
+    match PLUGIN_REGISTRY.write() {
+        Ok(mut registry) => {
+            registry.add_validator(table_name, column_name, validator);
+            Ok(())
+        }
+        Err(_e) => Err(IntelError::PluginRegistryError),
+    }
+}
+
+/// register a default value provider of the column
+/// to the global plugin registry
+pub fn register_default<D>(
+    table_name: &TableName,
+    column_name: &ColumnName,
+    provider: D,
+) -> Result<(), IntelError>
+where
+    D: DefaultProvider + 'static,
+{
+    match PLUGIN_REGISTRY.write() {
+        Ok(mut registry) => {
+            registry.set_default(table_name, column_name, provider);
+            Ok(())
+        }
+        Err(_e) => Err(IntelError::PluginRegistryError),
+    }
+}
+
+/// the new record with the registered defaults applied
+pub(crate) fn apply_defaults(
+    table: &Table,
+    record: &Dao,
+) -> Result<Dao, IntelError> {
+    match PLUGIN_REGISTRY.read() {
+        Ok(registry) => Ok(registry.apply_defaults(table, record)),
+        Err(_e) => Err(IntelError::PluginRegistryError),
+    }
+}
+
+/// the new rows with the registered defaults applied
+pub(crate) fn apply_defaults_to_rows(
+    table: &Table,
+    rows: &Rows,
+) -> Result<Rows, IntelError> {
+    match PLUGIN_REGISTRY.read() {
+        Ok(registry) => Ok(registry.apply_defaults_to_rows(table, rows)),
+        Err(_e) => Err(IntelError::PluginRegistryError),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validation;
+    use rustorm::{
+        column::ColumnSpecification,
+        types::SqlType,
+        Column,
+    };
+
+    fn users_table() -> Table {
+        let table_name = TableName::from("bazaar.users");
+        let columns = ["username", "email", "password"]
+            .iter()
+            .map(|name| {
+                Column {
+                    table: table_name.clone(),
+                    name: ColumnName::from(*name),
+                    comment: None,
+                    specification: ColumnSpecification {
+                        sql_type: SqlType::Text,
+                        capacity: None,
+                        constraints: vec![],
+                    },
+                    stat: None,
+                }
+            })
+            .collect();
+        Table {
+            name: table_name,
+            comment: None,
+            columns,
+            is_view: false,
+            table_key: vec![],
+        }
+    }
+
+    #[test]
+    fn registered_validator_fails_the_record() {
+        let table = users_table();
+        let mut registry = PluginRegistry::new();
+        registry.add_validator(
+            &table.name,
+            &ColumnName::from("email"),
+            |value: &Value, _record: &Dao| {
+                match value {
+                    Value::Text(email) if !email.contains('@') => {
+                        Err("must contain @".to_string())
+                    }
+                    _ => Ok(()),
+                }
+            },
+        );
+        let mut record = Dao::new();
+        record.insert_value("email", &Value::Text("john".to_string()));
+        let errors = validation::collect_record_errors(
+            &registry,
+            &[],
+            &table,
+            &record,
+            false,
+        );
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].column_name, ColumnName::from("email"));
+        assert_eq!(errors[0].message, "must contain @");
+        let mut record = Dao::new();
+        record.insert_value("email", &Value::Text("john@a.b".to_string()));
+        assert!(validation::collect_record_errors(
+            &registry,
+            &[],
+            &table,
+            &record,
+            false
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn defaults_fill_missing_and_null() {
+        let table = users_table();
+        let mut registry = PluginRegistry::new();
+        registry.set_default(
+            &table.name,
+            &ColumnName::from("email"),
+            |_record: &Dao| Value::Text("none@a.b".to_string()),
+        );
+        registry.set_default(
+            &table.name,
+            &ColumnName::from("password"),
+            |_record: &Dao| Value::Text("secret".to_string()),
+        );
+        let mut rows =
+            Rows::new(vec!["username".to_string(), "email".to_string()]);
+        rows.push(vec![Value::Text("john".to_string()), Value::Nil]);
+        rows.push(vec![
+            Value::Text("jane".to_string()),
+            Value::Text("jane@a.b".to_string()),
+        ]);
+        let rows = registry.apply_defaults_to_rows(&table, &rows);
+        assert_eq!(rows.columns, vec!["username", "email", "password"]);
+        assert_eq!(
+            rows.data[0],
+            vec![
+                Value::Text("john".to_string()),
+                Value::Text("none@a.b".to_string()),
+                Value::Text("secret".to_string()),
+            ]
+        );
+        assert_eq!(
+            rows.data[1],
+            vec![
+                Value::Text("jane".to_string()),
+                Value::Text("jane@a.b".to_string()),
+                Value::Text("secret".to_string()),
+            ]
+        );
+    }
+}
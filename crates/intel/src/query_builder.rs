@@ -1,9 +1,26 @@
 use crate::{
     common,
+    data_container::{
+        self,
+        Cursor,
+        Direction,
+        IdentifierDisplay,
+        Sort,
+    },
+    error::IntelError,
+    filter::{
+        self,
+        Condition,
+        Filter,
+    },
+    table_intel::TableIntel,
     Context,
 };
 use rustorm::{
+    common::cast_type,
     types::SqlType,
+    Column,
+    ColumnName,
     Dao,
     DaoManager,
     DbError,
@@ -14,14 +31,60 @@ use rustorm::{
 };
 use std::collections::BTreeMap;
 
+/// a foreign key column which value is displayed
+/// as the identifier of the record it refers to
+/// the display of the foreign key is named after its first column,
+/// the other columns of a composite foreign key are only joined on
+struct DisplayLookup {
+    columns: Vec<ColumnName>,
+    referred_table: TableName,
+    referred_columns: Vec<ColumnName>,
+    display: IdentifierDisplay,
+}
+
+/// the alias of the referred table joined for the display of this column
+fn display_alias(table: &Table, column: &ColumnName) -> String {
+    format!("{}__{}", table.name.name, column.name)
+}
+
+/// the display columns of the table qualified by the alias
+/// composed with the separator into a single text,
+/// a null column is shown as empty instead of nulling the whole display,
+/// while a display of only null columns, such as a missing reference,
+/// is null instead of the bare separators
+pub(crate) fn display_expression(
+    alias: &str,
+    display: &IdentifierDisplay,
+) -> String {
+    let separator = display
+        .separator
+        .as_ref()
+        .map(|separator| separator.replace('\'', "''"))
+        .unwrap_or_else(|| " ".to_string());
+    let composed = display
+        .columns
+        .iter()
+        .map(|column| {
+            format!("COALESCE(CAST({}.{} AS TEXT), '')", alias, column.name)
+        })
+        .collect::<Vec<String>>()
+        .join(&format!(" || '{}' || ", separator));
+    let empty = vec![""; display.columns.len()].join(&separator);
+    format!("NULLIF({}, '{}')", composed, empty)
+}
+
 pub struct Query<'c> {
     context: &'c Context,
     dm: &'c mut DaoManager,
     sql: String,
     from_table: Vec<TableName>,
+    /// the alias of the main table, when it is aliased
+    from_alias: Option<String>,
     joined_tables: Vec<TableName>,
     pub params: Vec<Value>,
     column_datatypes: BTreeMap<String, SqlType>,
+    /// a WHERE is already in the sql, the next condition is joined with AND
+    has_condition: bool,
 }
 
 impl<'c> Query<'c> {
@@ -30,9 +93,11 @@ impl<'c> Query<'c> {
             context,
             sql: String::new(),
             from_table: vec![],
+            from_alias: None,
             joined_tables: vec![],
             params: vec![],
             column_datatypes: BTreeMap::new(),
+            has_condition: false,
             dm,
         }
     }
@@ -51,6 +116,21 @@ impl<'c> Query<'c> {
         self.append("SELECT ");
     }
 
+    /// count the rows of this table, the rows of a left joined table
+    /// that has no matching row are not counted
+    pub fn select_count(&mut self, table: &Table) {
+        match table.get_primary_column_names().first() {
+            Some(pk) => {
+                self.append(&format!(
+                    "SELECT COUNT({}.{}) AS count ",
+                    table.safe_name(),
+                    pk.name
+                ))
+            }
+            None => self.append("SELECT COUNT(*) AS count "),
+        }
+    }
+
     /// enumerate all column including the rename to each specific data types
     pub fn enumerate_columns(&mut self, table: &Table) {
         let columns = &table.columns;
@@ -70,6 +150,79 @@ impl<'c> Query<'c> {
         self.add_table_datatypes(table);
     }
 
+    /// add the display of the records referred by the foreign keys
+    /// of the table, ie: `Smith, John` instead of the id of the person.
+    /// The referred tables are joined in `join_display_tables`
+    pub fn enumerate_display_columns(&mut self, table: &Table) {
+        for lookup in self.get_display_lookups(table) {
+            let alias = display_alias(table, &lookup.columns[0]);
+            self.append(&format!(
+                ", {} AS \"{}\"",
+                display_expression(&alias, &lookup.display),
+                data_container::display_column_name(&lookup.columns[0].name)
+            ));
+        }
+    }
+
+    /// join the tables referred by the foreign keys of the table
+    /// for the display columns
+    pub fn join_display_tables(&mut self, table: &Table) {
+        for lookup in self.get_display_lookups(table) {
+            let alias = display_alias(table, &lookup.columns[0]);
+            let on: Vec<String> = lookup
+                .columns
+                .iter()
+                .zip(lookup.referred_columns.iter())
+                .map(|(column, referred_column)| {
+                    format!(
+                        "{}.{} = {}.{}",
+                        alias,
+                        referred_column.name,
+                        table.safe_name(),
+                        column.name
+                    )
+                })
+                .collect();
+            self.append(&format!(
+                "LEFT JOIN {} AS {} ON {} \n",
+                lookup.referred_table.safe_complete_name(),
+                alias,
+                on.join(" AND ")
+            ));
+        }
+    }
+
+    /// the foreign key columns of the table which referred table
+    /// has an identifier to display
+    fn get_display_lookups(&self, table: &Table) -> Vec<DisplayLookup> {
+        let mut lookups = vec![];
+        for fk in table.get_foreign_keys() {
+            if fk.columns.is_empty()
+                || fk.columns.len() != fk.referred_columns.len()
+            {
+                continue;
+            }
+            let referred_table = match self.context.get_table(&fk.foreign_table)
+            {
+                Some(referred_table) => referred_table,
+                None => continue,
+            };
+            if let Some(dropdown_info) =
+                self.context.identifiers.get_dropdowninfo(referred_table)
+            {
+                if !dropdown_info.display.columns.is_empty() {
+                    lookups.push(DisplayLookup {
+                        columns: fk.columns.clone(),
+                        referred_table: referred_table.name.clone(),
+                        referred_columns: fk.referred_columns.clone(),
+                        display: dropdown_info.display,
+                    });
+                }
+            }
+        }
+        lookups
+    }
+
     /// add the data types of table columns that are not part of the main tables
     /// ie. the data type of the look up tables
     pub fn add_table_datatypes(&mut self, table: &Table) {
@@ -84,80 +237,407 @@ impl<'c> Query<'c> {
         self.append(&format!("\nFROM {} \n", table_name.safe_complete_name()));
     }
 
-    pub fn left_join(&mut self, join_to: &TableName, join_table: &TableName) {
-        assert!(!self.from_table.is_empty());
-        assert!(
-            self.from_table.contains(join_to)
-                || self.joined_tables.contains(join_to)
-        );
-        let join_to = self
+    /// the main table is aliased when the same table is joined later,
+    /// ie: the indirect table of a linker that links the table to itself
+    pub fn from_aliased(&mut self, table_name: &TableName, alias: &str) {
+        self.from_table.push(table_name.clone());
+        self.from_alias = Some(alias.to_string());
+        self.append(&format!(
+            "\nFROM {} AS {} \n",
+            table_name.safe_complete_name(),
+            alias
+        ));
+    }
+
+    /// the name the table is referred to in this query
+    fn table_alias(&self, table_name: &TableName) -> String {
+        match &self.from_alias {
+            Some(alias) if self.from_table.contains(table_name) => {
+                alias.to_string()
+            }
+            _ => table_name.name.to_string(),
+        }
+    }
+
+    /// join the table through the one foreign key between the 2 tables,
+    /// the foreign key of the joined table is looked up first,
+    /// then the foreign key of the table it is joined to
+    pub fn left_join(
+        &mut self,
+        join_to: &TableName,
+        join_table: &TableName,
+    ) -> Result<(), IntelError> {
+        let join_to_table = self
+            .context
+            .get_table(join_to)
+            .ok_or(IntelError::NotFound)?;
+        let joined_table = self
+            .context
+            .get_table(join_table)
+            .ok_or(IntelError::NotFound)?;
+        let referring: Vec<Vec<ColumnName>> = joined_table
+            .get_foreign_keys()
+            .into_iter()
+            .filter(|fk| fk.foreign_table == *join_to)
+            .map(|fk| fk.columns.clone())
+            .collect();
+        let referred: Vec<Vec<ColumnName>> = join_to_table
+            .get_foreign_keys()
+            .into_iter()
+            .filter(|fk| fk.foreign_table == *join_table)
+            .map(|fk| fk.columns.clone())
+            .collect();
+        match (referring.as_slice(), referred.as_slice()) {
+            ([columns], _) => {
+                self.left_join_referring(join_to, join_table, columns)
+            }
+            ([], [columns]) => {
+                self.left_join_referred(join_to, join_table, columns)
+            }
+            _ => {
+                Err(IntelError::ParamParseError(format!(
+                    "there is no single foreign key between {} and {}",
+                    join_to.complete_name(),
+                    join_table.complete_name()
+                )))
+            }
+        }
+    }
+
+    /// join the table that refers to the joined table
+    /// through the foreign key of these columns,
+    /// the other foreign keys of the table to the same table are not used
+    pub fn left_join_referring(
+        &mut self,
+        join_to: &TableName,
+        join_table: &TableName,
+        referring_columns: &[ColumnName],
+    ) -> Result<(), IntelError> {
+        let join_table = self
+            .context
+            .get_table(join_table)
+            .ok_or(IntelError::NotFound)?;
+        let fk = join_table
+            .get_foreign_keys()
+            .into_iter()
+            .find(|fk| {
+                fk.foreign_table == *join_to
+                    && fk.columns.as_slice() == referring_columns
+            })
+            .ok_or(IntelError::NotFound)?;
+        let join_to_alias = self.table_alias(join_to);
+        let condition = fk
+            .columns
+            .iter()
+            .zip(fk.referred_columns.iter())
+            .map(|(local, foreign)| {
+                format!(
+                    "{}.{} = {}.{}",
+                    join_table.safe_name(),
+                    local.name,
+                    join_to_alias,
+                    foreign.name
+                )
+            })
+            .collect::<Vec<String>>()
+            .join(" AND ");
+        self.append_left_join(join_table, &condition);
+        Ok(())
+    }
+
+    /// join the table referred by the foreign key of these columns
+    /// of the table it is joined to, ie: the indirect table of the linker
+    pub fn left_join_referred(
+        &mut self,
+        join_to: &TableName,
+        join_table: &TableName,
+        referring_columns: &[ColumnName],
+    ) -> Result<(), IntelError> {
+        let join_to_table = self
             .context
             .get_table(join_to)
-            .expect("should have a table");
+            .ok_or(IntelError::NotFound)?;
         let join_table = self
             .context
             .get_table(join_table)
-            .expect("Shoul have a table");
-        let local_foreign_pair =
-            join_table.get_local_foreign_columns_pair_to_table(&join_to.name);
-        self.append(&format!("LEFT JOIN {} ", join_table.complete_name()));
+            .ok_or(IntelError::NotFound)?;
+        let fk = join_to_table
+            .get_foreign_keys()
+            .into_iter()
+            .find(|fk| {
+                fk.foreign_table == join_table.name
+                    && fk.columns.as_slice() == referring_columns
+            })
+            .ok_or(IntelError::NotFound)?;
+        let join_to_alias = self.table_alias(join_to);
+        let condition = fk
+            .columns
+            .iter()
+            .zip(fk.referred_columns.iter())
+            .map(|(local, foreign)| {
+                format!(
+                    "{}.{} = {}.{}",
+                    join_to_alias,
+                    local.name,
+                    join_table.safe_name(),
+                    foreign.name
+                )
+            })
+            .collect::<Vec<String>>()
+            .join(" AND ");
+        self.append_left_join(join_table, &condition);
+        Ok(())
+    }
 
+    /// the joined table is aliased with its name, so it is not mistaken
+    /// for the aliased main table when they are the same table
+    fn append_left_join(&mut self, join_table: &Table, condition: &str) {
+        self.append(&format!(
+            "LEFT JOIN {} AS {} ON {} ",
+            join_table.complete_name(),
+            join_table.safe_name(),
+            condition
+        ));
         self.joined_tables.push(join_table.name.clone());
+    }
 
-        for (local, foreign) in local_foreign_pair {
+    /// the tables joined in this query
+    fn get_joined_tables(&self) -> Vec<&'c Table> {
+        self.joined_tables
+            .iter()
+            .filter_map(|table_name| self.context.get_table(table_name))
+            .collect()
+    }
+
+    /// add the extra columns of the linker table, named with the linker
+    /// so they don't clash with the columns of the indirect table
+    pub fn enumerate_linker_columns(&mut self, linker_table: &Table) {
+        for column in TableIntel(linker_table).get_linker_extra_columns() {
             self.append(&format!(
-                "ON {}.{} = {}.{} ",
-                join_table.name.name,
-                local.complete_name(),
-                join_to.name.name,
-                foreign.complete_name()
+                ", {}.{} AS \"{}\"",
+                linker_table.safe_name(),
+                column.name.name,
+                data_container::linker_column_name(
+                    &linker_table.name,
+                    &column.name.name
+                )
             ));
         }
+    }
 
-        let local_foreign_pair2 =
-            join_to.get_local_foreign_columns_pair_to_table(&join_table.name);
-
-        for (local, foreign) in local_foreign_pair2 {
-            self.append(&format!(
-                "ON {}.{} = {}.{} ",
-                join_to.name.name,
-                local.complete_name(),
-                join_table.name.name,
-                foreign.complete_name()
-            ));
+    /// start the next condition with WHERE or AND
+    fn append_condition(&mut self) {
+        if self.has_condition {
+            self.append("AND ");
+        } else {
+            self.append("\nWHERE ");
+            self.has_condition = true;
         }
     }
 
-    pub fn add_dao_filter(&mut self, table_name: &TableName, dao: &Dao) {
-        self.append("WHERE ");
-        for (column, value) in dao.0.iter() {
-            self.append(&format!("{}.{} = ", table_name.name, column));
+    /// filter the rows of the table to the values of the dao,
+    /// the keys of the dao are matched to the columns of the table
+    /// and only the matched column names are written in the sql
+    pub fn add_dao_filter(
+        &mut self,
+        table: &Table,
+        dao: &Dao,
+    ) -> Result<(), IntelError> {
+        for (key, value) in dao.0.iter() {
+            let column = table
+                .columns
+                .iter()
+                .find(|column| column.name.name == *key)
+                .ok_or_else(|| {
+                    IntelError::ParamParseError(format!(
+                        "{} is not a column of {}",
+                        key,
+                        table.complete_name()
+                    ))
+                })?;
+            self.append_condition();
+            let table_alias = self.table_alias(&table.name);
+            self.append(&format!("{}.{} = ", table_alias, column.name.name));
             self.add_param(value);
         }
+        Ok(())
+    }
+
+    /// add the conditions of the filter on the columns of this table,
+    /// the values are passed as parameters casted to the column type
+    pub fn add_filter(
+        &mut self,
+        table: &Table,
+        filter: &Filter,
+    ) -> Result<(), IntelError> {
+        let joined_tables = self.get_joined_tables();
+        for column_filter in filter.column_filters.iter() {
+            let (column_table, column) =
+                get_column(table, &joined_tables, &column_filter.column_name)?;
+            let sql_type = column.get_sql_type();
+            let column_name =
+                format!("{}.{}", column_table.safe_name(), column.name.name);
+            self.append_condition();
+            match &column_filter.condition {
+                Condition::Contains(search) => {
+                    self.append(&format!(
+                        "LOWER(CAST({} AS TEXT)) LIKE ",
+                        column_name
+                    ));
+                    let pattern = format!(
+                        "%{}%",
+                        filter::escape_like(&search.to_lowercase())
+                    );
+                    self.add_param(&Value::Text(pattern));
+                    self.append("ESCAPE '\\' ");
+                }
+                Condition::StartsWith(search) => {
+                    self.append(&format!(
+                        "LOWER(CAST({} AS TEXT)) LIKE ",
+                        column_name
+                    ));
+                    let pattern = format!(
+                        "{}%",
+                        filter::escape_like(&search.to_lowercase())
+                    );
+                    self.add_param(&Value::Text(pattern));
+                    self.append("ESCAPE '\\' ");
+                }
+                Condition::Equal(value) => {
+                    self.append(&format!("{} = ", column_name));
+                    self.add_param(&cast_type(value, &sql_type));
+                }
+                Condition::In(values) => {
+                    self.append(&format!("{} IN (", column_name));
+                    for (i, value) in values.iter().enumerate() {
+                        if i > 0 {
+                            self.append(", ");
+                        }
+                        self.add_param(&cast_type(value, &sql_type));
+                    }
+                    self.append(") ");
+                }
+                Condition::Range(from, to) => {
+                    match (from, to) {
+                        (Some(from), Some(to)) => {
+                            self.append(&format!("{} BETWEEN ", column_name));
+                            self.add_param(&cast_type(from, &sql_type));
+                            self.append("AND ");
+                            self.add_param(&cast_type(to, &sql_type));
+                        }
+                        (Some(from), None) => {
+                            self.append(&format!("{} >= ", column_name));
+                            self.add_param(&cast_type(from, &sql_type));
+                        }
+                        (None, Some(to)) => {
+                            self.append(&format!("{} <= ", column_name));
+                            self.add_param(&cast_type(to, &sql_type));
+                        }
+                        (None, None) => self.append("1 = 1 "),
+                    }
+                }
+                Condition::IsNull => {
+                    self.append(&format!("{} IS NULL ", column_name))
+                }
+                Condition::IsNotNull => {
+                    self.append(&format!("{} IS NOT NULL ", column_name))
+                }
+            }
+        }
+        Ok(())
     }
 
-    /*
-    pub fn set_sort(&mut self, sort: Sort) {
-        if !sort.orders.is_empty() {
-            self.append("ORDER BY ");
-            for (i, order) in sort.orders.iter().enumerate() {
+    /// order the rows of the table by the columns in the sort,
+    /// followed by the primary columns that are not in the sort
+    /// so the rows in the pages are always in the same order
+    pub fn set_sort(
+        &mut self,
+        table: &Table,
+        sort: &Sort,
+    ) -> Result<(), IntelError> {
+        let joined_tables = self.get_joined_tables();
+        let orders = get_orders(table, &joined_tables, sort)?;
+        if !orders.is_empty() {
+            self.append("\nORDER BY ");
+            for (i, (order_table, column, direction)) in
+                orders.iter().enumerate()
+            {
                 if i > 0 {
                     self.append(", ");
                 }
                 self.append(&format!(
-                    "{} ",
-                    order.column_name.safe_complete_name()
+                    "{}.{} ",
+                    order_table.safe_name(),
+                    column.name.name
                 ));
-                if order.direction == Direction::Asc {
-                    self.append("ASC ");
+                match direction {
+                    Direction::Asc => self.append("ASC "),
+                    Direction::Desc => self.append("DESC "),
                 }
-                if order.direction == Direction::Desc {
-                    self.append("DESC ");
+            }
+        }
+        Ok(())
+    }
+
+    /// only the rows after the cursor in the order of the sort,
+    /// ie: for `ORDER BY name ASC, id ASC` the condition is
+    /// `(name > $1) OR (name = $1 AND id > $2)`
+    pub fn add_keyset_condition(
+        &mut self,
+        table: &Table,
+        sort: &Sort,
+        cursor: &Cursor,
+    ) -> Result<(), IntelError> {
+        let orders = get_orders(table, &[], sort)?;
+        if !is_keyset_usable(table, &orders) {
+            return Err(IntelError::ParamParseError(format!(
+                "{} can not be paged by cursor in this sort",
+                table.complete_name()
+            )));
+        }
+        if cursor.values.len() != orders.len() {
+            return Err(IntelError::ParamParseError(format!(
+                "the cursor does not match the sort of {}",
+                table.complete_name()
+            )));
+        }
+        self.append_condition();
+        self.append("(");
+        for i in 0..orders.len() {
+            if i > 0 {
+                self.append("OR ");
+            }
+            self.append("(");
+            for (j, (_table, column, direction)) in
+                orders[..=i].iter().enumerate()
+            {
+                if j > 0 {
+                    self.append("AND ");
                 }
+                let operator = if j < i {
+                    "="
+                } else {
+                    match direction {
+                        Direction::Asc => ">",
+                        Direction::Desc => "<",
+                    }
+                };
+                self.append(&format!(
+                    "{}.{} {} ",
+                    table.safe_name(),
+                    column.name.name,
+                    operator
+                ));
+                self.add_param(&cast_type(
+                    &cursor.values[j],
+                    &column.get_sql_type(),
+                ));
             }
+            self.append(") ");
         }
+        self.append(") ");
+        Ok(())
     }
-    */
 
     pub fn set_page(&mut self, page: usize, page_size: usize) {
         self.set_limit(page_size);
@@ -180,6 +660,15 @@ impl<'c> Query<'c> {
         result.map(|rows| common::cast_rows(rows, &self.column_datatypes))
     }
 
+    /// the count of a SELECT COUNT(*) AS count query
+    pub fn collect_count(&mut self) -> Result<usize, DbError> {
+        println!("SQL: {}", self.sql);
+        println!("params: {:?}", self.params);
+        let bparams: Vec<&Value> = self.params.iter().collect();
+        let dao = self.dm.execute_sql_with_one_return(&self.sql, &bparams)?;
+        Ok(common::get_count(&dao, "count"))
+    }
+
     pub fn collect_maybe_record(&mut self) -> Result<Option<Dao>, DbError> {
         println!("SQL: {}", self.sql);
         println!("params: {:?}", self.params);
@@ -200,3 +689,143 @@ impl<'c> Query<'c> {
         Ok(common::cast_record(record, &self.column_datatypes))
     }
 }
+
+/// the columns the rows are ordered by, the primary columns
+/// are added last so the order of the rows is unique
+fn get_orders<'t>(
+    table: &'t Table,
+    joined_tables: &[&'t Table],
+    sort: &Sort,
+) -> Result<Vec<(&'t Table, &'t Column, Direction)>, IntelError> {
+    let mut orders: Vec<(&Table, &Column, Direction)> = vec![];
+    for order in sort.orders.iter() {
+        let (order_table, column) =
+            get_column(table, joined_tables, &order.column_name)?;
+        orders.push((order_table, column, order.direction));
+    }
+    for pk in table.get_primary_column_names() {
+        let is_ordered = orders.iter().any(|(order_table, column, _)| {
+            order_table.name == table.name && column.name == *pk
+        });
+        if !is_ordered {
+            if let Some(column) = table.get_column(pk) {
+                orders.push((table, column, Direction::Asc));
+            }
+        }
+    }
+    Ok(orders)
+}
+
+/// the column and the table it belongs to, the column name is qualified
+/// with the name of a joined table when the column is from that table,
+/// ie: `film_actor.role` on the indirect records
+fn get_column<'t>(
+    table: &'t Table,
+    joined_tables: &[&'t Table],
+    column_name: &ColumnName,
+) -> Result<(&'t Table, &'t Column), IntelError> {
+    let column_table = match &column_name.table {
+        Some(table_name) if *table_name != table.name.name => {
+            joined_tables
+                .iter()
+                .find(|joined| joined.name.name == *table_name)
+                .cloned()
+                .ok_or_else(|| {
+                    IntelError::ParamParseError(format!(
+                        "{} is not joined to {}",
+                        table_name,
+                        table.complete_name()
+                    ))
+                })?
+        }
+        _ => table,
+    };
+    let column = column_table
+        .columns
+        .iter()
+        .find(|column| column.name.name == column_name.name)
+        .ok_or_else(|| {
+            IntelError::ParamParseError(format!(
+                "no column {} in {}",
+                column_name.name,
+                column_table.complete_name()
+            ))
+        })?;
+    Ok((column_table, column))
+}
+
+/// the rows can be paged by cursor when the table has a primary key
+/// and none of the sort columns can be null,
+/// otherwise the rows are paged with OFFSET
+fn is_keyset_usable(
+    table: &Table,
+    orders: &[(&Table, &Column, Direction)],
+) -> bool {
+    !table.get_primary_column_names().is_empty()
+        && orders.iter().all(|(_, column, _)| column.is_not_null())
+}
+
+/// the cursor of the page after these rows, None if this is the last page
+/// or the rows can not be paged by cursor
+pub fn get_next_cursor(
+    table: &Table,
+    sort: &Sort,
+    rows: &Rows,
+    page_size: usize,
+) -> Result<Option<Cursor>, IntelError> {
+    let orders = get_orders(table, &[], sort)?;
+    if !is_keyset_usable(table, &orders) || rows.data.len() < page_size {
+        return Ok(None);
+    }
+    let last_row = match rows.data.last() {
+        Some(last_row) => last_row,
+        None => return Ok(None),
+    };
+    let mut values = vec![];
+    for (_table, column, _direction) in orders.iter() {
+        let index = rows
+            .columns
+            .iter()
+            .position(|name| *name == column.name.name);
+        match index {
+            Some(index) => values.push(last_row[index].clone()),
+            None => return Ok(None),
+        }
+    }
+    Ok(Some(Cursor { values }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn null_display_column_is_empty() {
+        let display = IdentifierDisplay {
+            columns: vec![
+                ColumnName::from("last_name"),
+                ColumnName::from("first_name"),
+            ],
+            pk: vec![],
+            separator: Some(", ".to_string()),
+        };
+        assert_eq!(
+            display_expression("actor", &display),
+            "NULLIF(COALESCE(CAST(actor.last_name AS TEXT), '') || ', ' || \
+             COALESCE(CAST(actor.first_name AS TEXT), ''), ', ')"
+        );
+    }
+
+    #[test]
+    fn null_single_display_column_is_null() {
+        let display = IdentifierDisplay {
+            columns: vec![ColumnName::from("name")],
+            pk: vec![],
+            separator: None,
+        };
+        assert_eq!(
+            display_expression("language", &display),
+            "NULLIF(COALESCE(CAST(language.name AS TEXT), ''), '')"
+        );
+    }
+}
@@ -0,0 +1,211 @@
+//! the quick find of a window, a single search matched against
+//! the identifier columns of the main table and then its other text columns.
+//! The rows which identifier matches the search the closest comes first
+use crate::{
+    common,
+    data_container::QueryResult,
+    error::IntelError,
+    filter,
+    media,
+    query_builder::Query,
+    Context,
+};
+use rustorm::{
+    Column,
+    DaoManager,
+    Table,
+    TableName,
+    Value,
+};
+
+/// how close the identifier of the row matches the search,
+/// the lower the more relevant
+enum Relevance {
+    Exact,
+    Prefix,
+    Contains,
+}
+
+impl Relevance {
+    fn pattern(&self, search: &str) -> String {
+        let search = filter::escape_like(search);
+        match self {
+            Relevance::Exact => search,
+            Relevance::Prefix => format!("{}%", search),
+            Relevance::Contains => format!("%{}%", search),
+        }
+    }
+}
+
+/// the case insensitive LIKE of the database,
+/// LIKE in sqlite is already case insensitive
+pub(crate) fn like_operator(db_url: &str) -> &'static str {
+    if db_url.starts_with("postgres") {
+        "ILIKE"
+    } else {
+        "LIKE"
+    }
+}
+
+/// the rows of the main table of the window that matches the search
+pub fn quick_find(
+    context: &Context,
+    dm: &mut DaoManager,
+    db_url: &str,
+    table_name: &TableName,
+    search: &str,
+    page: usize,
+    page_size: usize,
+) -> Result<QueryResult, IntelError> {
+    let window = context.get_window(table_name);
+    let main_table =
+        context.get_table(table_name).ok_or(IntelError::NotFound)?;
+    let identifier_columns: Vec<&Column> = match window {
+        Some(window) => {
+            window
+                .main_tab
+                .get_display_columns()
+                .into_iter()
+                .filter_map(|column_name| main_table.get_column(column_name))
+                .collect()
+        }
+        None => vec![],
+    };
+    let text_columns: Vec<&Column> = main_table
+        .columns
+        .iter()
+        .filter(|column| {
+            common::is_textual(&column.get_sql_type())
+                && !identifier_columns
+                    .iter()
+                    .any(|identifier| identifier.name == column.name)
+        })
+        .collect();
+    let search = search.trim();
+    let like = like_operator(db_url);
+
+    let mut query = Query::new(context, dm);
+    query.select();
+    query.enumerate_columns(main_table);
+    query.enumerate_display_columns(main_table);
+    query.from(table_name);
+    query.join_display_tables(main_table);
+    add_search_condition(
+        &mut query,
+        main_table,
+        &identifier_columns,
+        &text_columns,
+        search,
+        like,
+    );
+    set_relevance_order(
+        &mut query,
+        main_table,
+        &identifier_columns,
+        search,
+        like,
+    );
+    query.set_page(page, page_size);
+    let mut rows = query.collect_rows()?;
+    media::to_media_uris(context, main_table, &mut rows);
+
+    let mut count_query = Query::new(context, dm);
+    count_query.append("SELECT COUNT(*) AS count ");
+    count_query.from(table_name);
+    add_search_condition(
+        &mut count_query,
+        main_table,
+        &identifier_columns,
+        &text_columns,
+        search,
+        like,
+    );
+    rows.count = Some(count_query.collect_count()?);
+    Ok(QueryResult::with_rows(window, rows))
+}
+
+/// any of the identifier or text columns contains the search
+fn add_search_condition(
+    query: &mut Query,
+    table: &Table,
+    identifier_columns: &[&Column],
+    text_columns: &[&Column],
+    search: &str,
+    like: &str,
+) {
+    query.append("\nWHERE ");
+    let columns: Vec<&&Column> = identifier_columns
+        .iter()
+        .chain(text_columns.iter())
+        .collect();
+    if columns.is_empty() {
+        query.append("1 = 0 ");
+        return;
+    }
+    query.append("(");
+    for (i, column) in columns.iter().enumerate() {
+        if i > 0 {
+            query.append("OR ");
+        }
+        add_like(query, table, column, like, &Relevance::Contains, search);
+    }
+    query.append(") ");
+}
+
+/// the rows which identifier equals the search comes first,
+/// followed by the identifiers starting with the search,
+/// then the identifiers containing it, then the rest.
+/// The primary columns keeps the order of the rows the same in each page
+fn set_relevance_order(
+    query: &mut Query,
+    table: &Table,
+    identifier_columns: &[&Column],
+    search: &str,
+    like: &str,
+) {
+    let pks = table.get_primary_column_names();
+    if identifier_columns.is_empty() && pks.is_empty() {
+        return;
+    }
+    query.append("\nORDER BY ");
+    if !identifier_columns.is_empty() {
+        let relevances =
+            [Relevance::Exact, Relevance::Prefix, Relevance::Contains];
+        query.append("CASE ");
+        for (rank, relevance) in relevances.iter().enumerate() {
+            query.append("WHEN ");
+            for (i, column) in identifier_columns.iter().enumerate() {
+                if i > 0 {
+                    query.append("OR ");
+                }
+                add_like(query, table, column, like, relevance, search);
+            }
+            query.append(&format!("THEN {} ", rank));
+        }
+        query.append(&format!("ELSE {} END", relevances.len()));
+    }
+    for (i, pk) in pks.iter().enumerate() {
+        if i > 0 || !identifier_columns.is_empty() {
+            query.append(", ");
+        }
+        query.append(&format!("{}.{} ", table.safe_name(), pk.name));
+    }
+}
+
+fn add_like(
+    query: &mut Query,
+    table: &Table,
+    column: &Column,
+    like: &str,
+    relevance: &Relevance,
+    search: &str,
+) {
+    query.append(&format!(
+        "CAST({}.{} AS TEXT) {} ",
+        table.safe_name(),
+        column.name.name,
+        like
+    ));
+    query.add_param(&Value::Text(relevance.pattern(search)));
+    query.append("ESCAPE '\\' ");
+}
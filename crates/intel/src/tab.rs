@@ -5,10 +5,15 @@ use rustorm::{
 
 use crate::{
     data_container::{
+        self,
         DropdownInfo,
         IdentifierDisplay,
     },
     field::Field,
+    table_intel::{
+        self,
+        TableIntel,
+    },
 };
 use rustorm::{
     Column,
@@ -31,6 +36,16 @@ pub struct Tab {
     pub display: Option<IdentifierDisplay>,
 }
 
+/// the records referring to this record through these columns,
+/// the same table can be listed more than once when it has
+/// more than one foreign key to this record
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct HasManyTab {
+    /// the columns of the has_many table referring to this record
+    pub referring_columns: Vec<ColumnName>,
+    pub tab: Tab,
+}
+
 /// an indirect connection to this record
 /// must have an option to remove/show from the list
 /// async loaded?
@@ -38,14 +53,45 @@ pub struct Tab {
 pub struct IndirectTab {
     pub linker: TableName,
     pub tab: Tab,
+    /// the extra fields of the linker, displayed together with
+    /// the indirect records and named as `linker.column`
+    pub linker_fields: Vec<Field>,
 }
 
 impl IndirectTab {
-    pub fn new(linker: TableName, tab: Tab) -> Self {
-        IndirectTab { linker, tab }
+    pub fn new(linker: &Table, tab: Tab) -> Self {
+        let linker_fields = TableIntel(linker)
+            .get_linker_extra_columns()
+            .into_iter()
+            .map(|column| {
+                Field {
+                    name: data_container::linker_column_name(
+                        &linker.name,
+                        &column.name.name,
+                    ),
+                    ..Field::from_column(linker, column)
+                }
+            })
+            .collect();
+        IndirectTab {
+            linker: linker.name.clone(),
+            tab,
+            linker_fields,
+        }
     }
 }
 
+/// the hierarchy of the records of a table that refers to itself,
+/// ie: the employees under a manager
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct TreeTab {
+    /// the columns referring to the parent record, ie: `manager_id`
+    pub parent_columns: Vec<ColumnName>,
+    /// the columns of the parent record that are referred
+    pub referred_columns: Vec<ColumnName>,
+    pub tab: Tab,
+}
+
 impl Tab {
     pub fn from_table(
         table: &Table,
@@ -69,13 +115,52 @@ impl Tab {
     }
 
     /// The arrangement of fields are changed from the original arrangement in the table columns
-    /// causing a misalignment in the display
-    fn derive_fields(table: &Table, _tables: &[Table]) -> Vec<Field> {
-        table
-            .columns
-            .iter()
-            .map(|col| Field::from_column(table, col))
-            .collect()
+    /// causing a misalignment in the display,
+    /// the client places the values of the fields by their column names.
+    /// The columns of a composite foreign key are merged into one field
+    /// at the place of its first column
+    fn derive_fields(table: &Table, tables: &[Table]) -> Vec<Field> {
+        let compound_fks: Vec<(Vec<&Column>, &Table)> = table
+            .get_foreign_keys()
+            .into_iter()
+            .filter(|fk| fk.columns.len() > 1)
+            .filter_map(|fk| {
+                let referred_table =
+                    table_intel::get_table(&fk.foreign_table, tables)?;
+                let columns: Option<Vec<&Column>> = fk
+                    .columns
+                    .iter()
+                    .map(|column_name| {
+                        table
+                            .columns
+                            .iter()
+                            .find(|column| column.name == *column_name)
+                    })
+                    .collect();
+                columns.map(|columns| (columns, referred_table))
+            })
+            .collect();
+        let mut fields = vec![];
+        for column in table.columns.iter() {
+            let compound_fk = compound_fks.iter().find(|(columns, _)| {
+                columns
+                    .iter()
+                    .any(|fk_column| fk_column.name == column.name)
+            });
+            match compound_fk {
+                Some((columns, referred_table)) => {
+                    if columns[0].name == column.name {
+                        fields.push(Field::from_has_one_table(
+                            table,
+                            columns,
+                            referred_table,
+                        ));
+                    }
+                }
+                None => fields.push(Field::from_column(table, column)),
+            }
+        }
+        fields
     }
 
     pub fn derive_dropdowninfo(table: &Table) -> Option<DropdownInfo> {
@@ -114,7 +199,9 @@ impl Tab {
 
     // TODO: also make a function to do derive_image_display to detect
     // images that are displayeable
-    fn derive_display(table: &Table) -> Option<IdentifierDisplay> {
+    /// the display derived from the names of the columns,
+    /// the displays registered and inferred in the cache are applied over it
+    pub(crate) fn derive_display(table: &Table) -> Option<IdentifierDisplay> {
         let columns = &table.columns;
         let pk: Vec<ColumnName> = table
             .get_primary_column_names()
@@ -195,6 +282,18 @@ impl Tab {
     }
 }
 
+/// the has_many tab of the table referring through these columns
+pub fn find_has_many_tab<'a>(
+    has_many_tabs: &'a [HasManyTab],
+    table_name: &TableName,
+    referring_columns: &[ColumnName],
+) -> Option<&'a HasManyTab> {
+    has_many_tabs.iter().find(|has_many_tab| {
+        has_many_tab.tab.table_name == *table_name
+            && has_many_tab.referring_columns.as_slice() == referring_columns
+    })
+}
+
 pub fn find_tab<'a>(
     tabs: &'a [Tab],
     table_name: &TableName,
@@ -0,0 +1,108 @@
+//! keeps the journal of the recent modifications for a grace period
+//! so the user can undo the changes
+use crate::journal::Journal;
+use lazy_static::lazy_static;
+use std::{
+    collections::BTreeMap,
+    sync::{
+        Arc,
+        Mutex,
+    },
+    time::{
+        Duration,
+        Instant,
+    },
+};
+use uuid::Uuid;
+
+/// the default grace period in seconds
+const DEFAULT_GRACE_PERIOD: u64 = 60;
+
+lazy_static! {
+    pub static ref UNDO_POOL: Arc<Mutex<UndoPool>> =
+        { Arc::new(Mutex::new(UndoPool::new())) };
+}
+
+struct UndoEntry {
+    db_url: String,
+    /// only the user who made the changes can undo them
+    username: Option<String>,
+    created: Instant,
+    journal: Journal,
+}
+
+/// the journals that can still be undone, keyed by the undo id,
+/// the ids are random so they can not be guessed
+pub struct UndoPool {
+    entries: BTreeMap<String, UndoEntry>,
+    grace_period: Duration,
+}
+
+impl UndoPool {
+    fn new() -> Self {
+        UndoPool {
+            entries: BTreeMap::new(),
+            grace_period: Duration::from_secs(DEFAULT_GRACE_PERIOD),
+        }
+    }
+
+    pub fn set_grace_period(&mut self, grace_period: Duration) {
+        self.grace_period = grace_period;
+    }
+
+    /// keep the journal of the modification of this user on this DB_URL,
+    /// returns the undo id, None if there is nothing to undo
+    pub fn store(
+        &mut self,
+        db_url: &str,
+        username: Option<&str>,
+        journal: Journal,
+    ) -> Option<String> {
+        self.remove_expired();
+        if journal.is_empty() {
+            return None;
+        }
+        let undo_id = Uuid::new_v4().to_string();
+        self.entries.insert(
+            undo_id.clone(),
+            UndoEntry {
+                db_url: db_url.to_string(),
+                username: username.map(ToString::to_string),
+                created: Instant::now(),
+                journal,
+            },
+        );
+        Some(undo_id)
+    }
+
+    /// the journal of this undo id if it is still within the grace period
+    /// and was done by the same user in the same DB_URL.
+    /// It is kept until it is removed after the undo is committed,
+    /// so a failed undo can be tried again
+    pub fn get(
+        &mut self,
+        db_url: &str,
+        username: Option<&str>,
+        undo_id: &str,
+    ) -> Option<Journal> {
+        self.remove_expired();
+        self.entries
+            .get(undo_id)
+            .filter(|entry| {
+                entry.db_url == db_url
+                    && entry.username.as_ref().map(String::as_str) == username
+            })
+            .map(|entry| entry.journal.clone())
+    }
+
+    /// the journal is already undone
+    pub fn remove(&mut self, undo_id: &str) {
+        self.entries.remove(undo_id);
+    }
+
+    fn remove_expired(&mut self) {
+        let grace_period = self.grace_period;
+        self.entries
+            .retain(|_id, entry| entry.created.elapsed() <= grace_period);
+    }
+}
@@ -0,0 +1,631 @@
+//! check the values of a record against the column definitions of the table
+//! before any sql is issued, so the user gets an error per field
+//! instead of the raw database error
+use crate::{
+    cache,
+    common,
+    error::IntelError,
+    plugin::{
+        self,
+        PluginRegistry,
+    },
+};
+use rustorm::{
+    column::{
+        Capacity,
+        ColumnConstraint,
+    },
+    types::SqlType,
+    Column,
+    ColumnName,
+    Dao,
+    DaoManager,
+    Table,
+    Value,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::cmp::Ordering;
+
+/// the value of this column of the record is not valid
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct FieldError {
+    pub column_name: ColumnName,
+    pub message: String,
+}
+
+impl FieldError {
+    fn new(column: &Column, message: &str) -> Self {
+        FieldError {
+            column_name: column.name.clone(),
+            message: message.to_string(),
+        }
+    }
+}
+
+/// validate the record against the table definition and check constraints,
+/// when the record is new, the missing columns are treated as null,
+/// otherwise only the columns present in the record are checked
+pub fn validate_record(
+    dm: &mut DaoManager,
+    db_url: &str,
+    table: &Table,
+    record: &Dao,
+    is_new: bool,
+) -> Result<(), IntelError> {
+    let errors = get_record_errors(dm, db_url, table, record, is_new)?;
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(IntelError::ValidationError {
+            table_name: table.name.clone(),
+            errors,
+        })
+    }
+}
+
+/// all the errors of the record including the errors of the registered
+/// validators, so they can be shown next to the fields before it is saved
+pub fn get_record_errors(
+    dm: &mut DaoManager,
+    db_url: &str,
+    table: &Table,
+    record: &Dao,
+    is_new: bool,
+) -> Result<Vec<FieldError>, IntelError> {
+    let checks = get_check_constraints(dm, db_url, table)?;
+    match plugin::PLUGIN_REGISTRY.read() {
+        Ok(registry) => {
+            Ok(collect_record_errors(
+                &registry, &checks, table, record, is_new,
+            ))
+        }
+        Err(_e) => Err(IntelError::PluginRegistryError),
+    }
+}
+
+/// the errors of the column definitions, the check constraints
+/// and the validators of the registry
+pub(crate) fn collect_record_errors(
+    registry: &PluginRegistry,
+    checks: &[CheckConstraint],
+    table: &Table,
+    record: &Dao,
+    is_new: bool,
+) -> Vec<FieldError> {
+    let mut errors = get_field_errors(table, record, is_new);
+    for check in checks.iter() {
+        errors.extend(check.validate(table, record));
+    }
+    errors.extend(registry.get_field_errors(table, record));
+    errors
+}
+
+/// the errors of the record which can be determined
+/// from the column definitions alone
+pub fn get_field_errors(
+    table: &Table,
+    record: &Dao,
+    is_new: bool,
+) -> Vec<FieldError> {
+    let mut errors = vec![];
+    for column in table.columns.iter() {
+        let value = match record.get_value(&column.name.name) {
+            Some(value) => value,
+            None if is_new => &Value::Nil,
+            None => continue,
+        };
+        if let Some(error) = validate_value(column, value) {
+            errors.push(error);
+        }
+    }
+    errors.extend(get_composite_foreign_key_errors(table, record, is_new));
+    errors
+}
+
+/// the columns of a composite foreign key are edited as one field,
+/// they have to be given together and be either all null or all set,
+/// since the database does not check a partially null foreign key
+fn get_composite_foreign_key_errors(
+    table: &Table,
+    record: &Dao,
+    is_new: bool,
+) -> Vec<FieldError> {
+    table
+        .get_foreign_keys()
+        .iter()
+        .filter(|fk| fk.columns.len() > 1)
+        .flat_map(|fk| get_partial_key_errors(&fk.columns, record, is_new))
+        .collect()
+}
+
+/// the errors of the columns that are missing or null
+/// while the other columns of the key are set
+fn get_partial_key_errors(
+    key_columns: &[ColumnName],
+    record: &Dao,
+    is_new: bool,
+) -> Vec<FieldError> {
+    let values: Vec<Option<&Value>> = key_columns
+        .iter()
+        .map(|column| {
+            match record.get_value(&column.name) {
+                Some(value) => Some(value),
+                None if is_new => Some(&Value::Nil),
+                None => None,
+            }
+        })
+        .collect();
+    if values.iter().all(Option::is_none) {
+        return vec![];
+    }
+    let is_set = |value: &Option<&Value>| {
+        match value {
+            Some(Value::Nil) | None => false,
+            Some(_) => true,
+        }
+    };
+    let is_partial = values.iter().any(Option::is_none)
+        || (values.iter().any(is_set) && !values.iter().all(is_set));
+    if !is_partial {
+        return vec![];
+    }
+    let column_names: Vec<&str> = key_columns
+        .iter()
+        .map(|column| column.name.as_str())
+        .collect();
+    let message =
+        format!("must be set together with {}", column_names.join(", "));
+    key_columns
+        .iter()
+        .zip(values.iter())
+        .filter(|(_column_name, value)| !is_set(*value))
+        .map(|(column_name, _value)| {
+            FieldError {
+                column_name: column_name.clone(),
+                message: message.clone(),
+            }
+        })
+        .collect()
+}
+
+fn validate_value(column: &Column, value: &Value) -> Option<FieldError> {
+    if *value == Value::Nil {
+        if column.is_not_null() && !has_default(column) {
+            return Some(FieldError::new(column, "is required"));
+        }
+        return None;
+    }
+    let sql_type = column.get_sql_type();
+    // the value from the input may still be in text,
+    // the types that can not be parsed here are passed as is to the database,
+    // so are the dates and times in the other formats the database accepts
+    let parsed;
+    let value = match value {
+        Value::Text(text)
+            if !common::is_textual(&sql_type)
+                && common::is_parsable(&sql_type) =>
+        {
+            match common::parse_value(text, &sql_type) {
+                Ok(value) => {
+                    parsed = value;
+                    &parsed
+                }
+                Err(_) if is_temporal(&sql_type) => value,
+                Err(_) => {
+                    return Some(FieldError::new(
+                        column,
+                        &format!("is not a valid {:?}", sql_type),
+                    ));
+                }
+            }
+        }
+        _ => value,
+    };
+    if let Some(error) = validate_range(column, &sql_type, value) {
+        return Some(error);
+    }
+    match (&column.specification.capacity, value) {
+        (Some(Capacity::Limit(limit)), Value::Text(text))
+            if text.chars().count() > *limit as usize =>
+        {
+            Some(FieldError::new(
+                column,
+                &format!("must not be longer than {} characters", limit),
+            ))
+        }
+        (Some(Capacity::Range(precision, scale)), _) => {
+            let number = numeric_to_string(value)?;
+            validate_precision(column, &number, *precision, *scale)
+        }
+        _ => None,
+    }
+}
+
+fn has_default(column: &Column) -> bool {
+    column.specification.constraints.iter().any(|constraint| {
+        match constraint {
+            ColumnConstraint::DefaultValue(_)
+            | ColumnConstraint::AutoIncrement => true,
+            _ => false,
+        }
+    })
+}
+
+fn is_temporal(sql_type: &SqlType) -> bool {
+    match sql_type {
+        SqlType::Date
+        | SqlType::Timestamp
+        | SqlType::TimestampTz
+        | SqlType::Time
+        | SqlType::TimeTz => true,
+        _ => false,
+    }
+}
+
+/// the value must fit in the integer type of the column
+fn validate_range(
+    column: &Column,
+    sql_type: &SqlType,
+    value: &Value,
+) -> Option<FieldError> {
+    let number = match value {
+        Value::Tinyint(v) => i64::from(*v),
+        Value::Smallint(v) => i64::from(*v),
+        Value::Int(v) => i64::from(*v),
+        Value::Bigint(v) => *v,
+        _ => return None,
+    };
+    let (min, max) = match sql_type {
+        SqlType::Tinyint => {
+            (i64::from(i8::min_value()), i64::from(i8::max_value()))
+        }
+        SqlType::Smallint => {
+            (i64::from(i16::min_value()), i64::from(i16::max_value()))
+        }
+        SqlType::Int => {
+            (i64::from(i32::min_value()), i64::from(i32::max_value()))
+        }
+        _ => return None,
+    };
+    if number < min || number > max {
+        Some(FieldError::new(
+            column,
+            &format!("must be between {} and {}", min, max),
+        ))
+    } else {
+        None
+    }
+}
+
+fn numeric_to_string(value: &Value) -> Option<String> {
+    match value {
+        Value::Tinyint(v) => Some(v.to_string()),
+        Value::Smallint(v) => Some(v.to_string()),
+        Value::Int(v) => Some(v.to_string()),
+        Value::Bigint(v) => Some(v.to_string()),
+        Value::Float(v) => Some(v.to_string()),
+        Value::Double(v) => Some(v.to_string()),
+        Value::BigDecimal(v) => Some(v.to_string()),
+        _ => None,
+    }
+}
+
+/// a numeric(precision, scale) can only have precision - scale digits
+/// before the decimal point and scale digits after it
+fn validate_precision(
+    column: &Column,
+    number: &str,
+    precision: i32,
+    scale: i32,
+) -> Option<FieldError> {
+    let number = number.trim_start_matches('-');
+    let mut parts = number.splitn(2, '.');
+    let whole = parts.next().unwrap_or("").trim_start_matches('0');
+    let fraction = parts.next().unwrap_or("").trim_end_matches('0');
+    let max_whole = (precision - scale).max(0) as usize;
+    if whole.len() > max_whole {
+        Some(FieldError::new(
+            column,
+            &format!(
+                "must have at most {} digits before the decimal point",
+                max_whole
+            ),
+        ))
+    } else if fraction.len() > scale.max(0) as usize {
+        Some(FieldError::new(
+            column,
+            &format!("must have at most {} decimal places", scale),
+        ))
+    } else {
+        None
+    }
+}
+
+/// a CHECK constraint that is a comparison of a column to a literal,
+/// or several of them joined by AND
+#[derive(Debug, PartialEq)]
+pub struct CheckConstraint {
+    definition: String,
+    comparisons: Vec<Comparison>,
+}
+
+#[derive(Debug, PartialEq)]
+struct Comparison {
+    column: String,
+    operator: String,
+    literal: Literal,
+}
+
+#[derive(Debug, PartialEq)]
+enum Literal {
+    Number(f64),
+    Text(String),
+}
+
+impl CheckConstraint {
+    /// parse the definition as returned by pg_get_constraintdef,
+    /// ie: CHECK (((rating >= 1) AND (rating <= 5))),
+    /// None if it is not a simple check
+    pub fn parse(definition: &str) -> Option<Self> {
+        let body = definition.trim().trim_start_matches("CHECK").trim();
+        if has_function_call(body) {
+            return None;
+        }
+        let body: String =
+            body.chars().filter(|c| *c != '(' && *c != ')').collect();
+        let body = strip_casts(&body);
+        let mut comparisons = vec![];
+        for part in body.split(" AND ") {
+            comparisons.push(Comparison::parse(part)?);
+        }
+        Some(CheckConstraint {
+            definition: definition.to_string(),
+            comparisons,
+        })
+    }
+
+    fn validate(&self, table: &Table, record: &Dao) -> Vec<FieldError> {
+        let mut errors = vec![];
+        for comparison in self.comparisons.iter() {
+            let column = table
+                .columns
+                .iter()
+                .find(|column| column.name.name == comparison.column);
+            let value = record.get_value(&comparison.column);
+            if let (Some(column), Some(value)) = (column, value) {
+                if comparison.is_violated_by(value) {
+                    errors.push(FieldError::new(
+                        column,
+                        &format!("violates {}", self.definition),
+                    ));
+                }
+            }
+        }
+        errors
+    }
+}
+
+/// remove the type casts such as ::numeric and ::character varying
+fn strip_casts(body: &str) -> String {
+    let mut words = vec![];
+    let mut is_cast = false;
+    for word in body.split_whitespace() {
+        if is_cast && (word == "varying" || word == "precision") {
+            continue;
+        }
+        is_cast = word.contains("::");
+        words.push(word.split("::").next().unwrap_or(word));
+    }
+    words.join(" ")
+}
+
+/// a function call such as length(name) can not be checked here
+fn has_function_call(body: &str) -> bool {
+    let mut previous = ' ';
+    for c in body.chars() {
+        if c == '(' && (previous.is_alphanumeric() || previous == '_') {
+            return true;
+        }
+        previous = c;
+    }
+    false
+}
+
+impl Comparison {
+    fn parse(part: &str) -> Option<Self> {
+        let tokens: Vec<&str> = part.split_whitespace().collect();
+        if tokens.len() != 3 {
+            return None;
+        }
+        let operator = match tokens[1] {
+            "=" | "<>" | "!=" | "<" | "<=" | ">" | ">=" => tokens[1],
+            _ => return None,
+        };
+        let literal = tokens[2];
+        let literal = if literal.starts_with('\'') && literal.ends_with('\'') {
+            Literal::Text(literal.trim_matches('\'').to_string())
+        } else {
+            Literal::Number(literal.parse().ok()?)
+        };
+        Some(Comparison {
+            column: tokens[0].to_string(),
+            operator: operator.to_string(),
+            literal,
+        })
+    }
+
+    fn is_violated_by(&self, value: &Value) -> bool {
+        let ordering = match (&self.literal, value) {
+            (_, Value::Nil) => return false,
+            (Literal::Text(literal), Value::Text(text)) => {
+                text.as_str().partial_cmp(literal.as_str())
+            }
+            (Literal::Number(literal), value) => {
+                match numeric_to_string(value)
+                    .or_else(|| {
+                        match value {
+                            Value::Text(text) => Some(text.to_string()),
+                            _ => None,
+                        }
+                    })
+                    .and_then(|number| number.parse::<f64>().ok())
+                {
+                    Some(number) => number.partial_cmp(literal),
+                    None => return false,
+                }
+            }
+            _ => return false,
+        };
+        let ordering = match ordering {
+            Some(ordering) => ordering,
+            None => return false,
+        };
+        let is_satisfied = match self.operator.as_str() {
+            "=" => ordering == Ordering::Equal,
+            "<>" | "!=" => ordering != Ordering::Equal,
+            "<" => ordering == Ordering::Less,
+            "<=" => ordering != Ordering::Greater,
+            ">" => ordering == Ordering::Greater,
+            ">=" => ordering != Ordering::Less,
+            _ => true,
+        };
+        !is_satisfied
+    }
+}
+
+/// get the simple CHECK constraints of the table,
+/// the rest are left for the database to enforce.
+/// The definitions are read once for each table and cached on the db_url,
+/// they are only read from postgresql, the other databases enforce them alone
+fn get_check_constraints(
+    dm: &mut DaoManager,
+    db_url: &str,
+    table: &Table,
+) -> Result<Vec<CheckConstraint>, IntelError> {
+    if !db_url.starts_with("postgres") {
+        return Ok(vec![]);
+    }
+    let cached = match cache::CACHE_POOL.lock() {
+        Ok(cache_pool) => {
+            cache_pool.get_cached_check_constraints(db_url, &table.name)
+        }
+        Err(_) => return Err(IntelError::CacheServiceError),
+    };
+    let definitions = match cached {
+        Some(definitions) => definitions,
+        None => {
+            let definitions = fetch_check_definitions(dm, table)?;
+            match cache::CACHE_POOL.lock() {
+                Ok(mut cache_pool) => {
+                    cache_pool.cache_check_constraints(
+                        db_url,
+                        &table.name,
+                        definitions.clone(),
+                    )
+                }
+                Err(_) => return Err(IntelError::CacheServiceError),
+            }
+            definitions
+        }
+    };
+    let checks = definitions
+        .iter()
+        .filter_map(|definition| CheckConstraint::parse(definition))
+        .collect();
+    Ok(checks)
+}
+
+/// Warning: This only works for postgresql
+fn fetch_check_definitions(
+    dm: &mut DaoManager,
+    table: &Table,
+) -> Result<Vec<String>, IntelError> {
+    let sql = "SELECT pg_get_constraintdef(oid) AS definition \
+               FROM pg_constraint \
+               WHERE contype = 'c' AND conrelid = $1::text::regclass";
+    let params = [Value::Text(table.complete_name())];
+    let bparams: Vec<&Value> = params.iter().collect();
+    let rows = dm.execute_sql_with_return(sql, &bparams)?;
+    let definitions = rows
+        .iter()
+        .filter_map(|dao| {
+            match dao.get_value("definition") {
+                Some(Value::Text(definition)) => Some(definition.to_string()),
+                _ => None,
+            }
+        })
+        .collect();
+    Ok(definitions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_range_check() {
+        let check = CheckConstraint::parse(
+            "CHECK (((rating >= (1)::numeric) AND (rating <= (5)::numeric)))",
+        )
+        .expect("must be a simple check");
+        assert_eq!(check.comparisons.len(), 2);
+        assert!(check.comparisons[0].is_violated_by(&Value::Int(0)));
+        assert!(!check.comparisons[0].is_violated_by(&Value::Int(3)));
+        assert!(check.comparisons[1].is_violated_by(&Value::Int(6)));
+    }
+
+    fn film_key() -> Vec<ColumnName> {
+        vec![ColumnName::from("film_id"), ColumnName::from("store_id")]
+    }
+
+    #[test]
+    fn complete_composite_key_is_valid() {
+        let mut record = Dao::new();
+        record.insert_value("film_id", &Value::Int(1));
+        record.insert_value("store_id", &Value::Int(2));
+        assert!(get_partial_key_errors(&film_key(), &record, true).is_empty());
+        let mut record = Dao::new();
+        record.insert_value("film_id", &Value::Nil);
+        record.insert_value("store_id", &Value::Nil);
+        assert!(get_partial_key_errors(&film_key(), &record, true).is_empty());
+    }
+
+    #[test]
+    fn partially_null_composite_key() {
+        let mut record = Dao::new();
+        record.insert_value("film_id", &Value::Int(1));
+        record.insert_value("store_id", &Value::Nil);
+        let errors = get_partial_key_errors(&film_key(), &record, false);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].column_name, ColumnName::from("store_id"));
+        assert_eq!(
+            errors[0].message,
+            "must be set together with film_id, store_id"
+        );
+    }
+
+    #[test]
+    fn composite_key_given_partly_on_update() {
+        let mut record = Dao::new();
+        record.insert_value("film_id", &Value::Int(1));
+        let errors = get_partial_key_errors(&film_key(), &record, false);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].column_name, ColumnName::from("store_id"));
+        // the key is left as is when none of its columns are given
+        let record = Dao::new();
+        assert!(get_partial_key_errors(&film_key(), &record, false).is_empty());
+        // the missing columns of a new record are null
+        let errors = get_partial_key_errors(&film_key(), &record, true);
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn complex_check_is_not_parsed() {
+        let check =
+            CheckConstraint::parse("CHECK ((length((name)::text) > 0))");
+        assert_eq!(check, None);
+    }
+}
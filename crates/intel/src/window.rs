@@ -1,9 +1,14 @@
 use crate::{
     tab::{
+        HasManyTab,
         IndirectTab,
         Tab,
+        TreeTab,
     },
     table_intel::{
+        self,
+        HasManyTable,
+        HasOneTable,
         IndirectTable,
         TableIntel,
     },
@@ -44,10 +49,14 @@ pub struct Window {
 
     /// the tabs that refers to the selected record
     /// 1:M
-    pub has_many_tabs: Vec<Tab>,
+    pub has_many_tabs: Vec<HasManyTab>,
 
     pub indirect_tabs: Vec<IndirectTab>,
 
+    /// the hierarchies of the records of the main table,
+    /// one for each foreign key that refers to the main table itself
+    pub tree_tabs: Vec<TreeTab>,
+
     pub is_view: bool,
 }
 
@@ -55,8 +64,8 @@ impl Window {
     fn from_tables(
         main_table: &Table,
         one_one: &[&Table],
-        has_one: &[&Table],
-        has_many: &[&Table],
+        has_one: &[HasOneTable],
+        has_many: &[HasManyTable],
         indirect: &[IndirectTable],
         all_tables: &[Table],
     ) -> Self {
@@ -65,13 +74,48 @@ impl Window {
             .iter()
             .map(|t| Tab::from_table(t, None, all_tables))
             .collect();
+        // the tabs of the tables related more than once are named by their role
         let has_one_tabs: Vec<Tab> = has_one
             .iter()
-            .map(|t| Tab::from_table(t, None, all_tables))
+            .map(|t| {
+                let is_repeated = has_one
+                    .iter()
+                    .filter(|other| other.table.name == t.table.name)
+                    .count()
+                    > 1;
+                let tab_name = if is_repeated {
+                    Some(table_intel::role_name(t.columns, &t.table.name))
+                } else {
+                    None
+                };
+                Tab::from_table(t.table, tab_name, all_tables)
+            })
             .collect();
-        let has_many_tabs: Vec<Tab> = has_many
+        let has_many_tabs: Vec<HasManyTab> = has_many
             .iter()
-            .map(|t| Tab::from_table(t, None, all_tables))
+            .map(|t| {
+                let is_repeated = has_many
+                    .iter()
+                    .filter(|other| other.table.name == t.table.name)
+                    .count()
+                    > 1;
+                let tab_name = if is_repeated {
+                    Some(format!(
+                        "{} (as {})",
+                        t.table.name.name,
+                        table_intel::role_name(
+                            t.referring_columns,
+                            &main_table.name
+                        )
+                    ))
+                } else {
+                    None
+                };
+                HasManyTab {
+                    referring_columns: t.referring_columns.clone(),
+                    tab: Tab::from_table(t.table, tab_name, all_tables),
+                }
+            })
             .collect();
         let is_view = main_tab.is_view;
 
@@ -89,11 +133,35 @@ impl Window {
                     None
                 };
                 IndirectTab::new(
-                    t.linker.name.clone(),
+                    t.linker,
                     Tab::from_table(t.indirect_table, tab_name, all_tables),
                 )
             })
             .collect();
+        let tree_tabs: Vec<TreeTab> = TableIntel(main_table)
+            .get_self_references()
+            .into_iter()
+            .map(|(parent_columns, referred_columns)| {
+                let tab_name = format!(
+                    "{} tree (by {})",
+                    main_table.name.name,
+                    parent_columns
+                        .iter()
+                        .map(|column| column.name.to_string())
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                );
+                TreeTab {
+                    parent_columns: parent_columns.clone(),
+                    referred_columns: referred_columns.clone(),
+                    tab: Tab::from_table(
+                        main_table,
+                        Some(tab_name),
+                        all_tables,
+                    ),
+                }
+            })
+            .collect();
         Window {
             name: main_tab.name.to_string(),
             description: main_tab.description.to_owned(),
@@ -103,16 +171,16 @@ impl Window {
             one_one_tabs,
             has_many_tabs,
             indirect_tabs,
+            tree_tabs,
             is_view,
         }
     }
 
     pub fn has_column_name(&self, column_name: &ColumnName) -> bool {
         self.main_tab.has_column_name(column_name)
-            || self
-                .has_many_tabs
-                .iter()
-                .any(|tab| tab.has_column_name(column_name))
+            || self.has_many_tabs.iter().any(|has_many_tab| {
+                has_many_tab.tab.has_column_name(column_name)
+            })
             || self.indirect_tabs.iter().any(|indirect_tab| {
                 indirect_tab.tab.has_column_name(column_name)
             })
@@ -121,6 +189,27 @@ impl Window {
     pub fn table_name(&self) -> TableName {
         self.main_tab.table_name.clone()
     }
+
+    /// all the tabs of the window, starting with the main tab
+    pub(crate) fn tabs_mut(&mut self) -> Vec<&mut Tab> {
+        let mut tabs = vec![&mut self.main_tab];
+        tabs.extend(self.has_one_tabs.iter_mut());
+        tabs.extend(self.one_one_tabs.iter_mut());
+        tabs.extend(
+            self.has_many_tabs
+                .iter_mut()
+                .map(|has_many_tab| &mut has_many_tab.tab),
+        );
+        tabs.extend(
+            self.indirect_tabs
+                .iter_mut()
+                .map(|indirect_tab| &mut indirect_tab.tab),
+        );
+        tabs.extend(
+            self.tree_tabs.iter_mut().map(|tree_tab| &mut tree_tab.tab),
+        );
+        tabs
+    }
 }
 
 fn has_repeating_tab(
@@ -159,10 +248,15 @@ pub fn derive_all_windows(tables: &[Table]) -> Vec<Window> {
         if table_intel.is_window(&tables) {
             let one_one_tables: Vec<&Table> =
                 table_intel.get_one_one_tables(&tables);
-            let has_one_tables: Vec<&Table> =
-                table_intel.get_has_one_tables(&tables);
-            let has_many_tables: Vec<&Table> =
-                table_intel.get_has_many_tables(&tables);
+            let has_one_tables: Vec<HasOneTable> =
+                table_intel.get_has_one_relations(&tables);
+            // the records referring to the record of the same table
+            // are shown as a tree instead of a has_many tab
+            let has_many_tables: Vec<HasManyTable> = table_intel
+                .get_has_many_relations(&tables)
+                .into_iter()
+                .filter(|has_many| has_many.table.name != table.name)
+                .collect();
             let indirect_tables: Vec<IndirectTable> =
                 table_intel.get_indirect_tables(&tables);
             println!("window: {}", table.name.name);
@@ -0,0 +1,340 @@
+//! the windows derived from the tables can be adjusted
+//! with a declarative override file, one for each DB_URL.
+//! The tables are referred by their complete name `schema.table`
+//! or by the table name alone, ie:
+//!
+//! ```ron
+//! (
+//!     hidden_tables: ["public.schema_migrations"],
+//!     linker_tables: ["film_category"],
+//!     owned_tables: ["customer_detail"],
+//!     windows: [
+//!         (
+//!             table: "film",
+//!             name: Some("Movies"),
+//!             group: Some("catalog"),
+//!             tab_names: {"film_actor": "Cast"},
+//!             tab_order: ["inventory", "actor"],
+//!         ),
+//!     ],
+//! )
+//! ```
+//!
+//! Hiding a table only takes it out of the navigation: it has no window
+//! and no tab. It is not an access control, the records of a hidden table
+//! are still shown in the lookups and the has_one fields of the tables
+//! that refer to it.
+use crate::{
+    tab::{
+        HasManyTab,
+        IndirectTab,
+        Tab,
+        TreeTab,
+    },
+    table_intel,
+    window::Window,
+};
+use rustorm::{
+    Table,
+    TableName,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::collections::BTreeMap;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct WindowOverrides {
+    /// these tables have no window and are not shown in the tabs of the other windows,
+    /// their records are still shown in the lookups of the tables referring to them
+    pub hidden_tables: Vec<String>,
+    /// these tables are treated as linkers of the 2 tables they refer to,
+    /// shown as indirect tabs instead of having their own window
+    pub linker_tables: Vec<String>,
+    /// these tables are treated as owned by the table they refer to,
+    /// shown as one_one tabs instead of has_many tabs
+    pub owned_tables: Vec<String>,
+    pub windows: Vec<WindowOverride>,
+}
+
+/// the adjustments to the window of this table
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct WindowOverride {
+    /// the main table of the window
+    pub table: String,
+    pub name: Option<String>,
+    pub group: Option<String>,
+    /// the new names of the tabs, keyed by the derived tab name
+    pub tab_names: BTreeMap<String, String>,
+    /// the derived names of the related tabs in the order they are shown,
+    /// the tabs not listed here come after them in their original order
+    pub tab_order: Vec<String>,
+}
+
+/// the table name is either the complete name or just the name of the table
+fn is_table(name: &str, table_name: &TableName) -> bool {
+    name == table_name.complete_name() || name == table_name.name
+}
+
+fn is_listed(names: &[String], table_name: &TableName) -> bool {
+    names.iter().any(|name| is_table(name, table_name))
+}
+
+/// apply the overrides over the windows derived from the tables
+pub fn apply_overrides(
+    mut windows: Vec<Window>,
+    overrides: &WindowOverrides,
+    tables: &[Table],
+) -> Vec<Window> {
+    for table in tables.iter() {
+        if is_listed(&overrides.linker_tables, &table.name) {
+            force_linker(&mut windows, table, tables);
+        }
+        if is_listed(&overrides.owned_tables, &table.name) {
+            force_owned(&mut windows, table, tables);
+        }
+    }
+    let hidden = &overrides.hidden_tables;
+    windows.retain(|window| !is_listed(hidden, &window.main_tab.table_name));
+    for window in windows.iter_mut() {
+        remove_hidden_tabs(window, hidden);
+        if let Some(window_override) = overrides
+            .windows
+            .iter()
+            .find(|w| is_table(&w.table, &window.main_tab.table_name))
+        {
+            apply_window_override(window, window_override);
+        }
+    }
+    windows
+}
+
+/// the distinct tables referred by the foreign keys of this table
+fn get_referred_tables<'t>(
+    table: &Table,
+    tables: &'t [Table],
+) -> Vec<&'t Table> {
+    let mut referred_tables: Vec<&Table> = vec![];
+    for fk in table.get_foreign_keys() {
+        if let Some(referred) =
+            table_intel::get_table(&fk.foreign_table, tables)
+        {
+            if !referred_tables.contains(&referred) {
+                referred_tables.push(referred);
+            }
+        }
+    }
+    referred_tables
+}
+
+/// the linker has no window of its own, the 2 tables it refers to
+/// show each other as indirect tabs instead of showing the linker as has_many
+fn force_linker(windows: &mut Vec<Window>, linker: &Table, tables: &[Table]) {
+    let referred_tables = get_referred_tables(linker, tables);
+    if referred_tables.len() != 2 {
+        println!(
+            "{} can not be a linker, it must refer to 2 tables",
+            linker.name.complete_name()
+        );
+        return;
+    }
+    windows.retain(|window| window.main_tab.table_name != linker.name);
+    for window in windows.iter_mut() {
+        let main_table_name = &window.main_tab.table_name;
+        let indirect_table = if *main_table_name == referred_tables[0].name {
+            referred_tables[1]
+        } else if *main_table_name == referred_tables[1].name {
+            referred_tables[0]
+        } else {
+            continue;
+        };
+        window
+            .has_many_tabs
+            .retain(|has_many| has_many.tab.table_name != linker.name);
+        if window
+            .indirect_tabs
+            .iter()
+            .any(|indirect| indirect.linker == linker.name)
+        {
+            continue;
+        }
+        let is_repeated = window
+            .indirect_tabs
+            .iter()
+            .any(|indirect| indirect.tab.table_name == indirect_table.name);
+        let tab_name = if is_repeated {
+            Some(format!(
+                "{} (via {})",
+                indirect_table.name.name, linker.name.name
+            ))
+        } else {
+            None
+        };
+        window.indirect_tabs.push(IndirectTab::new(
+            linker,
+            Tab::from_table(indirect_table, tab_name, tables),
+        ));
+    }
+}
+
+/// the owned table is edited together with the record it refers to
+fn force_owned(windows: &mut Vec<Window>, owned: &Table, tables: &[Table]) {
+    let referred_tables = get_referred_tables(owned, tables);
+    if referred_tables.len() != 1 {
+        println!(
+            "{} can not be owned, it must refer to 1 table",
+            owned.name.complete_name()
+        );
+        return;
+    }
+    let owner = &referred_tables[0].name;
+    for window in windows.iter_mut() {
+        if window.main_tab.table_name != *owner {
+            continue;
+        }
+        window
+            .has_many_tabs
+            .retain(|has_many| has_many.tab.table_name != owned.name);
+        if !window
+            .one_one_tabs
+            .iter()
+            .any(|tab| tab.table_name == owned.name)
+        {
+            window
+                .one_one_tabs
+                .push(Tab::from_table(owned, None, tables));
+        }
+    }
+}
+
+fn remove_hidden_tabs(window: &mut Window, hidden: &[String]) {
+    window
+        .has_one_tabs
+        .retain(|tab| !is_listed(hidden, &tab.table_name));
+    window
+        .one_one_tabs
+        .retain(|tab| !is_listed(hidden, &tab.table_name));
+    window
+        .has_many_tabs
+        .retain(|has_many| !is_listed(hidden, &has_many.tab.table_name));
+    window.indirect_tabs.retain(|indirect| {
+        !is_listed(hidden, &indirect.tab.table_name)
+            && !is_listed(hidden, &indirect.linker)
+    });
+}
+
+fn apply_window_override(
+    window: &mut Window,
+    window_override: &WindowOverride,
+) {
+    if let Some(name) = &window_override.name {
+        window.name = name.to_string();
+    }
+    if let Some(group) = &window_override.group {
+        window.group = Some(group.to_string());
+    }
+    // the tabs are ordered first, since the order refers to the derived names
+    let order = &window_override.tab_order;
+    let names = &window_override.tab_names;
+    order_and_rename_tabs(&mut window.has_one_tabs, order, names);
+    order_and_rename_tabs(&mut window.one_one_tabs, order, names);
+    order_and_rename_tabs(&mut window.has_many_tabs, order, names);
+    order_and_rename_tabs(&mut window.indirect_tabs, order, names);
+    order_and_rename_tabs(&mut window.tree_tabs, order, names);
+}
+
+/// the tabs of the related tables which wraps the tab
+trait RelatedTab {
+    fn tab(&self) -> &Tab;
+    fn tab_mut(&mut self) -> &mut Tab;
+}
+
+impl RelatedTab for Tab {
+    fn tab(&self) -> &Tab {
+        self
+    }
+
+    fn tab_mut(&mut self) -> &mut Tab {
+        self
+    }
+}
+
+impl RelatedTab for HasManyTab {
+    fn tab(&self) -> &Tab {
+        &self.tab
+    }
+
+    fn tab_mut(&mut self) -> &mut Tab {
+        &mut self.tab
+    }
+}
+
+impl RelatedTab for IndirectTab {
+    fn tab(&self) -> &Tab {
+        &self.tab
+    }
+
+    fn tab_mut(&mut self) -> &mut Tab {
+        &mut self.tab
+    }
+}
+
+impl RelatedTab for TreeTab {
+    fn tab(&self) -> &Tab {
+        &self.tab
+    }
+
+    fn tab_mut(&mut self) -> &mut Tab {
+        &mut self.tab
+    }
+}
+
+fn order_and_rename_tabs<T: RelatedTab>(
+    tabs: &mut Vec<T>,
+    order: &[String],
+    names: &BTreeMap<String, String>,
+) {
+    tabs.sort_by_key(|tab| {
+        order
+            .iter()
+            .position(|ordered| *ordered == tab.tab().name)
+            .unwrap_or(order.len())
+    });
+    for tab in tabs.iter_mut() {
+        let tab = tab.tab_mut();
+        if let Some(new_name) = names.get(&tab.name) {
+            tab.name = new_name.to_string();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tab(name: &str) -> Tab {
+        Tab {
+            name: name.to_string(),
+            description: None,
+            table_name: TableName::from(name),
+            fields: vec![],
+            is_view: false,
+            display: None,
+        }
+    }
+
+    #[test]
+    fn order_and_rename() {
+        let mut tabs = vec![tab("rental"), tab("payment"), tab("inventory")];
+        let order = vec!["inventory".to_string(), "rental".to_string()];
+        let mut names = BTreeMap::new();
+        names.insert("rental".to_string(), "Rentals".to_string());
+        order_and_rename_tabs(&mut tabs, &order, &names);
+        let tab_names: Vec<&str> =
+            tabs.iter().map(|tab| tab.name.as_str()).collect();
+        assert_eq!(tab_names, vec!["inventory", "Rentals", "payment"]);
+    }
+}
@@ -11,8 +11,32 @@ use actix_web::{
     HttpResponse,
 };
 use diwata_intel::{
+    audit,
+    data_container::{
+        CascadeImpact,
+        Lookup,
+        Modified,
+        PageRequest,
+        QueryResult,
+        RecordAction,
+        RecordChangeset,
+        SaveContainer,
+        Sort,
+        TreeRecords,
+    },
+    data_modify,
     data_read,
+    filter::Filter,
+    lookup,
+    media,
+    quick_find,
+    validation::{
+        self,
+        FieldError,
+    },
+    ColumnName,
     Dao,
+    Rows,
     TableName,
 };
 use futures::future::Future;
@@ -86,7 +110,21 @@ pub fn sql(
 
         let mut dm = global::get_pool_dm().expect("must get dm");
         let mut em = global::get_pool_em().expect("must get em");
-        data_read::execute_sql_query(&context, &mut em, &mut dm, &sql_param.sql)
+        let result = data_read::execute_sql_query(
+            &context,
+            &mut em,
+            &mut dm,
+            &sql_param.sql,
+        );
+        let is_select = sql_param
+            .sql
+            .trim_start()
+            .to_lowercase()
+            .starts_with("select");
+        if !is_select {
+            global::clear_lookups().expect("unable to clear lookups");
+        }
+        result
     })
     .from_err()
     .then(move |rows| {
@@ -151,9 +189,102 @@ pub fn record_detail(
     })
 }
 
+#[derive(Debug, Deserialize)]
+pub struct RecordIdsParam {
+    record_ids: String,
+}
+
+/// preview the dependent rows affected by deleting these records,
+/// the record ids are sent as a ron list in the query string
+pub fn cascade_impact(
+    req: HttpRequest,
+    table_name_param: web::Path<String>,
+    record_ids_param: web::Query<RecordIdsParam>,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    require_credentials(&req).expect("Should have credentials");
+    let credentials: Result<Credentials, ServiceError> =
+        TryFrom::try_from(&req);
+
+    let credentials = credentials.ok();
+
+    web::block(move || -> Result<CascadeImpact, ServiceError> {
+        let context = session::create_context(credentials.clone())?;
+        let table_name = TableName::from(&table_name_param.to_string());
+        let record_ids: Vec<String> =
+            ron::de::from_str(&record_ids_param.record_ids)
+                .map_err(|e| ServiceError::GenericError(e.to_string()))?;
+        let (_em, mut dm) = session::get_em_dm(credentials)?;
+        let db_url = global::get_db_url()?;
+        let impact = data_read::get_cascade_impact(
+            &context,
+            &mut dm,
+            &db_url,
+            &table_name,
+            &record_ids,
+        )?;
+        Ok(impact)
+    })
+    .from_err()
+    .then(move |impact| {
+        match impact {
+            Ok(impact) => {
+                Ok(HttpResponse::Ok().body(
+                    ron::ser::to_string(&impact)
+                        .expect("unable to serialize to ron"),
+                ))
+            }
+            Err(e) => Err(e),
+        }
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MainDataParam {
+    /// the cursor of the page, in place of the page number
+    cursor: Option<String>,
+    filter: Option<String>,
+    sort: Option<String>,
+}
+
+/// the sort in the url, ie: `?sort=name.asc,created.desc`
+fn parse_sort(sort: &Option<String>) -> Result<Sort, ServiceError> {
+    match sort {
+        Some(sort) => Ok(sort.parse()?),
+        None => Ok(Sort::default()),
+    }
+}
+
+/// the cursor is sent as ron in the url, it takes the place
+/// of the page number when supplied
+fn parse_page(
+    page: usize,
+    cursor: &Option<String>,
+) -> Result<PageRequest, ServiceError> {
+    match cursor {
+        Some(cursor) => {
+            let cursor = ron::de::from_str(cursor)
+                .map_err(|e| ServiceError::GenericError(e.to_string()))?;
+            Ok(PageRequest::After(cursor))
+        }
+        None => Ok(PageRequest::Number(page)),
+    }
+}
+
+/// the filter is sent as ron in the url
+fn parse_filter(filter: &Option<String>) -> Result<Filter, ServiceError> {
+    match filter {
+        Some(filter) => {
+            ron::de::from_str(filter)
+                .map_err(|e| ServiceError::GenericError(e.to_string()))
+        }
+        None => Ok(Filter::default()),
+    }
+}
+
 pub fn main_data(
     req: HttpRequest,
     param: web::Path<(String, usize)>,
+    data_param: web::Query<MainDataParam>,
 ) -> impl Future<Item = HttpResponse, Error = Error> {
     require_credentials(&req).expect("Should have credentials");
     let credentials: Result<Credentials, ServiceError> =
@@ -161,21 +292,188 @@ pub fn main_data(
 
     let credentials = credentials.ok();
 
-    web::block(move || {
-        let context = session::create_context(credentials.clone())
-            .expect("unable to create context");
-        let (mut em, mut dm) =
-            crate::session::get_em_dm(credentials).expect("must not error");
+    web::block(move || -> Result<QueryResult, ServiceError> {
+        let context = session::create_context(credentials.clone())?;
+        let (mut em, mut dm) = crate::session::get_em_dm(credentials)?;
         let table_name = TableName::from(&param.0);
+        let filter = parse_filter(&data_param.filter)?;
+        let sort = parse_sort(&data_param.sort)?;
+        let page = parse_page(param.1, &data_param.cursor)?;
+        let db_url = global::get_db_url()?;
         let res = data_read::get_window_main_table_data(
             &context,
             &mut em,
             &mut dm,
+            &db_url,
             &table_name,
+            &filter,
+            &sort,
+            &page,
+            global::PAGE_SIZE,
+            global::get_estimate_count_threshold()?,
+        )?;
+        Ok(res)
+    })
+    .from_err()
+    .then(move |res| {
+        match res {
+            Ok(res) => {
+                Ok(HttpResponse::Ok().body(
+                    ron::ser::to_string(&res)
+                        .expect("unable to serialize to ron"),
+                ))
+            }
+            Err(e) => Err(e),
+        }
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct QuickFindParam {
+    search: String,
+}
+
+/// the rows of the window that matches the search typed in the quick find,
+/// the closest match of the identifier comes first
+pub fn quick_find(
+    req: HttpRequest,
+    param: web::Path<(String, usize)>,
+    quick_find_param: web::Query<QuickFindParam>,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    require_credentials(&req).expect("Should have credentials");
+    let credentials: Result<Credentials, ServiceError> =
+        TryFrom::try_from(&req);
+
+    let credentials = credentials.ok();
+
+    web::block(move || -> Result<QueryResult, ServiceError> {
+        let context = session::create_context(credentials.clone())?;
+        let (_em, mut dm) = crate::session::get_em_dm(credentials)?;
+        let db_url = global::get_db_url()?;
+        let table_name = TableName::from(&param.0);
+        let res = quick_find::quick_find(
+            &context,
+            &mut dm,
+            &db_url,
+            &table_name,
+            &quick_find_param.search,
             param.1,
             global::PAGE_SIZE,
-        );
-        res
+        )?;
+        Ok(res)
+    })
+    .from_err()
+    .then(move |res| {
+        match res {
+            Ok(res) => {
+                Ok(HttpResponse::Ok().body(
+                    ron::ser::to_string(&res)
+                        .expect("unable to serialize to ron"),
+                ))
+            }
+            Err(e) => Err(e),
+        }
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LookupParam {
+    search: Option<String>,
+}
+
+/// the page of options of a foreign key field,
+/// the primary key and display of the records of the source table
+pub fn lookup(
+    req: HttpRequest,
+    param: web::Path<(String, usize)>,
+    lookup_param: web::Query<LookupParam>,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    require_credentials(&req).expect("Should have credentials");
+    let credentials: Result<Credentials, ServiceError> =
+        TryFrom::try_from(&req);
+
+    let credentials = credentials.ok();
+
+    web::block(move || -> Result<Lookup, ServiceError> {
+        let context = session::create_context(credentials.clone())?;
+        let (_em, mut dm) = crate::session::get_em_dm(credentials)?;
+        let db_url = global::get_user_db_url()?;
+        let table_name = TableName::from(&param.0);
+        let res = lookup::get_lookup(
+            &context,
+            &mut dm,
+            &db_url,
+            &table_name,
+            lookup_param.search.as_ref().map(String::as_str),
+            param.1,
+            global::PAGE_SIZE,
+        )?;
+        Ok(res)
+    })
+    .from_err()
+    .then(move |res| {
+        match res {
+            Ok(res) => {
+                Ok(HttpResponse::Ok().body(
+                    ron::ser::to_string(&res)
+                        .expect("unable to serialize to ron"),
+                ))
+            }
+            Err(e) => Err(e),
+        }
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TreeParam {
+    dao: Option<String>,
+}
+
+impl TreeParam {
+    fn to_dao(&self) -> Result<Option<Dao>, ServiceError> {
+        match &self.dao {
+            Some(dao) => {
+                ron::de::from_str(dao)
+                    .map(Some)
+                    .map_err(|e| ServiceError::GenericError(e.to_string()))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+/// the ancestors and the page of the children of the record in the hierarchy
+/// of the table formed by the comma separated parent columns, the roots are
+/// listed when there is no record, the record is sent as ron dao in the query string
+pub fn tree_records(
+    req: HttpRequest,
+    param: web::Path<(String, String, usize)>,
+    tree_param: web::Query<TreeParam>,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    require_credentials(&req).expect("Should have credentials");
+    let credentials: Result<Credentials, ServiceError> =
+        TryFrom::try_from(&req);
+
+    let credentials = credentials.ok();
+
+    web::block(move || -> Result<TreeRecords, ServiceError> {
+        let context = session::create_context(credentials.clone())?;
+        let (_em, mut dm) = session::get_em_dm(credentials)?;
+        let db_url = global::get_db_url()?;
+        let parent_columns: Vec<ColumnName> =
+            param.1.split(',').map(ColumnName::from).collect();
+        let record = tree_param.to_dao()?;
+        let res = data_read::fetch_tree_records(
+            &context,
+            &mut dm,
+            &db_url,
+            &TableName::from(&param.0),
+            &parent_columns,
+            record.as_ref(),
+            param.2,
+            global::PAGE_SIZE,
+        )?;
+        Ok(res)
     })
     .from_err()
     .then(move |res| {
@@ -190,3 +488,457 @@ pub fn main_data(
         }
     })
 }
+
+#[derive(Debug, Deserialize)]
+pub struct DetailTabParam {
+    dao: String,
+    filter: Option<String>,
+    sort: Option<String>,
+    /// the comma separated columns of the has_many table
+    /// referring to the record
+    via: Option<String>,
+}
+
+impl DetailTabParam {
+    fn to_dao(&self) -> Result<Dao, ServiceError> {
+        ron::de::from_str(&self.dao)
+            .map_err(|e| ServiceError::GenericError(e.to_string()))
+    }
+
+    fn to_via_columns(&self) -> Result<Vec<ColumnName>, ServiceError> {
+        match &self.via {
+            Some(via) => Ok(via.split(',').map(ColumnName::from).collect()),
+            None => {
+                Err(ServiceError::GenericError(
+                    "missing the referring columns".to_string(),
+                ))
+            }
+        }
+    }
+}
+
+/// the page of the has_many records referring to the selected record
+/// through the via columns,
+/// the selected record is sent as ron dao in the query string
+pub fn has_many_records(
+    req: HttpRequest,
+    param: web::Path<(String, String, usize)>,
+    tab_param: web::Query<DetailTabParam>,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    require_credentials(&req).expect("Should have credentials");
+    let credentials: Result<Credentials, ServiceError> =
+        TryFrom::try_from(&req);
+
+    let credentials = credentials.ok();
+
+    web::block(move || -> Result<Rows, ServiceError> {
+        let context = session::create_context(credentials.clone())?;
+        let (mut em, mut dm) = session::get_em_dm(credentials)?;
+        let rows = data_read::fetch_has_many_records(
+            &context,
+            &mut em,
+            &mut dm,
+            &TableName::from(&param.0),
+            &tab_param.to_dao()?,
+            &TableName::from(&param.1),
+            &tab_param.to_via_columns()?,
+            &parse_filter(&tab_param.filter)?,
+            &parse_sort(&tab_param.sort)?,
+            param.2,
+            global::PAGE_SIZE,
+        )?;
+        Ok(rows)
+    })
+    .from_err()
+    .then(move |rows| {
+        match rows {
+            Ok(rows) => {
+                Ok(HttpResponse::Ok().body(
+                    ron::ser::to_string(&rows)
+                        .expect("unable to serialize to ron"),
+                ))
+            }
+            Err(e) => Err(e),
+        }
+    })
+}
+
+/// the page of the indirect records of the selected record linked
+/// through the linker table,
+/// the selected record is sent as ron dao in the query string
+pub fn indirect_records(
+    req: HttpRequest,
+    param: web::Path<(String, String, String, usize)>,
+    tab_param: web::Query<DetailTabParam>,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    require_credentials(&req).expect("Should have credentials");
+    let credentials: Result<Credentials, ServiceError> =
+        TryFrom::try_from(&req);
+
+    let credentials = credentials.ok();
+
+    web::block(move || -> Result<Rows, ServiceError> {
+        let context = session::create_context(credentials.clone())?;
+        let (mut em, mut dm) = session::get_em_dm(credentials)?;
+        let rows = data_read::fetch_indirect_records(
+            &context,
+            &mut em,
+            &mut dm,
+            &TableName::from(&param.0),
+            &tab_param.to_dao()?,
+            &TableName::from(&param.1),
+            &TableName::from(&param.2),
+            &parse_filter(&tab_param.filter)?,
+            &parse_sort(&tab_param.sort)?,
+            param.3,
+            global::PAGE_SIZE,
+        )?;
+        Ok(rows)
+    })
+    .from_err()
+    .then(move |rows| {
+        match rows {
+            Ok(rows) => {
+                Ok(HttpResponse::Ok().body(
+                    ron::ser::to_string(&rows)
+                        .expect("unable to serialize to ron"),
+                ))
+            }
+            Err(e) => Err(e),
+        }
+    })
+}
+
+/// save the changeset of a record in the window of this table,
+/// the changeset is posted as ron in the request body
+pub fn record_changeset(
+    req: HttpRequest,
+    table_name_param: web::Path<String>,
+    body: String,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    require_credentials(&req).expect("Should have credentials");
+    let credentials: Result<Credentials, ServiceError> =
+        TryFrom::try_from(&req);
+
+    let credentials = credentials.ok();
+
+    web::block(move || -> Result<Modified<Dao>, ServiceError> {
+        let context = session::create_context(credentials.clone())?;
+        let table_name = TableName::from(&table_name_param.to_string());
+        let changeset: RecordChangeset = ron::de::from_str(&body)
+            .map_err(|e| ServiceError::GenericError(e.to_string()))?;
+        let window = context
+            .get_window(&table_name)
+            .ok_or(ServiceError::NotFound)?;
+        let table = context
+            .get_table(&table_name)
+            .ok_or(ServiceError::NotFound)?;
+        let username = session::get_username(&credentials);
+        let (_em, mut dm) = session::get_em_dm(credentials)?;
+        let db_url = global::get_db_url()?;
+        let (record, journal) = data_modify::save_changeset(
+            &context, &mut dm, &db_url, window, table, &changeset,
+        )?;
+        global::write_audit_log(&context, &username, &journal);
+        global::clear_lookups()?;
+        let undo_id = global::store_undo(&username, journal)?;
+        Ok(Modified {
+            result: record,
+            undo_id,
+        })
+    })
+    .from_err()
+    .then(move |record| {
+        match record {
+            Ok(record) => {
+                Ok(HttpResponse::Ok().body(
+                    ron::ser::to_string(&record)
+                        .expect("unable to serialize to ron"),
+                ))
+            }
+            Err(e) => Err(e),
+        }
+    })
+}
+
+/// check the record of the changeset before it is saved,
+/// returns the errors of each of the fields
+pub fn validate_record(
+    req: HttpRequest,
+    table_name_param: web::Path<String>,
+    body: String,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    require_credentials(&req).expect("Should have credentials");
+    let credentials: Result<Credentials, ServiceError> =
+        TryFrom::try_from(&req);
+
+    let credentials = credentials.ok();
+
+    web::block(move || -> Result<Vec<FieldError>, ServiceError> {
+        let context = session::create_context(credentials.clone())?;
+        let table_name = TableName::from(&table_name_param.to_string());
+        let changeset: RecordChangeset = ron::de::from_str(&body)
+            .map_err(|e| ServiceError::GenericError(e.to_string()))?;
+        let table = context
+            .get_table(&table_name)
+            .ok_or(ServiceError::NotFound)?;
+        let (_em, mut dm) = session::get_em_dm(credentials)?;
+        let db_url = global::get_db_url()?;
+        let is_new = changeset.action == RecordAction::CreateNew;
+        let errors = validation::get_record_errors(
+            &mut dm,
+            &db_url,
+            table,
+            &changeset.record,
+            is_new,
+        )?;
+        Ok(errors)
+    })
+    .from_err()
+    .then(move |errors| {
+        match errors {
+            Ok(errors) => {
+                Ok(HttpResponse::Ok().body(
+                    ron::ser::to_string(&errors)
+                        .expect("unable to serialize to ron"),
+                ))
+            }
+            Err(e) => Err(e),
+        }
+    })
+}
+
+/// insert and update the rows of a tab,
+/// the container is put as ron in the request body
+pub fn tab_changeset(
+    req: HttpRequest,
+    body: String,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    require_credentials(&req).expect("Should have credentials");
+    let credentials: Result<Credentials, ServiceError> =
+        TryFrom::try_from(&req);
+
+    let credentials = credentials.ok();
+
+    web::block(move || -> Result<Modified<Vec<Dao>>, ServiceError> {
+        let context = session::create_context(credentials.clone())?;
+        let container: SaveContainer = ron::de::from_str(&body)
+            .map_err(|e| ServiceError::GenericError(e.to_string()))?;
+        let username = session::get_username(&credentials);
+        let (_em, mut dm) = session::get_em_dm(credentials)?;
+        let db_url = global::get_db_url()?;
+        let (records, journal) = data_modify::save_container(
+            &context, &mut dm, &db_url, &container,
+        )?;
+        global::write_audit_log(&context, &username, &journal);
+        global::clear_lookups()?;
+        let undo_id = global::store_undo(&username, journal)?;
+        Ok(Modified {
+            result: records,
+            undo_id,
+        })
+    })
+    .from_err()
+    .then(move |records| {
+        match records {
+            Ok(records) => {
+                Ok(HttpResponse::Ok().body(
+                    ron::ser::to_string(&records)
+                        .expect("unable to serialize to ron"),
+                ))
+            }
+            Err(e) => Err(e),
+        }
+    })
+}
+
+/// delete the records of this table,
+/// the record ids are sent as a ron list in the request body
+pub fn delete_records(
+    req: HttpRequest,
+    table_name_param: web::Path<String>,
+    body: String,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    require_credentials(&req).expect("Should have credentials");
+    let credentials: Result<Credentials, ServiceError> =
+        TryFrom::try_from(&req);
+
+    let credentials = credentials.ok();
+
+    web::block(move || -> Result<Modified<Rows>, ServiceError> {
+        let context = session::create_context(credentials.clone())?;
+        let table_name = TableName::from(&table_name_param.to_string());
+        let record_ids: Vec<String> = ron::de::from_str(&body)
+            .map_err(|e| ServiceError::GenericError(e.to_string()))?;
+        let table = context
+            .get_table(&table_name)
+            .ok_or(ServiceError::NotFound)?;
+        let username = session::get_username(&credentials);
+        let (_em, mut dm) = session::get_em_dm(credentials)?;
+        let db_url = global::get_db_url()?;
+        let (rows, journal) = data_modify::delete_records(
+            &context,
+            &mut dm,
+            &db_url,
+            table,
+            &record_ids,
+        )?;
+        global::write_audit_log(&context, &username, &journal);
+        global::clear_lookups()?;
+        let undo_id = global::store_undo(&username, journal)?;
+        Ok(Modified {
+            result: rows,
+            undo_id,
+        })
+    })
+    .from_err()
+    .then(move |rows| {
+        match rows {
+            Ok(rows) => {
+                Ok(HttpResponse::Ok().body(
+                    ron::ser::to_string(&rows)
+                        .expect("unable to serialize to ron"),
+                ))
+            }
+            Err(e) => Err(e),
+        }
+    })
+}
+
+/// revert the changes of a save or delete
+/// if it is still within the grace period
+pub fn undo(
+    req: HttpRequest,
+    undo_id: web::Path<String>,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    require_credentials(&req).expect("Should have credentials");
+    let credentials: Result<Credentials, ServiceError> =
+        TryFrom::try_from(&req);
+
+    let credentials = credentials.ok();
+
+    web::block(move || -> Result<Vec<(TableName, Dao)>, ServiceError> {
+        let context = session::create_context(credentials.clone())?;
+        let username = session::get_username(&credentials);
+        let journal =
+            global::get_undo(&username, &undo_id)?.ok_or_else(|| {
+                ServiceError::GenericError(
+                    "nothing to undo or the grace period has passed"
+                        .to_string(),
+                )
+            })?;
+        let (_em, mut dm) = session::get_em_dm(credentials)?;
+        let db_url = global::get_db_url()?;
+        let (restored, undo_journal) =
+            data_modify::undo_journal(&context, &mut dm, &db_url, &journal)?;
+        global::remove_undo(&undo_id)?;
+        global::write_audit_log(&context, &username, &undo_journal);
+        global::clear_lookups()?;
+        Ok(restored)
+    })
+    .from_err()
+    .then(move |restored| {
+        match restored {
+            Ok(restored) => {
+                Ok(HttpResponse::Ok().body(
+                    ron::ser::to_string(&restored)
+                        .expect("unable to serialize to ron"),
+                ))
+            }
+            Err(e) => Err(e),
+        }
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuditLogParam {
+    record_id: Option<String>,
+}
+
+/// browse the audit log of the changes done to this table,
+/// limited to one record when the record_id is in the query string
+pub fn audit_log(
+    req: HttpRequest,
+    param: web::Path<(String, usize)>,
+    audit_log_param: web::Query<AuditLogParam>,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    require_credentials(&req).expect("Should have credentials");
+    let credentials: Result<Credentials, ServiceError> =
+        TryFrom::try_from(&req);
+
+    let credentials = credentials.ok();
+
+    web::block(move || -> Result<QueryResult, ServiceError> {
+        let table_name = TableName::from(&param.0);
+        let (_em, mut dm) = session::get_em_dm(credentials)?;
+        let db_url = global::get_db_url()?;
+        if !audit::can_read_table(&mut dm, &db_url, &table_name)? {
+            return Err(ServiceError::NotFound);
+        }
+        let mut audit_em = global::get_pool_audit_em()?;
+        let mut audit_dm = global::get_pool_audit_dm()?;
+        let audit_log = audit::get_audit_log(
+            &mut audit_em,
+            &mut audit_dm,
+            &table_name,
+            audit_log_param.record_id.as_ref().map(String::as_str),
+            param.1,
+            global::PAGE_SIZE,
+        )?;
+        Ok(audit_log)
+    })
+    .from_err()
+    .then(move |audit_log| {
+        match audit_log {
+            Ok(audit_log) => {
+                Ok(HttpResponse::Ok().body(
+                    ron::ser::to_string(&audit_log)
+                        .expect("unable to serialize to ron"),
+                ))
+            }
+            Err(e) => Err(e),
+        }
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MediaParam {
+    record_id: String,
+}
+
+/// serve the bytes of the binary column of the record
+/// with the mime type detected from the bytes
+pub fn media(
+    req: HttpRequest,
+    param: web::Path<(String, String)>,
+    media_param: web::Query<MediaParam>,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    require_credentials(&req).expect("Should have credentials");
+    let credentials: Result<Credentials, ServiceError> =
+        TryFrom::try_from(&req);
+
+    let credentials = credentials.ok();
+
+    web::block(move || -> Result<(String, Vec<u8>), ServiceError> {
+        let context = session::create_context(credentials.clone())?;
+        let table_name = TableName::from(&param.0);
+        let (_em, mut dm) = session::get_em_dm(credentials)?;
+        let media = media::get_media(
+            &context,
+            &mut dm,
+            &table_name,
+            &param.1,
+            &media_param.record_id,
+        )?;
+        Ok(media)
+    })
+    .from_err()
+    .then(move |media| {
+        match media {
+            Ok((mime, bytes)) => {
+                Ok(HttpResponse::Ok().content_type(mime).body(bytes))
+            }
+            Err(e) => Err(e),
+        }
+    })
+}
@@ -1,15 +1,30 @@
 use crate::error::ServiceError;
-use diwata_intel::cache;
+use diwata_intel::{
+    audit,
+    cache::{
+        self,
+        CachePool,
+    },
+    journal::Journal,
+    undo,
+    window_override::WindowOverrides,
+    Context,
+};
 use lazy_static::lazy_static;
+use log::*;
 use rustorm::{
     pool,
     DaoManager,
     EntityManager,
     Pool,
 };
-use std::sync::{
-    Arc,
-    RwLock,
+use std::{
+    fs,
+    sync::{
+        Arc,
+        RwLock,
+    },
+    time::Duration,
 };
 use url::Url;
 
@@ -25,6 +40,16 @@ lazy_static! {
     // all the connection pool, key is the db_url
     pub static ref POOL: Arc<RwLock<Pool>> = { Arc::new(RwLock::new(Pool::new())) };
     pub static ref LOGIN_REQUIRED: RwLock<bool> = RwLock::new(false);
+    // the db url of the separate database where the audit log is written,
+    // nothing is audited when it is not set
+    pub static ref AUDIT_DB_URL: RwLock<Option<String>> = RwLock::new(None);
+    // the row count of the tables with more rows than this is estimated
+    pub static ref ESTIMATE_COUNT_THRESHOLD: RwLock<Option<usize>> =
+        RwLock::new(None);
+    // applied over the windows derived on the configured db url
+    // and on the db url of each role
+    pub static ref WINDOW_OVERRIDES: RwLock<Option<WindowOverrides>> =
+        RwLock::new(None);
 }
 
 fn get_db_url_value() -> Result<Option<String>, ServiceError> {
@@ -113,19 +138,225 @@ pub fn get_session_db_url() -> Result<String, ServiceError> {
     }
 }
 
+/// the db_url of the session user when login is required,
+/// the records visible to each user may differ
+pub fn get_user_db_url() -> Result<String, ServiceError> {
+    if is_login_required()? {
+        get_session_db_url()
+    } else {
+        get_db_url_internal()
+    }
+}
+
+/// the lookup options may have changed once the records are modified
+pub fn clear_lookups() -> Result<(), ServiceError> {
+    match cache::CACHE_POOL.lock() {
+        Ok(mut cache_pool) => {
+            cache_pool.clear_lookups();
+            Ok(())
+        }
+        Err(e) => Err(ServiceError::GenericError(format!("{}", e))),
+    }
+}
+
 /// precache the processing of tables, and window in advance
 pub fn precache() -> Result<(), ServiceError> {
     match cache::CACHE_POOL.lock() {
         Ok(mut cache_pool) => {
             let mut em = get_pool_em()?;
+            let mut dm = get_pool_dm()?;
             let db_url = get_db_url()?;
-            cache_pool.precache(&mut em, &db_url)?;
+            set_cached_window_overrides(&mut cache_pool, &db_url)?;
+            cache_pool.precache(&mut em, &mut dm, &db_url)?;
+            Ok(())
+        }
+        Err(e) => Err(ServiceError::GenericError(format!("{}", e))),
+    }
+}
+
+/// read the overrides of the derived windows from the RON file
+pub fn load_window_overrides(path: &str) -> Result<(), ServiceError> {
+    let content = fs::read_to_string(path).map_err(|e| {
+        ServiceError::GenericError(format!("unable to read {}: {}", path, e))
+    })?;
+    let overrides: WindowOverrides =
+        ron::de::from_str(&content).map_err(|e| {
+            ServiceError::GenericError(format!(
+                "unable to parse {}: {}",
+                path, e
+            ))
+        })?;
+    match WINDOW_OVERRIDES.write() {
+        Ok(mut window_overrides) => {
+            *window_overrides = Some(overrides);
+            Ok(())
+        }
+        Err(e) => Err(ServiceError::GenericError(format!("{}", e))),
+    }
+}
+
+/// set the loaded window overrides to the cache of this db_url,
+/// the windows cached on every db_url are derived with the same overrides
+pub fn set_cached_window_overrides(
+    cache_pool: &mut CachePool,
+    db_url: &str,
+) -> Result<(), ServiceError> {
+    if cache_pool.get_window_overrides(db_url).is_some() {
+        return Ok(());
+    }
+    match WINDOW_OVERRIDES.read() {
+        Ok(window_overrides) => {
+            if let Some(ref overrides) = *window_overrides {
+                cache_pool.set_window_overrides(db_url, overrides.clone());
+            }
+            Ok(())
+        }
+        Err(e) => Err(ServiceError::GenericError(format!("{}", e))),
+    }
+}
+
+/// sets how long the modifications can be undone
+pub fn set_undo_grace_period(
+    grace_period: Duration,
+) -> Result<(), ServiceError> {
+    match undo::UNDO_POOL.lock() {
+        Ok(mut undo_pool) => {
+            undo_pool.set_grace_period(grace_period);
+            Ok(())
+        }
+        Err(e) => Err(ServiceError::GenericError(format!("{}", e))),
+    }
+}
+
+/// estimate the row count of the tables which has more rows than this
+/// instead of counting all of the rows
+pub fn set_estimate_count_threshold(
+    threshold: usize,
+) -> Result<(), ServiceError> {
+    match ESTIMATE_COUNT_THRESHOLD.write() {
+        Ok(mut estimate_count_threshold) => {
+            *estimate_count_threshold = Some(threshold);
             Ok(())
         }
         Err(e) => Err(ServiceError::GenericError(format!("{}", e))),
     }
 }
 
+pub fn get_estimate_count_threshold() -> Result<Option<usize>, ServiceError> {
+    match ESTIMATE_COUNT_THRESHOLD.read() {
+        Ok(threshold) => Ok(*threshold),
+        Err(e) => Err(ServiceError::GenericError(format!("{}", e))),
+    }
+}
+
+/// keep the journal of the modification of this user for undo,
+/// returns the undo id
+pub fn store_undo(
+    username: &Option<String>,
+    journal: Journal,
+) -> Result<Option<String>, ServiceError> {
+    match undo::UNDO_POOL.lock() {
+        Ok(mut undo_pool) => {
+            let db_url = get_db_url()?;
+            let username = username.as_ref().map(String::as_str);
+            Ok(undo_pool.store(&db_url, username, journal))
+        }
+        Err(e) => Err(ServiceError::GenericError(format!("{}", e))),
+    }
+}
+
+/// the journal of this undo id made by this user,
+/// returns None if the grace period has passed
+pub fn get_undo(
+    username: &Option<String>,
+    undo_id: &str,
+) -> Result<Option<Journal>, ServiceError> {
+    match undo::UNDO_POOL.lock() {
+        Ok(mut undo_pool) => {
+            let db_url = get_db_url()?;
+            let username = username.as_ref().map(String::as_str);
+            Ok(undo_pool.get(&db_url, username, undo_id))
+        }
+        Err(e) => Err(ServiceError::GenericError(format!("{}", e))),
+    }
+}
+
+/// the undo is committed, it can not be done again
+pub fn remove_undo(undo_id: &str) -> Result<(), ServiceError> {
+    match undo::UNDO_POOL.lock() {
+        Ok(mut undo_pool) => {
+            undo_pool.remove(undo_id);
+            Ok(())
+        }
+        Err(e) => Err(ServiceError::GenericError(format!("{}", e))),
+    }
+}
+
+pub fn set_audit_db_url(new_url: &str) -> Result<(), ServiceError> {
+    match AUDIT_DB_URL.write() {
+        Ok(mut db_url) => {
+            *db_url = Some(new_url.to_string());
+            Ok(())
+        }
+        Err(e) => Err(ServiceError::GenericError(format!("{}", e))),
+    }
+}
+
+pub fn is_audited() -> Result<bool, ServiceError> {
+    match AUDIT_DB_URL.read() {
+        Ok(db_url) => Ok(db_url.is_some()),
+        Err(e) => Err(ServiceError::GenericError(format!("{}", e))),
+    }
+}
+
+fn get_audit_db_url() -> Result<String, ServiceError> {
+    match AUDIT_DB_URL.read() {
+        Ok(db_url) => {
+            match *db_url {
+                Some(ref db_url) => Ok(db_url.to_owned()),
+                None => {
+                    Err(ServiceError::GenericError(
+                        "AUDIT_DB_URL is not set".to_string(),
+                    ))
+                }
+            }
+        }
+        Err(e) => Err(ServiceError::GenericError(format!("{}", e))),
+    }
+}
+
+/// create the audit log table in the audit database if it is not there yet
+pub fn prepare_audit_log() -> Result<(), ServiceError> {
+    if !is_audited()? {
+        return Ok(());
+    }
+    let mut audit_dm = get_pool_audit_dm()?;
+    audit::ensure_audit_table(&mut audit_dm)?;
+    Ok(())
+}
+
+/// write the journal of the modification to the audit log,
+/// the modification is already committed at this point
+/// so a failure to write the audit log is only logged
+pub fn write_audit_log(
+    context: &Context,
+    username: &Option<String>,
+    journal: &Journal,
+) {
+    let username = username.as_ref().map(String::as_str);
+    let written = is_audited().and_then(|is_audited| {
+        if !is_audited {
+            return Ok(());
+        }
+        let mut audit_dm = get_pool_audit_dm()?;
+        audit::write_journal(&mut audit_dm, context, username, journal)?;
+        Ok(())
+    });
+    if let Err(e) = written {
+        error!("Unable to write the audit log: {}", e);
+    }
+}
+
 pub fn set_db_url(new_url: &str) -> Result<(), ServiceError> {
     match DB_URL.write() {
         Ok(mut db_url) => {
@@ -253,6 +484,30 @@ pub fn get_pool_session_dm() -> Result<DaoManager, ServiceError> {
     }
 }
 
+pub fn get_pool_audit_em() -> Result<EntityManager, ServiceError> {
+    let mut pool = match POOL.write() {
+        Ok(pool) => pool,
+        Err(_e) => return Err(ServiceError::PoolResourceError),
+    };
+    let db_url = &get_audit_db_url()?;
+    match pool.em(db_url) {
+        Ok(em) => Ok(em),
+        Err(e) => Err(ServiceError::DbError(e)),
+    }
+}
+
+pub fn get_pool_audit_dm() -> Result<DaoManager, ServiceError> {
+    let mut pool = match POOL.write() {
+        Ok(pool) => pool,
+        Err(_e) => return Err(ServiceError::PoolResourceError),
+    };
+    let db_url = &get_audit_db_url()?;
+    match pool.dm(db_url) {
+        Ok(em) => Ok(em),
+        Err(e) => Err(ServiceError::DbError(e)),
+    }
+}
+
 pub fn is_login_required() -> Result<bool, ServiceError> {
     match LOGIN_REQUIRED.read() {
         Ok(required) => Ok(*required),
@@ -9,6 +9,7 @@ pub use global::{
 use std::{
     env,
     io,
+    time::Duration,
 };
 
 use actix_files as fs;
@@ -44,6 +45,33 @@ pub fn start() -> io::Result<()> {
     let port = env::var("PORT").expect("PORT must be set");
     println!("PORT: {}", port);
     global::set_db_url(&database_url).expect("unable to set global db_url");
+    if let Ok(grace_period) = env::var("UNDO_GRACE_PERIOD") {
+        let seconds: u64 = grace_period
+            .parse()
+            .expect("UNDO_GRACE_PERIOD must be in seconds");
+        println!("UNDO_GRACE_PERIOD: {}", seconds);
+        global::set_undo_grace_period(Duration::from_secs(seconds))
+            .expect("unable to set undo grace period");
+    }
+    if let Ok(audit_db_url) = env::var("AUDIT_DB_URL") {
+        println!("AUDIT_DB_URL: {}", audit_db_url);
+        global::set_audit_db_url(&audit_db_url)
+            .expect("unable to set audit db_url");
+    }
+    if let Ok(threshold) = env::var("ESTIMATE_COUNT_THRESHOLD") {
+        let threshold: usize = threshold
+            .parse()
+            .expect("ESTIMATE_COUNT_THRESHOLD must be a number of rows");
+        println!("ESTIMATE_COUNT_THRESHOLD: {}", threshold);
+        global::set_estimate_count_threshold(threshold)
+            .expect("unable to set estimate count threshold");
+    }
+    if let Ok(window_overrides) = env::var("WINDOW_OVERRIDES") {
+        println!("WINDOW_OVERRIDES: {}", window_overrides);
+        global::load_window_overrides(&window_overrides)
+            .expect("unable to load the window overrides");
+    }
+    global::prepare_audit_log().expect("unable to prepare the audit log");
     global::precache().expect("unable to precache");
     let app = move || {
         let error_handlers = ErrorHandlers::new()
@@ -72,10 +100,68 @@ pub fn start() -> io::Result<()> {
                 web::resource("/main_data/{table_name}/page/{page}")
                     .route(web::get().to_async(api::main_data)),
             )
+            .service(
+                web::resource("/quick_find/{table_name}/page/{page}")
+                    .route(web::get().to_async(api::quick_find)),
+            )
+            .service(
+                web::resource("/lookup/{table_name}/page/{page}")
+                    .route(web::get().to_async(api::lookup)),
+            )
             .service(
                 web::resource("/record_detail/{table_name}/")
                     .route(web::get().to_async(api::record_detail)),
             )
+            .service(
+                web::resource(
+                    "/has_many_records/{table_name}/{has_many_table}/page/{page}",
+                )
+                .route(web::get().to_async(api::has_many_records)),
+            )
+            .service(
+                web::resource(
+                    "/indirect_records/{table_name}/{linker_table}/{indirect_table}/page/{page}",
+                )
+                .route(web::get().to_async(api::indirect_records)),
+            )
+            .service(
+                web::resource(
+                    "/tree_records/{table_name}/{parent_columns}/page/{page}",
+                )
+                .route(web::get().to_async(api::tree_records)),
+            )
+            .service(
+                web::resource("/record_changeset/{table_name}/")
+                    .route(web::post().to_async(api::record_changeset)),
+            )
+            .service(
+                web::resource("/validate_record/{table_name}/")
+                    .route(web::post().to_async(api::validate_record)),
+            )
+            .service(
+                web::resource("/tab_changeset/")
+                    .route(web::put().to_async(api::tab_changeset)),
+            )
+            .service(
+                web::resource("/cascade_impact/{table_name}/")
+                    .route(web::get().to_async(api::cascade_impact)),
+            )
+            .service(
+                web::resource("/delete_records/{table_name}/")
+                    .route(web::delete().to_async(api::delete_records)),
+            )
+            .service(
+                web::resource("/undo/{undo_id}/")
+                    .route(web::post().to_async(api::undo)),
+            )
+            .service(
+                web::resource("/audit_log/{table_name}/page/{page}")
+                    .route(web::get().to_async(api::audit_log)),
+            )
+            .service(
+                web::resource("/media/{table_name}/{column_name}/")
+                    .route(web::get().to_async(api::media)),
+            )
             .service(fs::Files::new("/webapp", "crates/webapp/"))
     };
 
@@ -46,12 +46,16 @@ fn get_index_html(
     dm: &mut DaoManager,
     table_name: Option<TableName>,
 ) -> String {
+    let db_url = global::get_db_url().expect("unable to get db_url");
     let app_data = data_read::retrieve_app_data(
         context,
         em,
         dm,
+        &db_url,
         table_name,
         global::PAGE_SIZE,
+        global::get_estimate_count_threshold()
+            .expect("unable to get estimate count threshold"),
     )
     .expect("there should be app data");
     let app_data_serialized =
@@ -5,10 +5,6 @@ use crate::{
 };
 use diwata_intel::{
     cache,
-    table_intel::{
-        self,
-        TableIntel,
-    },
     window::{
         GroupedWindow,
         WindowName,
@@ -48,6 +44,13 @@ pub fn get_em_dm(
     Ok((active_em, active_dm))
 }
 
+/// the name of the user doing the request, used in the audit log
+pub fn get_username(credentials: &Option<Credentials>) -> Option<String> {
+    credentials
+        .as_ref()
+        .map(|credentials| credentials.username.to_string())
+}
+
 pub fn create_context(
     credentials: Option<Credentials>,
 ) -> Result<Context, ServiceError> {
@@ -55,47 +58,74 @@ pub fn create_context(
     let mut cache_pool = cache::CACHE_POOL.lock().unwrap();
 
     let db_url = global::get_db_url()?;
+    global::set_cached_window_overrides(&mut cache_pool, &db_url)?;
 
-    let windows = cache_pool.get_cached_windows(&mut active_em, &db_url)?;
-    let tables = cache_pool.get_cached_tables(&mut active_em, &db_url)?;
-    let grouped_window = get_grouped_windows(&mut active_em, &tables)?;
+    let windows = cache_pool.get_cached_windows(
+        &mut active_em,
+        &mut active_dm,
+        &db_url,
+    )?;
+    let tables = cache_pool.get_cached_tables(
+        &mut active_em,
+        &mut active_dm,
+        &db_url,
+    )?;
+    let grouped_window = get_grouped_windows(&mut active_em, &windows)?;
     Ok(Context {
         tables: to_hashmap_tables(tables),
         windows: to_hashmap_windows(windows),
         grouped_window,
+        identifiers: cache_pool.get_cached_identifiers(&db_url),
+        media: cache_pool.get_cached_media(&db_url),
     })
 }
 
 /// get all the schema content and convert to grouped window
 /// for displaying as a list in the client side
-/// filter out tablenames that are not window
+/// filter out tablenames that has no window,
+/// the window is listed in its own group when it is regrouped
 fn get_grouped_windows(
     em: &mut EntityManager,
-    tables: &[Table],
+    windows: &[Window],
 ) -> Result<Vec<GroupedWindow>, ServiceError> {
     let schema_content: Vec<SchemaContent> = em.get_grouped_tables()?;
     let mut grouped_windows: Vec<GroupedWindow> =
         Vec::with_capacity(schema_content.len());
     for sc in schema_content {
-        let mut window_names =
-            Vec::with_capacity(sc.tablenames.len() + sc.views.len());
         for table_name in sc.tablenames.iter().chain(sc.views.iter()) {
-            let table = table_intel::get_table(&table_name, tables);
-            if let Some(table) = table {
-                let table_intel = TableIntel(table);
-                if table_intel.is_window(tables) {
-                    window_names.push(WindowName {
-                        name: table_name.name.to_string(),
-                        table_name: table_name.to_owned(),
-                        is_view: table.is_view,
-                    })
+            let window = windows
+                .iter()
+                .find(|window| window.main_tab.table_name == *table_name);
+            if let Some(window) = window {
+                let group = match window.group {
+                    Some(ref group) => group.to_string(),
+                    None => sc.schema.to_string(),
+                };
+                let window_name = WindowName {
+                    name: window.name.to_string(),
+                    table_name: table_name.to_owned(),
+                    is_view: window.is_view,
+                };
+                match grouped_windows.iter_mut().find(|g| g.group == group) {
+                    Some(grouped_window) => {
+                        grouped_window.window_names.push(window_name)
+                    }
+                    None => {
+                        grouped_windows.push(GroupedWindow {
+                            group,
+                            window_names: vec![window_name],
+                        })
+                    }
                 }
             }
         }
-        grouped_windows.push(GroupedWindow {
-            group: sc.schema.to_string(),
-            window_names,
-        });
+        // the schema is still listed even if it has no windows
+        if !grouped_windows.iter().any(|g| g.group == sc.schema) {
+            grouped_windows.push(GroupedWindow {
+                group: sc.schema.to_string(),
+                window_names: vec![],
+            });
+        }
     }
     Ok(grouped_windows)
 }
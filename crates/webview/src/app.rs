@@ -1,8 +1,8 @@
 use crate::rest_api;
 use diwata_intel::{
-    data_container::{AppData, QueryResult, WindowData},
+    data_container::{AppData, QueryResult, TreeRecords, WindowData},
     window::GroupedWindow,
-    RecordDetail,
+    RecordDetail, Rows,
 };
 use sauron::{
     html::{attributes::*, events::*, *},
@@ -22,6 +22,7 @@ mod row_view;
 mod tab_view;
 mod table_view;
 mod toolbar_view;
+mod tree_view;
 mod window_list_view;
 mod window_view;
 
@@ -40,7 +41,15 @@ pub enum Msg {
     ReceivedWindowQueryResult(usize, Result<QueryResult, JsValue>),
     ReceivedWindowData(Result<QueryResult, JsValue>),
     ReceivedWindowDataNextPage(usize, usize, Result<QueryResult, JsValue>),
+    ReceivedSortedWindowData(usize, Result<QueryResult, JsValue>),
     ReceivedWindowMainTabDetail(usize, usize, usize, Result<RecordDetail, JsValue>),
+    /// window_index, tab_index, page, rows
+    ReceivedHasManyPage(usize, usize, usize, Result<Rows, JsValue>),
+    ReceivedIndirectPage(usize, usize, usize, Result<Rows, JsValue>),
+    /// window_index, tree_index, the records around the selected record
+    ReceivedTreeRecords(usize, usize, Result<TreeRecords, JsValue>),
+    /// window_index, tree_index, the path of the expanded node, its children
+    ReceivedTreeChildren(usize, usize, Vec<usize>, Result<TreeRecords, JsValue>),
 }
 
 pub struct App {
@@ -118,6 +127,102 @@ impl App {
         self.activate_window(0);
     }
 
+    /// fetch the page of the main tab of the window, matching the quick find
+    /// if there is, otherwise matching the filter in the sort of the columns
+    fn fetch_main_tab_page<F>(&self, window_index: usize, page: usize, msg_receiver: F) -> Cmd
+    where
+        F: Fn(Result<QueryResult, JsValue>) -> Msg + Clone + 'static,
+    {
+        let window_view = &self.window_views[window_index];
+        let main_tab = &window_view.main_tab;
+        // the first page is always fetched by page number
+        let cursor = if page > 1 {
+            self.window_data[window_index].main_tab_cursor.as_ref()
+        } else {
+            None
+        };
+        match window_view.quick_find_search() {
+            Some(search) => {
+                rest_api::fetch_quick_find(&main_tab.table_name, &search, page, msg_receiver)
+            }
+            None => rest_api::fetch_window_data_next_page(
+                &main_tab.table_name,
+                page,
+                cursor,
+                &main_tab.table_view.get_filter(),
+                &main_tab.table_view.get_sort(),
+                msg_receiver,
+            ),
+        }
+    }
+
+    /// fetch the page of the has_many tab of the record in the detail view
+    fn fetch_has_many_page(&self, window_index: usize, tab_index: usize, page: usize) -> Cmd {
+        let record_detail = match &self.window_data[window_index].record_detail {
+            Some(record_detail) => record_detail,
+            None => return Cmd::none(),
+        };
+        let window_view = &self.window_views[window_index];
+        let (referring_columns, has_many_tab) = window_view.has_many_tab(tab_index);
+        rest_api::fetch_has_many_records(
+            &window_view.main_tab.table_name,
+            &record_detail.primary_dao(),
+            &has_many_tab.table_name,
+            referring_columns,
+            page,
+            &has_many_tab.table_view.get_filter(),
+            &has_many_tab.table_view.get_sort(),
+            move |rows| Msg::ReceivedHasManyPage(window_index, tab_index, page, rows),
+        )
+    }
+
+    /// fetch the page of the indirect tab of the record in the detail view
+    fn fetch_indirect_page(&self, window_index: usize, tab_index: usize, page: usize) -> Cmd {
+        let record_detail = match &self.window_data[window_index].record_detail {
+            Some(record_detail) => record_detail,
+            None => return Cmd::none(),
+        };
+        let window_view = &self.window_views[window_index];
+        let (linker, indirect_tab) = window_view.indirect_tab(tab_index);
+        rest_api::fetch_indirect_records(
+            &window_view.main_tab.table_name,
+            &record_detail.primary_dao(),
+            linker,
+            &indirect_tab.table_name,
+            page,
+            &indirect_tab.table_view.get_filter(),
+            &indirect_tab.table_view.get_sort(),
+            move |rows| Msg::ReceivedIndirectPage(window_index, tab_index, page, rows),
+        )
+    }
+
+    /// fetch the ancestors and the children of the record in the detail view
+    /// for each of the hierarchies of the window
+    fn fetch_tree_records(&self, window_index: usize) -> Cmd {
+        let record_detail = match &self.window_data[window_index].record_detail {
+            Some(record_detail) => record_detail,
+            None => return Cmd::none(),
+        };
+        let dao = record_detail.primary_dao();
+        let window_view = &self.window_views[window_index];
+        Cmd::batch(
+            (0..window_view.tree_tab_count())
+                .map(|tree_index| {
+                    let tree_tab = window_view.tree_tab(tree_index);
+                    rest_api::fetch_tree_records(
+                        &tree_tab.table_name,
+                        &tree_tab.parent_columns,
+                        Some(&dao),
+                        1,
+                        move |tree_records| {
+                            Msg::ReceivedTreeRecords(window_index, tree_index, tree_records)
+                        },
+                    )
+                })
+                .collect(),
+        )
+    }
+
     fn setup_window_resize_listener(&self) -> Cmd {
         Browser::onresize(Msg::BrowserResized)
     }
@@ -184,6 +289,42 @@ impl Component<Msg> for App {
                     Cmd::none()
                 }
             }
+            Msg::WindowMsg(index, window_view::Msg::ToolbarMsg(toolbar_view::Msg::ShowHistory)) => {
+                let table_name = &self.window_views[index].main_tab.table_name;
+                rest_api::fetch_audit_log(table_name, Msg::ReceivedWindowData)
+            }
+            Msg::WindowMsg(
+                index,
+                window_view::Msg::ToolbarMsg(toolbar_msg @ toolbar_view::Msg::ChangeQuickFind(_)),
+            ) => {
+                self.window_views[index].update(window_view::Msg::ToolbarMsg(toolbar_msg));
+                self.fetch_main_tab_page(index, 1, move |query_result| {
+                    Msg::ReceivedSortedWindowData(index, query_result)
+                })
+            }
+            Msg::WindowMsg(
+                window_index,
+                window_view::Msg::MainTabMsg(tab_view::Msg::TableMsg(table_view::Msg::ColumnMsg(
+                    column_index,
+                    column_msg @ column_view::Msg::ToggleSort,
+                ))),
+            )
+            | Msg::WindowMsg(
+                window_index,
+                window_view::Msg::MainTabMsg(tab_view::Msg::TableMsg(table_view::Msg::ColumnMsg(
+                    column_index,
+                    column_msg @ column_view::Msg::ChangeSearch(_),
+                ))),
+            ) => {
+                let main_tab = &mut self.window_views[window_index].main_tab;
+                main_tab.update(tab_view::Msg::TableMsg(table_view::Msg::ColumnMsg(
+                    column_index,
+                    column_msg,
+                )));
+                self.fetch_main_tab_page(window_index, 1, move |query_result| {
+                    Msg::ReceivedSortedWindowData(window_index, query_result)
+                })
+            }
             Msg::WindowMsg(window_index, window_view::Msg::MainTabMsg(tab_msg)) => {
                 let main_tab = &mut self.window_views[window_index].main_tab;
                 let main_tab_current_page = self.window_data[window_index].main_tab_current_page;
@@ -199,17 +340,81 @@ impl Component<Msg> for App {
                         "---->>> is a page request in flight: {}",
                         self.is_page_request_in_flight
                     );
-                    rest_api::fetch_window_data_next_page(
-                        &main_tab.table_name,
-                        next_page,
-                        move |query_result| {
-                            Msg::ReceivedWindowDataNextPage(window_index, next_page, query_result)
-                        },
-                    )
+                    self.fetch_main_tab_page(window_index, next_page, move |query_result| {
+                        Msg::ReceivedWindowDataNextPage(window_index, next_page, query_result)
+                    })
+                } else {
+                    Cmd::none()
+                }
+            }
+            Msg::WindowMsg(window_index, window_view::Msg::HasManyTabMsg(tab_index, tab_msg)) => {
+                let is_refetch = is_sort_or_search(&tab_msg);
+                let window_view = &mut self.window_views[window_index];
+                window_view.update(window_view::Msg::HasManyTabMsg(tab_index, tab_msg));
+                if is_refetch {
+                    self.fetch_has_many_page(window_index, tab_index, 1)
+                } else if window_view.has_many_tab(tab_index).1.need_next_page()
+                    && !self.is_page_request_in_flight
+                {
+                    self.is_page_request_in_flight = true;
+                    let next_page =
+                        self.window_data[window_index].has_many_tab_current_page[tab_index] + 1;
+                    self.fetch_has_many_page(window_index, tab_index, next_page)
+                } else {
+                    Cmd::none()
+                }
+            }
+            Msg::WindowMsg(
+                window_index,
+                window_view::Msg::IndirectTabMsg(tab_index, (table_name, tab_msg)),
+            ) => {
+                let is_refetch = is_sort_or_search(&tab_msg);
+                let window_view = &mut self.window_views[window_index];
+                window_view.update(window_view::Msg::IndirectTabMsg(
+                    tab_index,
+                    (table_name, tab_msg),
+                ));
+                if is_refetch {
+                    self.fetch_indirect_page(window_index, tab_index, 1)
+                } else if window_view.indirect_tab(tab_index).1.need_next_page()
+                    && !self.is_page_request_in_flight
+                {
+                    self.is_page_request_in_flight = true;
+                    let next_page =
+                        self.window_data[window_index].indirect_tab_current_page[tab_index] + 1;
+                    self.fetch_indirect_page(window_index, tab_index, next_page)
                 } else {
                     Cmd::none()
                 }
             }
+            Msg::WindowMsg(
+                window_index,
+                window_view::Msg::TreeTabMsg(tree_index, tree_view::Msg::ToggleNode(path)),
+            ) => {
+                let window_view = &mut self.window_views[window_index];
+                window_view.update(window_view::Msg::TreeTabMsg(
+                    tree_index,
+                    tree_view::Msg::ToggleNode(path.clone()),
+                ));
+                let tree_tab = window_view.tree_tab(tree_index);
+                match tree_tab.children_to_fetch(&path) {
+                    Some(dao) => rest_api::fetch_tree_records(
+                        &tree_tab.table_name,
+                        &tree_tab.parent_columns,
+                        Some(&dao),
+                        1,
+                        move |tree_records| {
+                            Msg::ReceivedTreeChildren(
+                                window_index,
+                                tree_index,
+                                path.clone(),
+                                tree_records,
+                            )
+                        },
+                    ),
+                    None => Cmd::none(),
+                }
+            }
             Msg::WindowMsg(index, window_msg) => self.window_views[index].update(window_msg),
             Msg::BrowserResized(width, height) => {
                 trace!("Browser is resized to: {}, {}", width, height);
@@ -259,6 +464,7 @@ impl Component<Msg> for App {
                                 format!("SELECT * FROM {}", window.table_name().complete_name());
                             let mut window_data = WindowData::from_rows(query_result.rows);
                             window_data.sql_query = Some(sql_query.to_string());
+                            window_data.main_tab_cursor = query_result.cursor;
                             let new_window = WindowView::new(
                                 window_clone,
                                 &window_data,
@@ -287,6 +493,7 @@ impl Component<Msg> for App {
                 let window_data = &mut self.window_data[window_index];
                 window_data.add_main_data_page(query_result.rows);
                 window_data.main_tab_current_page = page;
+                window_data.main_tab_cursor = query_result.cursor;
                 self.window_views[window_index].set_window_data(window_data);
                 self.is_page_request_in_flight = false;
                 Cmd::none()
@@ -295,6 +502,18 @@ impl Component<Msg> for App {
                 trace!("Error retrieving next page {}", page);
                 Cmd::none()
             }
+            Msg::ReceivedSortedWindowData(window_index, Ok(query_result)) => {
+                // keep the window view, so the sorted columns and searches stays
+                let mut window_data = WindowData::from_rows(query_result.rows);
+                window_data.main_tab_cursor = query_result.cursor;
+                self.window_views[window_index].set_window_data(&window_data);
+                self.window_data[window_index] = window_data;
+                Cmd::none()
+            }
+            Msg::ReceivedSortedWindowData(_window_index, Err(_e)) => {
+                trace!("Error retrieving sorted data");
+                Cmd::none()
+            }
 
             Msg::ReceivedWindowQueryResult(index, Ok(query_result)) => {
                 if let Some(window) = query_result.window {
@@ -340,7 +559,7 @@ impl Component<Msg> for App {
                 new_window.show_main_tab_detail_view(page_index, row_index);
                 new_window.update_size_allocation();
                 self.window_views[window_index] = new_window;
-                Cmd::none()
+                self.fetch_tree_records(window_index)
             }
             Msg::ReceivedWindowMainTabDetail(
                 _window_index,
@@ -351,6 +570,43 @@ impl Component<Msg> for App {
                 trace!("Error retrieveing window main tab detail..");
                 Cmd::none()
             }
+            Msg::ReceivedHasManyPage(window_index, tab_index, page, Ok(rows)) => {
+                let window_data = &mut self.window_data[window_index];
+                window_data.set_has_many_page(tab_index, page, rows);
+                self.window_views[window_index].set_window_data(window_data);
+                self.is_page_request_in_flight = false;
+                Cmd::none()
+            }
+            Msg::ReceivedIndirectPage(window_index, tab_index, page, Ok(rows)) => {
+                let window_data = &mut self.window_data[window_index];
+                window_data.set_indirect_page(tab_index, page, rows);
+                self.window_views[window_index].set_window_data(window_data);
+                self.is_page_request_in_flight = false;
+                Cmd::none()
+            }
+            Msg::ReceivedTreeRecords(window_index, tree_index, Ok(tree_records)) => {
+                self.window_views[window_index]
+                    .tree_tab_mut(tree_index)
+                    .set_tree_records(tree_records);
+                Cmd::none()
+            }
+            Msg::ReceivedTreeChildren(window_index, tree_index, path, Ok(tree_records)) => {
+                self.window_views[window_index]
+                    .tree_tab_mut(tree_index)
+                    .set_children(&path, tree_records.children);
+                Cmd::none()
+            }
+            Msg::ReceivedTreeRecords(_window_index, tree_index, Err(_e))
+            | Msg::ReceivedTreeChildren(_window_index, tree_index, _, Err(_e)) => {
+                trace!("Error retrieving the records of tree {}", tree_index);
+                Cmd::none()
+            }
+            Msg::ReceivedHasManyPage(_window_index, _tab_index, page, Err(_e))
+            | Msg::ReceivedIndirectPage(_window_index, _tab_index, page, Err(_e)) => {
+                trace!("Error retrieving page {} of the related records", page);
+                self.is_page_request_in_flight = false;
+                Cmd::none()
+            }
         }
     }
 
@@ -430,3 +686,16 @@ impl Component<Msg> for App {
         )
     }
 }
+
+/// the sort or the search of a column of the tab is changed,
+/// so its rows are fetched again from the first page
+fn is_sort_or_search(tab_msg: &tab_view::Msg) -> bool {
+    match tab_msg {
+        tab_view::Msg::TableMsg(table_view::Msg::ColumnMsg(_, column_view::Msg::ToggleSort))
+        | tab_view::Msg::TableMsg(table_view::Msg::ColumnMsg(
+            _,
+            column_view::Msg::ChangeSearch(_),
+        )) => true,
+        _ => false,
+    }
+}
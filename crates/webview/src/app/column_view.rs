@@ -1,5 +1,6 @@
 use crate::{assets, widgets};
 use data_table::DataColumn;
+use diwata_intel::data_container::Direction;
 use sauron::{
     html::{attributes::*, events::*, units::*, *},
     Cmd, Component, Node,
@@ -8,10 +9,15 @@ use sauron::{
 #[derive(Debug, PartialEq, Clone)]
 pub enum Msg {
     ChangeSearch(String),
+    ToggleSort,
 }
 
 pub struct ColumnView {
     pub column: DataColumn,
+    /// the rows are sorted by this column in this direction
+    pub sort: Option<Direction>,
+    /// the search typed in the column, parsed into the filter of the rows
+    pub search: String,
     width: i32,
     height: i32,
 }
@@ -20,6 +26,8 @@ impl ColumnView {
     pub fn new(column: DataColumn) -> Self {
         ColumnView {
             column,
+            sort: None,
+            search: String::new(),
             width: 210,
             height: 20,
         }
@@ -31,6 +39,15 @@ impl Component<Msg> for ColumnView {
         match msg {
             Msg::ChangeSearch(search) => {
                 trace!("Search term change: {}", search);
+                self.search = search;
+                Cmd::none()
+            }
+            Msg::ToggleSort => {
+                self.sort = match self.sort {
+                    None => Some(Direction::Asc),
+                    Some(Direction::Asc) => Some(Direction::Desc),
+                    Some(Direction::Desc) => None,
+                };
                 Cmd::none()
             }
         }
@@ -47,7 +64,17 @@ impl Component<Msg> for ColumnView {
                     ],
                     vec![
                         div(vec![class("column_name")], vec![text(&self.column.name)]),
-                        div(vec![], vec![assets::sort_btn_asc(18, 18, "#888")]),
+                        div(
+                            vec![
+                                class("sort_btn"),
+                                classes_flag(vec![
+                                    ("sorted", self.sort.is_some()),
+                                    ("desc", self.sort == Some(Direction::Desc)),
+                                ]),
+                                onclick(|_| Msg::ToggleSort),
+                            ],
+                            vec![assets::sort_btn_asc(18, 18, "#888")],
+                        ),
                     ],
                 ),
                 div(
@@ -8,6 +8,8 @@ use sauron::{
 #[derive(Debug, PartialEq, Clone)]
 pub enum Msg {
     TextChange(String),
+    /// the value of one of the columns of a compound field changed
+    CompoundChange(usize, String),
     PrimaryClicked,
 }
 
@@ -16,22 +18,58 @@ pub struct FieldView {
     pub column: DataColumn,
     pub value: Value,
     pub new_value: Value,
+    /// the values of the other columns of a composite foreign key,
+    /// edited together with the value of its first column
+    pub compound_values: Vec<Value>,
+    pub new_compound_values: Vec<Value>,
     /// is part of a frozen row, serves no
     /// other purposed other than coloring in css style
     pub is_frozen_row: bool,
     /// is part of a frozen column, serves no
     /// other puposed other than coloring in css style
     pub is_frozen_column: bool,
+    /// the validation error of the new value from the server
+    pub error: Option<String>,
+    /// the display of the record referred by this foreign key value,
+    /// ie: the name of the person instead of its id
+    pub display: Option<String>,
+    /// the uri of the image or file served by the media route,
+    /// the bytes of the binary columns are not in the value
+    pub media: Option<Value>,
 }
 
 impl FieldView {
-    pub fn new(value: &Value, column: &DataColumn) -> Self {
+    /// the values are the values of the columns of the field,
+    /// 2 or more for a compound field
+    pub fn new(
+        values: &[&Value],
+        column: &DataColumn,
+        display: Option<&Value>,
+        media: Option<&Value>,
+    ) -> Self {
+        let value = values
+            .first()
+            .map(|value| (*value).clone())
+            .unwrap_or(Value::Nil);
+        let compound_values: Vec<Value> = values
+            .iter()
+            .skip(1)
+            .map(|value| (*value).clone())
+            .collect();
         FieldView {
             new_value: value.clone(),
-            value: value.clone(),
+            value,
+            new_compound_values: compound_values.clone(),
+            compound_values,
             column: column.clone(),
             is_frozen_row: false,
             is_frozen_column: false,
+            error: None,
+            display: match display {
+                Some(Value::Text(display)) => Some(display.to_string()),
+                _ => None,
+            },
+            media: media.filter(|media| **media != Value::Nil).cloned(),
         }
     }
 
@@ -44,7 +82,22 @@ impl FieldView {
     }
 
     pub fn is_changed(&self) -> bool {
-        self.value != self.new_value
+        self.value != self.new_value || self.compound_values != self.new_compound_values
+    }
+
+    /// the columns of a composite foreign key are edited as one field
+    pub fn is_compound(&self) -> bool {
+        self.column.column_names.len() > 1
+    }
+
+    /// the value of each column of the field
+    pub fn column_values(&self) -> Vec<(&str, &Value)> {
+        self.column
+            .column_names
+            .iter()
+            .map(String::as_str)
+            .zip(std::iter::once(&self.value).chain(self.compound_values.iter()))
+            .collect()
     }
 
     pub fn set_is_frozen_row(&mut self, frozen: bool) {
@@ -83,6 +136,7 @@ impl FieldView {
             ("frozen_row", self.is_frozen_row),
             ("frozen_column", self.is_frozen_column),
             ("modified", self.is_changed()),
+            ("invalid", self.error.is_some()),
         ]);
         match &self.value {
             Value::Nil => match self.column.data_type {
@@ -247,6 +301,71 @@ impl FieldView {
         }
     }
 
+    /// the display of the referred record in place of the foreign key value,
+    /// the value is shown for editing once it is changed
+    fn view_display(&self, display: &str) -> Node<Msg> {
+        span(
+            vec![classes_flag(vec![
+                ("value", true),
+                ("lookup_display", true),
+                ("frozen_row", self.is_frozen_row),
+                ("frozen_column", self.is_frozen_column),
+            ])],
+            vec![text(display)],
+        )
+    }
+
+    /// the image or the link to the file, shown in place of the value
+    fn view_media(&self, media: &Value) -> Node<Msg> {
+        let classes = classes_flag(vec![
+            ("value", true),
+            ("media", true),
+            ("frozen_row", self.is_frozen_row),
+            ("frozen_column", self.is_frozen_column),
+        ]);
+        match media {
+            Value::ImageUri(uri) => img(vec![src(uri), classes], vec![]),
+            Value::Text(uri) => a(vec![href(uri), classes], vec![text("download")]),
+            _ => self.view_value(),
+        }
+    }
+
+    /// an input for each of the columns of the composite foreign key
+    fn view_compound(&self) -> Node<Msg> {
+        let new_values = std::iter::once(&self.new_value).chain(self.new_compound_values.iter());
+        span(
+            vec![classes_flag(vec![
+                ("compound", true),
+                ("frozen_row", self.is_frozen_row),
+                ("frozen_column", self.is_frozen_column),
+                ("modified", self.is_changed()),
+                ("invalid", self.error.is_some()),
+            ])],
+            self.column
+                .column_names
+                .iter()
+                .zip(new_values)
+                .enumerate()
+                .map(|(index, (column_name, new_value))| {
+                    let column_value = match new_value {
+                        Value::Nil => String::new(),
+                        new_value => new_value.to_string(),
+                    };
+                    input(
+                        vec![
+                            r#type("text"),
+                            class("value"),
+                            placeholder(column_name),
+                            value(column_value),
+                            onchange(move |input| Msg::CompoundChange(index, input.value)),
+                        ],
+                        vec![],
+                    )
+                })
+                .collect(),
+        )
+    }
+
     pub fn view_in_detail(&self) -> Node<Msg> {
         div(
             vec![
@@ -261,11 +380,16 @@ impl FieldView {
                     vec![class("in_detail_column")],
                     vec![text(&self.column.name)],
                 ),
-                if self.column.is_primary {
-                    self.view_value_as_primary()
-                } else {
-                    self.view_value()
+                match &self.media {
+                    _ if self.is_compound() => self.view_compound(),
+                    _ if self.column.is_primary => self.view_value_as_primary(),
+                    Some(media) if !self.is_changed() => self.view_media(media),
+                    _ => self.view_value(),
                 },
+                span(
+                    vec![class("field_error")],
+                    vec![text(self.error.as_ref().map(String::as_str).unwrap_or(""))],
+                ),
             ],
         )
     }
@@ -277,6 +401,19 @@ impl Component<Msg> for FieldView {
         match msg {
             Msg::TextChange(value) => {
                 self.new_value = Value::Text(value);
+                self.error = None;
+                Cmd::none()
+            }
+            Msg::CompoundChange(index, value) => {
+                match index {
+                    0 => self.new_value = Value::Text(value),
+                    index => {
+                        if let Some(new_value) = self.new_compound_values.get_mut(index - 1) {
+                            *new_value = Value::Text(value);
+                        }
+                    }
+                }
+                self.error = None;
                 Cmd::none()
             }
             Msg::PrimaryClicked => {
@@ -296,7 +433,12 @@ impl Component<Msg> for FieldView {
                     ("frozen_column", self.is_frozen_column),
                 ]),
             ],
-            vec![self.view_value()],
+            vec![match (&self.display, &self.media) {
+                (Some(display), _) if !self.is_changed() => self.view_display(display),
+                (_, Some(media)) if !self.is_changed() => self.view_media(media),
+                _ if self.is_compound() => self.view_compound(),
+                _ => self.view_value(),
+            }],
         )
     }
 }
@@ -1,4 +1,7 @@
-use crate::app::{self, column_view, row_view, row_view::RowView};
+use crate::app::{
+    self, column_view, row_view,
+    row_view::{RowView, ValueIndexes},
+};
 use data_table::DataColumn;
 use diwata_intel::{data_container::Page, DataRow, Field};
 use sauron::{
@@ -75,26 +78,42 @@ impl PageView {
             tags: vec![],
             data_type: field.get_data_type().clone(),
             is_primary: field.is_primary,
+            column_names: field.row_column_names(),
         }
     }
 
     pub fn set_page(&mut self, page: &Page, current_page: usize, total_rows: usize) {
         trace!("setting pages in page_view: {:#?}", page);
-        self.set_data_rows(&page.rows, current_page, total_rows);
+        let indexes: Vec<ValueIndexes> = self
+            .data_columns
+            .iter()
+            .map(|column| ValueIndexes {
+                values: column
+                    .column_names
+                    .iter()
+                    .filter_map(|column_name| page.column_index(column_name))
+                    .collect(),
+                display: page.display_index(column.first_column_name()),
+                media: page.media_index(column.first_column_name()),
+            })
+            .collect();
+        self.set_data_rows(&page.rows, &indexes, current_page, total_rows);
     }
 
-    /// replace all the data with a new data row
+    /// replace all the data with a new data row,
+    /// the indexes points to the values, the display and the media of each column
     /// TODO: also update the freeze_columns for each row_views
     pub fn set_data_rows(
         &mut self,
         data_row: &Vec<DataRow>,
+        indexes: &[ValueIndexes],
         current_page: usize,
         total_rows: usize,
     ) {
         self.row_views = data_row
             .into_iter()
             .enumerate()
-            .map(|(index, row)| RowView::new(index, row, &self.data_columns))
+            .map(|(index, row)| RowView::new(index, row, &self.data_columns, indexes))
             .collect();
         self.update_freeze_columns();
         self.total_rows = total_rows;
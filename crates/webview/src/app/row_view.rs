@@ -3,7 +3,7 @@ use crate::app::{
     field_view::{self, FieldView},
 };
 use data_table::DataColumn;
-use diwata_intel::{Dao, DataRow};
+use diwata_intel::{Dao, DataRow, Value};
 use sauron::{
     html::{attributes::*, events::*, units::*, *},
     Component, Node,
@@ -17,6 +17,14 @@ pub enum Msg {
     Click,
 }
 
+/// where the values of a column are in the data row
+pub struct ValueIndexes {
+    /// the values of the column, the columns of a composite foreign key have one each
+    pub values: Vec<usize>,
+    pub display: Option<usize>,
+    pub media: Option<usize>,
+}
+
 pub struct RowView {
     pub index: usize,
     pub fields: Vec<Rc<RefCell<FieldView>>>,
@@ -25,13 +33,29 @@ pub struct RowView {
 }
 
 impl RowView {
-    pub fn new(index: usize, data_rows: &DataRow, data_columns: &[DataColumn]) -> Self {
+    pub fn new(
+        index: usize,
+        data_rows: &DataRow,
+        data_columns: &[DataColumn],
+        indexes: &[ValueIndexes],
+    ) -> Self {
         RowView {
             index,
-            fields: data_rows
-                .into_iter()
-                .zip(data_columns.iter())
-                .map(|(value, column)| Rc::new(RefCell::new(FieldView::new(value, column))))
+            fields: data_columns
+                .iter()
+                .zip(indexes.iter())
+                .map(|(column, indexes)| {
+                    let values: Vec<&Value> = indexes
+                        .values
+                        .iter()
+                        .filter_map(|index| data_rows.get(*index))
+                        .collect();
+                    let display = indexes.display.and_then(|index| data_rows.get(index));
+                    let media = indexes.media.and_then(|index| data_rows.get(index));
+                    Rc::new(RefCell::new(FieldView::new(
+                        &values, column, display, media,
+                    )))
+                })
                 .collect(),
             frozen_fields: vec![],
             is_frozen: false,
@@ -50,9 +74,9 @@ impl RowView {
             .filter(|field| field.borrow().column.is_primary)
             .fold(Dao::new(), |mut dao, field_view| {
                 let field = field_view.borrow();
-                let column = &field.column.name;
-                let value = &field.value;
-                dao.insert_value(column, &value);
+                for (column, value) in field.column_values() {
+                    dao.insert_value(column, value);
+                }
                 dao
             })
     }
@@ -6,7 +6,11 @@ use crate::app::{
     row_view::RowView,
 };
 use data_table::DataColumn;
-use diwata_intel::{data_container::Page, Dao, Field, Tab, TableName};
+use diwata_intel::{
+    data_container::{Order, Page, Sort},
+    filter::{ColumnFilter, Filter},
+    ColumnName, Dao, Field, Tab, TableName,
+};
 use sauron::{
     html::{attributes::*, events::*, units::*, *},
     Component, Node,
@@ -28,6 +32,8 @@ pub struct TableView {
     /// Which columns of the rows are to be frozen on the left side of the table
     frozen_rows: Vec<(usize, Vec<usize>)>,
     frozen_columns: Vec<usize>,
+    /// the index of the sorted columns, in the order they are sorted
+    sorted_columns: Vec<usize>,
     pub scroll_top: i32,
     scroll_left: i32,
     allocated_width: i32,
@@ -52,6 +58,7 @@ impl TableView {
             page_views: vec![],
             frozen_rows: vec![],
             frozen_columns: vec![],
+            sorted_columns: vec![],
             scroll_top: 0,
             scroll_left: 0,
             allocated_width: 0,
@@ -103,6 +110,7 @@ impl TableView {
             tags: vec![],
             data_type: field.get_data_type().clone(),
             is_primary: field.is_primary,
+            column_names: field.row_column_names(),
         }
     }
 
@@ -331,6 +339,44 @@ impl TableView {
         )
     }
 
+    /// the sort of the rows from the sorted columns,
+    /// the column sorted first takes precedence
+    pub fn get_sort(&self) -> Sort {
+        let orders = self
+            .sorted_columns
+            .iter()
+            .filter_map(|index| {
+                let column_view = &self.column_views[*index];
+                column_view.sort.map(|direction| Order {
+                    column_name: ColumnName::from(column_view.column.first_column_name()),
+                    direction,
+                })
+            })
+            .collect();
+        Sort { orders }
+    }
+
+    /// the filter of the rows from the search of each column,
+    /// a search that can not be parsed is ignored
+    pub fn get_filter(&self) -> Filter {
+        let column_filters = self
+            .column_views
+            .iter()
+            .filter_map(|column_view| {
+                let column = &column_view.column;
+                let column_name = ColumnName::from(column.first_column_name());
+                match ColumnFilter::parse(&column_name, &column.data_type, &column_view.search) {
+                    Ok(column_filter) => column_filter,
+                    Err(e) => {
+                        trace!("Ignoring search of {}: {:?}", column.name, e);
+                        None
+                    }
+                }
+            })
+            .collect();
+        Filter { column_filters }
+    }
+
     pub fn need_next_page(&self) -> bool {
         self.is_scrolled_near_bottom()
     }
@@ -343,6 +389,10 @@ impl TableView {
             }
             Msg::ColumnMsg(column_index, column_msg) => {
                 self.column_views[column_index].update(column_msg);
+                self.sorted_columns.retain(|index| *index != column_index);
+                if self.column_views[column_index].sort.is_some() {
+                    self.sorted_columns.push(column_index);
+                }
                 app::Cmd::none()
             }
             Msg::Scrolled((scroll_top, scroll_left)) => {
@@ -12,6 +12,7 @@ pub enum Msg {
     ChangeQuickFind(String),
     QueryChanged(String),
     RunQuery,
+    ShowHistory,
 }
 
 pub struct ToolbarView {
@@ -19,7 +20,7 @@ pub struct ToolbarView {
     pub show_related_tabs: bool,
     allocated_width: i32,
     allocated_height: i32,
-    quick_find_search: String,
+    pub quick_find_search: String,
     pub sql_query: String,
     pub formatted_query: String,
 }
@@ -135,6 +136,9 @@ impl Component<Msg> for ToolbarView {
             Msg::RunQuery => {
                 trace!("Running sql_query: {}", self.sql_query);
             }
+            Msg::ShowHistory => {
+                trace!("Showing the audit log");
+            }
         }
         Cmd::none()
     }
@@ -157,6 +161,7 @@ impl Component<Msg> for ToolbarView {
                         button(vec![], vec![text("Filter more..")]),
                         button(vec![], vec![text("Sort..")]),
                         button(vec![], vec![text("Export")]),
+                        button(vec![onclick(|_| Msg::ShowHistory)], vec![text("History")]),
                         button(
                             vec![onclick(|_| Msg::ToggleShowQuery)],
                             vec![
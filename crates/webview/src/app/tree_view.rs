@@ -0,0 +1,188 @@
+use diwata_intel::{
+    data_container::{TreeRecords, TREE_DISPLAY_COLUMN, TREE_HAS_CHILDREN_COLUMN},
+    ColumnName, Dao, Rows, TableName, TreeTab, Value,
+};
+use sauron::{
+    html::{attributes::*, events::*, *},
+    Node,
+};
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Msg {
+    /// the indexes of the node and its parents, starting from the top nodes
+    ToggleNode(Vec<usize>),
+}
+
+/// the hierarchy of the records of a table that refers to itself,
+/// showing the ancestors of the record and the records under it
+/// which are fetched as they are expanded
+pub struct TreeView {
+    pub name: String,
+    pub table_name: TableName,
+    pub parent_columns: Vec<ColumnName>,
+    /// the path from the root to the parent of the record
+    ancestors: Vec<TreeNode>,
+    nodes: Vec<TreeNode>,
+}
+
+struct TreeNode {
+    /// the primary key of the record
+    dao: Dao,
+    display: String,
+    has_children: bool,
+    is_expanded: bool,
+    /// None when the children are not fetched yet
+    children: Option<Vec<TreeNode>>,
+}
+
+impl TreeNode {
+    fn from_dao(mut dao: Dao) -> Self {
+        let display = match dao.0.remove(TREE_DISPLAY_COLUMN) {
+            Some(Value::Text(display)) => display,
+            _ => String::new(),
+        };
+        let has_children = match dao.0.remove(TREE_HAS_CHILDREN_COLUMN) {
+            Some(Value::Bool(v)) => v,
+            // sqlite has no boolean
+            Some(Value::Int(v)) => v != 0,
+            Some(Value::Bigint(v)) => v != 0,
+            _ => false,
+        };
+        TreeNode {
+            dao,
+            display,
+            has_children,
+            is_expanded: false,
+            children: None,
+        }
+    }
+
+    fn from_rows(rows: Rows) -> Vec<TreeNode> {
+        rows.iter().map(TreeNode::from_dao).collect()
+    }
+
+    fn view(&self, path: Vec<usize>) -> Node<Msg> {
+        let toggle_path = path.clone();
+        li(
+            vec![class("tree_node")],
+            vec![
+                button(
+                    vec![
+                        classes_flag(vec![
+                            ("tree_node_toggle", true),
+                            ("leaf", !self.has_children),
+                        ]),
+                        onclick(move |_| Msg::ToggleNode(toggle_path.clone())),
+                    ],
+                    vec![text(if !self.has_children {
+                        "•"
+                    } else if self.is_expanded {
+                        "▾"
+                    } else {
+                        "▸"
+                    })],
+                ),
+                span(vec![class("tree_node_display")], vec![text(&self.display)]),
+                match &self.children {
+                    Some(children) if self.is_expanded => ul(
+                        vec![class("tree_nodes")],
+                        children
+                            .iter()
+                            .enumerate()
+                            .map(|(index, child)| {
+                                let mut child_path = path.clone();
+                                child_path.push(index);
+                                child.view(child_path)
+                            })
+                            .collect::<Vec<Node<Msg>>>(),
+                    ),
+                    _ => span(vec![], vec![]),
+                },
+            ],
+        )
+    }
+}
+
+impl TreeView {
+    pub fn new(tree_tab: TreeTab) -> Self {
+        TreeView {
+            name: tree_tab.tab.name,
+            table_name: tree_tab.tab.table_name,
+            parent_columns: tree_tab.parent_columns,
+            ancestors: vec![],
+            nodes: vec![],
+        }
+    }
+
+    pub fn update(&mut self, msg: Msg) {
+        match msg {
+            Msg::ToggleNode(path) => {
+                if let Some(node) = self.get_node_mut(&path) {
+                    node.is_expanded = !node.is_expanded;
+                }
+            }
+        }
+    }
+
+    /// the primary key of the expanded node which children are not fetched yet
+    pub fn children_to_fetch(&self, path: &[usize]) -> Option<Dao> {
+        self.get_node(path)
+            .filter(|node| node.has_children && node.is_expanded && node.children.is_none())
+            .map(|node| node.dao.clone())
+    }
+
+    /// set the ancestors and the children of the record
+    pub fn set_tree_records(&mut self, tree_records: TreeRecords) {
+        self.ancestors = TreeNode::from_rows(tree_records.ancestors);
+        self.nodes = TreeNode::from_rows(tree_records.children);
+    }
+
+    /// set the children of the node at this path
+    pub fn set_children(&mut self, path: &[usize], rows: Rows) {
+        if let Some(node) = self.get_node_mut(path) {
+            node.children = Some(TreeNode::from_rows(rows));
+        }
+    }
+
+    fn get_node(&self, path: &[usize]) -> Option<&TreeNode> {
+        let (first, rest) = path.split_first()?;
+        rest.iter()
+            .try_fold(self.nodes.get(*first)?, |node, index| {
+                node.children.as_ref()?.get(*index)
+            })
+    }
+
+    fn get_node_mut(&mut self, path: &[usize]) -> Option<&mut TreeNode> {
+        let (first, rest) = path.split_first()?;
+        let mut node = self.nodes.get_mut(*first)?;
+        for index in rest.iter() {
+            node = node.children.as_mut()?.get_mut(*index)?;
+        }
+        Some(node)
+    }
+
+    pub fn view(&self) -> Node<Msg> {
+        div(
+            vec![class("tree_view")],
+            vec![
+                nav(
+                    vec![class("tree_ancestors")],
+                    self.ancestors
+                        .iter()
+                        .map(|ancestor| {
+                            span(vec![class("tree_ancestor")], vec![text(&ancestor.display)])
+                        })
+                        .collect::<Vec<Node<Msg>>>(),
+                ),
+                ul(
+                    vec![class("tree_nodes")],
+                    self.nodes
+                        .iter()
+                        .enumerate()
+                        .map(|(index, node)| node.view(vec![index]))
+                        .collect::<Vec<Node<Msg>>>(),
+                ),
+            ],
+        )
+    }
+}
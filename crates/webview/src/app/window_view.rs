@@ -8,10 +8,11 @@ use crate::{
         self,
         tab_view::{self, TabView},
         toolbar_view::{self, ToolbarView},
+        tree_view::{self, TreeView},
     },
     assets,
 };
-use diwata_intel::{TableName, Window};
+use diwata_intel::{ColumnName, TableName, Window};
 
 use diwata_intel::data_container::WindowData;
 
@@ -19,8 +20,10 @@ pub struct WindowView {
     pub name: String,
     pub main_tab: TabView,
     one_one_tabs: Vec<TabView>,
-    has_many_tabs: Vec<TabView>,
+    /// the columns of the has_many table referring to the record, and its tab
+    has_many_tabs: Vec<(Vec<ColumnName>, TabView)>,
     indirect_tabs: Vec<(TableName, TabView)>,
+    tree_tabs: Vec<TreeView>,
     pub is_visible: bool,
     active_has_many_tab: Option<usize>,
     active_indirect_tab: Option<usize>,
@@ -35,6 +38,7 @@ pub enum Msg {
     OneOneTabMsg(usize, tab_view::Msg),
     HasManyTabMsg(usize, tab_view::Msg),
     IndirectTabMsg(usize, (TableName, tab_view::Msg)),
+    TreeTabMsg(usize, tree_view::Msg),
     ShowHasManyTab(usize),
     ShowIndirectTab(usize),
     BrowserResized(i32, i32),
@@ -55,13 +59,17 @@ impl WindowView {
                 app::Cmd::none()
             }
             Msg::HasManyTabMsg(index, tab_msg) => {
-                self.has_many_tabs[index].update(tab_msg);
+                self.has_many_tabs[index].1.update(tab_msg);
                 app::Cmd::none()
             }
             Msg::IndirectTabMsg(index, (_table_name, tab_msg)) => {
                 self.indirect_tabs[index].1.update(tab_msg);
                 app::Cmd::none()
             }
+            Msg::TreeTabMsg(index, tree_msg) => {
+                self.tree_tabs[index].update(tree_msg);
+                app::Cmd::none()
+            }
             Msg::ShowHasManyTab(index) => {
                 self.activate_has_many_tab(index);
                 app::Cmd::none()
@@ -147,6 +155,31 @@ impl WindowView {
                                         })
                                         .collect::<Vec<Node<Msg>>>(),
                                 ),
+                                section(
+                                    vec![
+                                        class("tree_tabs"),
+                                        styles_flag(vec![(
+                                            "display",
+                                            "none",
+                                            !self.in_detail_view(),
+                                        )]),
+                                    ],
+                                    self.tree_tabs
+                                        .iter()
+                                        .enumerate()
+                                        .map(|(index, tree)| {
+                                            details(
+                                                vec![class("tree_tab"), open(true)],
+                                                vec![
+                                                    html::summary(vec![], vec![text(&tree.name)]),
+                                                    tree.view().map_msg(move |tree_msg| {
+                                                        Msg::TreeTabMsg(index, tree_msg)
+                                                    }),
+                                                ],
+                                            )
+                                        })
+                                        .collect::<Vec<Node<Msg>>>(),
+                                ),
                             ],
                         ),
                         div(
@@ -173,7 +206,7 @@ impl WindowView {
                                     self.has_many_tabs
                                         .iter()
                                         .enumerate()
-                                        .map(|(index, tab)| {
+                                        .map(|(index, (_referring_columns, tab))| {
                                             a(
                                                 vec![
                                                     class("tab_links"),
@@ -212,7 +245,7 @@ impl WindowView {
                                     self.has_many_tabs
                                         .iter()
                                         .enumerate()
-                                        .map(|(index, tab)| {
+                                        .map(|(index, (_referring_columns, tab))| {
                                             TabView::view(tab).map_msg(move |tab_msg| {
                                                 Msg::HasManyTabMsg(index, tab_msg)
                                             })
@@ -263,12 +296,22 @@ impl WindowView {
                     tab_view
                 })
                 .collect(),
-            has_many_tabs: window.has_many_tabs.into_iter().map(TabView::new).collect(),
+            has_many_tabs: window
+                .has_many_tabs
+                .into_iter()
+                .map(|tab| (tab.referring_columns, TabView::new(tab.tab)))
+                .collect(),
             indirect_tabs: window
                 .indirect_tabs
                 .into_iter()
-                .map(|tab| (tab.linker, TabView::new(tab.tab)))
+                .map(|tab| {
+                    // the extra fields of the linker are shown alongside the indirect record
+                    let mut indirect = tab.tab;
+                    indirect.fields.extend(tab.linker_fields);
+                    (tab.linker, TabView::new(indirect))
+                })
                 .collect(),
+            tree_tabs: window.tree_tabs.into_iter().map(TreeView::new).collect(),
             is_visible: true,
             active_has_many_tab: Some(0),
             active_indirect_tab: None,
@@ -292,6 +335,39 @@ impl WindowView {
         }
     }
 
+    /// the columns referring to the record and the tab of the has_many table
+    pub fn has_many_tab(&self, index: usize) -> &(Vec<ColumnName>, TabView) {
+        &self.has_many_tabs[index]
+    }
+
+    /// the linker table and the tab of the indirect table
+    pub fn indirect_tab(&self, index: usize) -> &(TableName, TabView) {
+        &self.indirect_tabs[index]
+    }
+
+    pub fn tree_tab(&self, index: usize) -> &TreeView {
+        &self.tree_tabs[index]
+    }
+
+    pub fn tree_tab_mut(&mut self, index: usize) -> &mut TreeView {
+        &mut self.tree_tabs[index]
+    }
+
+    /// the number of the hierarchies of the main table
+    pub fn tree_tab_count(&self) -> usize {
+        self.tree_tabs.len()
+    }
+
+    /// the search in the quick find, None if it is blank
+    pub fn quick_find_search(&self) -> Option<String> {
+        let search = &self.toolbar_view.quick_find_search;
+        if search.trim().is_empty() {
+            None
+        } else {
+            Some(search.to_string())
+        }
+    }
+
     /// Important: set the data rows first before setting the frozen data
     pub fn set_window_data(&mut self, window_data: &WindowData) {
         trace!("In setting window data");
@@ -299,6 +375,7 @@ impl WindowView {
             sql_query,
             main_tab_data,
             main_tab_current_page,
+            main_tab_cursor: _,
             main_tab_total_rows,
 
             record_detail: _,
@@ -330,7 +407,9 @@ impl WindowView {
 
         trace!("Setting has_many");
         for (index, pages) in has_many_tab_data.into_iter().enumerate() {
-            self.has_many_tabs[index].set_pages(pages, 0, has_many_tab_total_rows[index]);
+            self.has_many_tabs[index]
+                .1
+                .set_pages(pages, 0, has_many_tab_total_rows[index]);
         }
 
         trace!("Setting indirect");
@@ -353,7 +432,7 @@ impl WindowView {
         self.has_many_tabs
             .iter_mut()
             .enumerate()
-            .for_each(|(index, tab)| {
+            .for_each(|(index, (_referring_columns, tab))| {
                 if active_has_many_tab == Some(index) {
                     tab.show();
                 } else {
@@ -490,7 +569,7 @@ impl WindowView {
             .for_each(|tab| tab.set_table_size(calculated_main_table_size));
         self.has_many_tabs
             .iter_mut()
-            .for_each(|tab| tab.set_table_size(calculated_related_tabs_size));
+            .for_each(|(_referring_columns, tab)| tab.set_table_size(calculated_related_tabs_size));
         self.indirect_tabs
             .iter_mut()
             .for_each(|(_table_name, tab)| tab.set_table_size(calculated_related_tabs_size));
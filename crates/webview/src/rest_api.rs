@@ -1,5 +1,9 @@
 use crate::app::{App, Msg};
-use diwata_intel::{data_container::QueryResult, Dao, RecordDetail, TableName};
+use diwata_intel::{
+    data_container::{Cursor, Lookup, QueryResult, Sort, TreeRecords},
+    filter::Filter,
+    ColumnName, Dao, RecordDetail, Rows, TableName,
+};
 use sauron::{Cmd, Http};
 use wasm_bindgen::JsValue;
 
@@ -30,15 +34,194 @@ where
     Http::fetch_with_text_response_decoder(&url, text_decoder, msg_receiver)
 }
 
+/// fetch the page of the window data that passes the filter,
+/// in the order of the sort. The rows after the cursor is fetched
+/// in place of the page number when there is a cursor
 pub fn fetch_window_data_next_page<F>(
     table_name: &TableName,
     page: usize,
+    cursor: Option<&Cursor>,
+    filter: &Filter,
+    sort: &Sort,
     msg_receiver: F,
 ) -> Cmd<App, Msg>
 where
     F: Fn(Result<QueryResult, JsValue>) -> Msg + Clone + 'static,
 {
-    let url = format!("/main_data/{}/page/{}", table_name.complete_name(), page);
+    let mut params = vec![];
+    if let Some(cursor) = cursor {
+        let cursor = ron::ser::to_string(cursor).expect("Unable to serialize cursor");
+        params.push(format!("cursor={}", js_sys::encode_uri_component(&cursor)));
+    }
+    params.extend(filter_and_sort_params(filter, sort));
+    let mut url = format!("/main_data/{}/page/{}", table_name.complete_name(), page);
+    if !params.is_empty() {
+        url += &format!("?{}", params.join("&"));
+    }
+    let text_decoder = |v: String| ron::de::from_str(&v).expect("Unable to decode ron data");
+    Http::fetch_with_text_response_decoder(&url, text_decoder, msg_receiver)
+}
+
+/// the query string parameters of the filter and the sort
+fn filter_and_sort_params(filter: &Filter, sort: &Sort) -> Vec<String> {
+    let mut params = vec![];
+    if !filter.is_empty() {
+        let filter = ron::ser::to_string(filter).expect("Unable to serialize filter");
+        params.push(format!("filter={}", js_sys::encode_uri_component(&filter)));
+    }
+    if !sort.orders.is_empty() {
+        params.push(format!("sort={}", sort));
+    }
+    params
+}
+
+/// fetch the page of the has_many records referring to the selected record
+/// through the referring columns
+#[allow(clippy::too_many_arguments)]
+pub fn fetch_has_many_records<F>(
+    table_name: &TableName,
+    dao: &Dao,
+    has_many_table: &TableName,
+    referring_columns: &[ColumnName],
+    page: usize,
+    filter: &Filter,
+    sort: &Sort,
+    msg_receiver: F,
+) -> Cmd<App, Msg>
+where
+    F: Fn(Result<Rows, JsValue>) -> Msg + Clone + 'static,
+{
+    let dao = ron::ser::to_string(dao).expect("Unable to serialize dao");
+    let via = referring_columns
+        .iter()
+        .map(|column| column.name.to_string())
+        .collect::<Vec<String>>()
+        .join(",");
+    let mut params = vec![
+        format!("dao={}", js_sys::encode_uri_component(&dao)),
+        format!("via={}", js_sys::encode_uri_component(&via)),
+    ];
+    params.extend(filter_and_sort_params(filter, sort));
+    let url = format!(
+        "/has_many_records/{}/{}/page/{}?{}",
+        table_name.complete_name(),
+        has_many_table.complete_name(),
+        page,
+        params.join("&")
+    );
+    let text_decoder = |v: String| ron::de::from_str(&v).expect("Unable to decode ron data");
+    Http::fetch_with_text_response_decoder(&url, text_decoder, msg_receiver)
+}
+
+/// fetch the page of the indirect records of the selected record
+#[allow(clippy::too_many_arguments)]
+pub fn fetch_indirect_records<F>(
+    table_name: &TableName,
+    dao: &Dao,
+    linker_table: &TableName,
+    indirect_table: &TableName,
+    page: usize,
+    filter: &Filter,
+    sort: &Sort,
+    msg_receiver: F,
+) -> Cmd<App, Msg>
+where
+    F: Fn(Result<Rows, JsValue>) -> Msg + Clone + 'static,
+{
+    let dao = ron::ser::to_string(dao).expect("Unable to serialize dao");
+    let mut params = vec![format!("dao={}", js_sys::encode_uri_component(&dao))];
+    params.extend(filter_and_sort_params(filter, sort));
+    let url = format!(
+        "/indirect_records/{}/{}/{}/page/{}?{}",
+        table_name.complete_name(),
+        linker_table.complete_name(),
+        indirect_table.complete_name(),
+        page,
+        params.join("&")
+    );
+    let text_decoder = |v: String| ron::de::from_str(&v).expect("Unable to decode ron data");
+    Http::fetch_with_text_response_decoder(&url, text_decoder, msg_receiver)
+}
+
+/// fetch the ancestors and the page of the children of the record in the hierarchy
+/// formed by the parent columns, the roots are fetched when there is no record
+pub fn fetch_tree_records<F>(
+    table_name: &TableName,
+    parent_columns: &[ColumnName],
+    dao: Option<&Dao>,
+    page: usize,
+    msg_receiver: F,
+) -> Cmd<App, Msg>
+where
+    F: Fn(Result<TreeRecords, JsValue>) -> Msg + Clone + 'static,
+{
+    let parent_columns = parent_columns
+        .iter()
+        .map(|column| column.name.to_string())
+        .collect::<Vec<String>>()
+        .join(",");
+    let mut url = format!(
+        "/tree_records/{}/{}/page/{}",
+        table_name.complete_name(),
+        parent_columns,
+        page
+    );
+    if let Some(dao) = dao {
+        let dao = ron::ser::to_string(dao).expect("Unable to serialize dao");
+        url += &format!("?dao={}", js_sys::encode_uri_component(&dao));
+    }
+    let text_decoder = |v: String| ron::de::from_str(&v).expect("Unable to decode ron data");
+    Http::fetch_with_text_response_decoder(&url, text_decoder, msg_receiver)
+}
+
+/// fetch the page of the window data that matches the quick find search
+pub fn fetch_quick_find<F>(
+    table_name: &TableName,
+    search: &str,
+    page: usize,
+    msg_receiver: F,
+) -> Cmd<App, Msg>
+where
+    F: Fn(Result<QueryResult, JsValue>) -> Msg + Clone + 'static,
+{
+    let url = format!(
+        "/quick_find/{}/page/{}?search={}",
+        table_name.complete_name(),
+        page,
+        js_sys::encode_uri_component(search)
+    );
+    let text_decoder = |v: String| ron::de::from_str(&v).expect("Unable to decode ron data");
+    Http::fetch_with_text_response_decoder(&url, text_decoder, msg_receiver)
+}
+
+/// fetch the page of options of a foreign key field from its source table,
+/// used by the foreign key editors and when linking existing indirect records
+#[allow(unused)]
+pub fn fetch_lookup<F>(
+    source: &TableName,
+    search: &str,
+    page: usize,
+    msg_receiver: F,
+) -> Cmd<App, Msg>
+where
+    F: Fn(Result<Lookup, JsValue>) -> Msg + Clone + 'static,
+{
+    let url = format!(
+        "/lookup/{}/page/{}?search={}",
+        source.complete_name(),
+        page,
+        js_sys::encode_uri_component(search)
+    );
+    let text_decoder = |v: String| ron::de::from_str(&v).expect("Unable to decode ron data");
+    Http::fetch_with_text_response_decoder(&url, text_decoder, msg_receiver)
+}
+
+/// the audit log of the changes made to this table, opened as a window
+pub fn fetch_audit_log<F>(table_name: &TableName, msg_receiver: F) -> Cmd<App, Msg>
+where
+    F: Fn(Result<QueryResult, JsValue>) -> Msg + Clone + 'static,
+{
+    let url = format!("/audit_log/{}/page/1", table_name.complete_name());
     let text_decoder = |v: String| ron::de::from_str(&v).expect("Unable to decode ron data");
     Http::fetch_with_text_response_decoder(&url, text_decoder, msg_receiver)
 }